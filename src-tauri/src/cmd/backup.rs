@@ -1,6 +1,6 @@
 use super::CmdResult;
-use crate::{cmd::StringifyErr as _, feat};
-use feat::LocalBackupFile;
+use crate::{cmd::StringifyErr as _, core::backup_target::BackupFileInfo, feat};
+use feat::{LocalBackupFile, RestoreOptions, RestorePlan, RetentionPreview};
 use smartstring::alias::String;
 
 /// Create a local backup
@@ -21,10 +21,49 @@ pub async fn delete_local_backup(filename: String) -> CmdResult<()> {
     feat::delete_local_backup(filename).await.stringify_err()
 }
 
-/// Restore local backup
+/// Preview which files restoring a local backup would add, overwrite or leave alone
 #[tauri::command]
-pub async fn restore_local_backup(filename: String) -> CmdResult<()> {
-    feat::restore_local_backup(filename).await.stringify_err()
+pub async fn preview_local_backup_restore(
+    filename: String,
+    passphrase: Option<String>,
+    options: Option<RestoreOptions>,
+) -> CmdResult<RestorePlan> {
+    feat::preview_local_backup_restore(
+        filename,
+        passphrase,
+        options.unwrap_or_else(RestoreOptions::all),
+    )
+    .await
+    .stringify_err()
+}
+
+/// Restore local backup, `passphrase` is required for encrypted backups
+/// and everything is restored when `options` is omitted
+#[tauri::command]
+pub async fn restore_local_backup(
+    filename: String,
+    passphrase: Option<String>,
+    options: Option<RestoreOptions>,
+) -> CmdResult<RestorePlan> {
+    feat::restore_local_backup(
+        filename,
+        passphrase,
+        options.unwrap_or_else(RestoreOptions::all),
+    )
+    .await
+    .stringify_err()
+}
+
+/// List the local and WebDAV backups the retention policy would delete
+#[tauri::command]
+pub async fn preview_backup_retention() -> CmdResult<RetentionPreview> {
+    feat::preview_backup_retention().await.stringify_err()
+}
+
+/// Delete the local and WebDAV backups that fall outside the retention policy
+#[tauri::command]
+pub async fn apply_backup_retention() -> CmdResult<RetentionPreview> {
+    feat::apply_backup_retention().await.stringify_err()
 }
 
 /// Export local backup to a user selected destination
@@ -34,3 +73,59 @@ pub async fn export_local_backup(filename: String, destination: String) -> CmdRe
         .await
         .stringify_err()
 }
+
+/// Create a backup and push it to a configured backup target
+#[tauri::command]
+pub async fn create_target_backup(uid: String) -> CmdResult<()> {
+    feat::create_target_backup(uid).await.stringify_err()
+}
+
+/// List backups on a configured backup target
+#[tauri::command]
+pub async fn list_target_backup(uid: String) -> CmdResult<Vec<BackupFileInfo>> {
+    feat::list_target_backup(uid).await.stringify_err()
+}
+
+/// Delete a backup from a configured backup target
+#[tauri::command]
+pub async fn delete_target_backup(uid: String, filename: String) -> CmdResult<()> {
+    feat::delete_target_backup(uid, filename)
+        .await
+        .stringify_err()
+}
+
+/// Preview which files restoring a backup from a configured backup target would change
+#[tauri::command]
+pub async fn preview_target_backup_restore(
+    uid: String,
+    filename: String,
+    passphrase: Option<String>,
+    options: Option<RestoreOptions>,
+) -> CmdResult<RestorePlan> {
+    feat::preview_target_backup_restore(
+        uid,
+        filename,
+        passphrase,
+        options.unwrap_or_else(RestoreOptions::all),
+    )
+    .await
+    .stringify_err()
+}
+
+/// Restore a backup from a configured backup target
+#[tauri::command]
+pub async fn restore_target_backup(
+    uid: String,
+    filename: String,
+    passphrase: Option<String>,
+    options: Option<RestoreOptions>,
+) -> CmdResult<RestorePlan> {
+    feat::restore_target_backup(
+        uid,
+        filename,
+        passphrase,
+        options.unwrap_or_else(RestoreOptions::all),
+    )
+    .await
+    .stringify_err()
+}
@@ -4,7 +4,9 @@ use crate::{
     cmd::StringifyErr as _,
     config::{ClashInfo, Config},
     constants,
-    core::{CoreManager, handle, validate::CoreConfigValidator},
+    core::{
+        CoreManager, handle, validate::CoreConfigValidator, validation_report::ValidationReport,
+    },
 };
 use crate::{feat, logging, utils::logging::Type};
 use compact_str::CompactString;
@@ -278,15 +280,12 @@ pub async fn get_dns_config_content() -> CmdResult<String> {
 
 /// 验证DNS配置文件
 #[tauri::command]
-pub async fn validate_dns_config() -> CmdResult<(bool, String)> {
+pub async fn validate_dns_config() -> CmdResult<ValidationReport> {
     let app_dir = dirs::app_home_dir().stringify_err()?;
     let dns_path = app_dir.join(constants::files::DNS_CONFIG);
     let dns_path_str = dns_path.to_str().unwrap_or_default();
 
-    if !dns_path.exists() {
-        return Ok((false, "DNS config file not found".into()));
-    }
-
+    // 文件不存在时报告 file_not_found
     CoreConfigValidator::validate_config_file(dns_path_str, None)
         .await
         .stringify_err()
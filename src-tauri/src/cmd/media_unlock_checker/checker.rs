@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use reqwest::Client;
+
+use crate::config::Config;
+
+use super::{
+    UnlockItem,
+    bahamut::check_bahamut_anime,
+    bilibili::{check_bilibili_china_mainland, check_bilibili_hk_mc_tw},
+    chatgpt::check_chatgpt_combined,
+    claude::check_claude,
+    custom::CustomChecker,
+    disney_plus::check_disney_plus,
+    gemini::check_gemini,
+    netflix::check_netflix,
+    prime_video::check_prime_video,
+    spotify::check_spotify,
+    tiktok::check_tiktok,
+    youtube::check_youtube_premium,
+};
+
+/// A service whose availability is checked through the current route
+#[async_trait]
+pub trait UnlockChecker: Send + Sync {
+    /// The names of the items `check` reports, in order
+    fn names(&self) -> Vec<String>;
+
+    async fn check(&self, client: &Client) -> Vec<UnlockItem>;
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Builtin {
+    BilibiliChinaMainland,
+    BilibiliHkMcTw,
+    ChatGpt,
+    Claude,
+    Gemini,
+    YoutubePremium,
+    BahamutAnime,
+    Netflix,
+    DisneyPlus,
+    PrimeVideo,
+    Spotify,
+    TikTok,
+}
+
+impl Builtin {
+    const ALL: [Self; 12] = [
+        Self::BilibiliChinaMainland,
+        Self::BilibiliHkMcTw,
+        Self::ChatGpt,
+        Self::Claude,
+        Self::Gemini,
+        Self::YoutubePremium,
+        Self::BahamutAnime,
+        Self::Netflix,
+        Self::DisneyPlus,
+        Self::PrimeVideo,
+        Self::Spotify,
+        Self::TikTok,
+    ];
+}
+
+#[async_trait]
+impl UnlockChecker for Builtin {
+    fn names(&self) -> Vec<String> {
+        let names: &[&str] = match self {
+            Self::BilibiliChinaMainland => &["哔哩哔哩大陆"],
+            Self::BilibiliHkMcTw => &["哔哩哔哩港澳台"],
+            Self::ChatGpt => &["ChatGPT iOS", "ChatGPT Web"],
+            Self::Claude => &["Claude"],
+            Self::Gemini => &["Gemini"],
+            Self::YoutubePremium => &["Youtube Premium"],
+            Self::BahamutAnime => &["Bahamut Anime"],
+            Self::Netflix => &["Netflix"],
+            Self::DisneyPlus => &["Disney+"],
+            Self::PrimeVideo => &["Prime Video"],
+            Self::Spotify => &["Spotify"],
+            Self::TikTok => &["TikTok"],
+        };
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    async fn check(&self, client: &Client) -> Vec<UnlockItem> {
+        match self {
+            Self::BilibiliChinaMainland => vec![check_bilibili_china_mainland(client).await],
+            Self::BilibiliHkMcTw => vec![check_bilibili_hk_mc_tw(client).await],
+            Self::ChatGpt => check_chatgpt_combined(client).await,
+            Self::Claude => vec![check_claude(client).await],
+            Self::Gemini => vec![check_gemini(client).await],
+            Self::YoutubePremium => vec![check_youtube_premium(client).await],
+            Self::BahamutAnime => vec![check_bahamut_anime(client).await],
+            Self::Netflix => vec![check_netflix(client).await],
+            Self::DisneyPlus => vec![check_disney_plus(client).await],
+            Self::PrimeVideo => vec![check_prime_video(client).await],
+            Self::Spotify => vec![check_spotify(client).await],
+            Self::TikTok => vec![check_tiktok(client).await],
+        }
+    }
+}
+
+/// The built-in checks followed by the custom checks configured in `unlock_checks`
+pub async fn registry() -> Vec<Arc<dyn UnlockChecker>> {
+    let custom = Config::verge()
+        .await
+        .latest_arc()
+        .unlock_checks
+        .clone()
+        .unwrap_or_default();
+
+    let mut checkers: Vec<Arc<dyn UnlockChecker>> = Builtin::ALL
+        .into_iter()
+        .map(|builtin| Arc::new(builtin) as Arc<dyn UnlockChecker>)
+        .collect();
+    checkers.extend(
+        custom
+            .iter()
+            .map(|config| Arc::new(CustomChecker::new(config)) as Arc<dyn UnlockChecker>),
+    );
+    checkers
+}
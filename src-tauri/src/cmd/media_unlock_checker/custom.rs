@@ -0,0 +1,241 @@
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::{
+    Client, Method,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+
+use crate::{config::IUnlockCheck, logging, utils::logging::Type};
+
+use super::{
+    UnlockItem,
+    checker::UnlockChecker,
+    utils::{country_code_to_emoji, get_local_date_string},
+};
+
+/// A check declared in `IVerge::unlock_checks`
+pub struct CustomChecker {
+    name: String,
+    /// the error of an invalid configuration, reported when the check runs
+    request: Result<CustomRequest, String>,
+}
+
+struct CustomRequest {
+    url: String,
+    method: Method,
+    headers: HeaderMap,
+    expected_status: Vec<u16>,
+    expected_body: Option<Regex>,
+    region: Option<Regex>,
+}
+
+impl CustomChecker {
+    pub fn new(config: &IUnlockCheck) -> Self {
+        let name = config
+            .name
+            .as_deref()
+            .or(config.url.as_deref())
+            .unwrap_or("Custom")
+            .to_string();
+        Self {
+            name,
+            request: CustomRequest::new(config),
+        }
+    }
+}
+
+impl CustomRequest {
+    fn new(config: &IUnlockCheck) -> Result<Self, String> {
+        let url = config
+            .url
+            .as_deref()
+            .filter(|url| !url.is_empty())
+            .ok_or("缺少检测地址")?
+            .to_string();
+        let method = config.method.as_deref().unwrap_or("GET").to_uppercase();
+        let method = Method::from_bytes(method.as_bytes())
+            .map_err(|_| format!("无效的请求方法: {method}"))?;
+
+        let mut headers = HeaderMap::new();
+        for (key, value) in config.headers.iter().flatten() {
+            let key = HeaderName::from_bytes(key.as_bytes())
+                .map_err(|_| format!("无效的请求头: {key}"))?;
+            let value =
+                HeaderValue::from_str(value).map_err(|_| format!("无效的请求头的值: {value}"))?;
+            headers.insert(key, value);
+        }
+
+        let regex = |pattern: Option<&str>| {
+            pattern
+                .filter(|pattern| !pattern.is_empty())
+                .map(Regex::new)
+                .transpose()
+                .map_err(|e| format!("无效的正则表达式: {e}"))
+        };
+
+        Ok(Self {
+            url,
+            method,
+            headers,
+            expected_status: config.expected_status.clone().unwrap_or_default(),
+            expected_body: regex(config.expected_body.as_deref())?,
+            region: regex(config.region_regex.as_deref())?,
+        })
+    }
+
+    /// Whether the service is unlocked, and the region it reports
+    async fn run(&self, client: &Client) -> Result<(bool, Option<String>), String> {
+        let response = client
+            .request(self.method.clone(), &self.url)
+            .headers(self.headers.clone())
+            .send()
+            .await
+            .map_err(|e| format!("请求失败: {e}"))?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("读取响应失败: {e}"))?;
+
+        let status_ok = if self.expected_status.is_empty() {
+            status.is_success()
+        } else {
+            self.expected_status.contains(&status.as_u16())
+        };
+        let body_ok = self
+            .expected_body
+            .as_ref()
+            .is_none_or(|expected| expected.is_match(&body));
+        let region = self.region.as_ref().and_then(|regex| {
+            let captures = regex.captures(&body)?;
+            let region = captures.get(1).or_else(|| captures.get(0))?.as_str().trim();
+            Some(format_region(region))
+        });
+        Ok((status_ok && body_ok, region))
+    }
+}
+
+/// Country codes get their flag like the built-in checks
+fn format_region(region: &str) -> String {
+    if region.len() == 2 && region.chars().all(|c| c.is_ascii_alphabetic()) {
+        let code = region.to_uppercase();
+        format!("{}{code}", country_code_to_emoji(&code))
+    } else {
+        region.to_string()
+    }
+}
+
+#[async_trait]
+impl UnlockChecker for CustomChecker {
+    fn names(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+
+    async fn check(&self, client: &Client) -> Vec<UnlockItem> {
+        let result = match &self.request {
+            Ok(request) => request.run(client).await,
+            Err(e) => Err(e.clone()),
+        };
+        let (status, region) = match result {
+            Ok((true, region)) => ("Yes", region),
+            Ok((false, region)) => ("No", region),
+            Err(e) => {
+                logging!(
+                    warn,
+                    Type::Network,
+                    "Warning: 自定义检测 {} 失败: {e}",
+                    self.name
+                );
+                ("Failed", None)
+            }
+        };
+        vec![UnlockItem {
+            name: self.name.clone(),
+            status: status.to_string(),
+            region,
+            check_time: Some(get_local_date_string()),
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Read as _, Write as _},
+        net::TcpListener,
+    };
+
+    /// Answers each connection by path: `/ok` with a 200 trace body, anything else with a 403
+    #[allow(clippy::unwrap_used)]
+    fn mock_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buffer = [0; 4096];
+                let read = stream.read(&mut buffer).unwrap_or(0);
+                let request = std::string::String::from_utf8_lossy(&buffer[..read]);
+                let (status, body) = if request.starts_with("GET /ok ") {
+                    ("200 OK", "ip=1.2.3.4\nloc=jp\nservice=available\n")
+                } else {
+                    ("403 Forbidden", "not available in your region")
+                };
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        format!("http://{address}")
+    }
+
+    async fn check(config: IUnlockCheck) -> UnlockItem {
+        #[allow(clippy::unwrap_used)]
+        let client = Client::builder().no_proxy().build().unwrap();
+        let mut items = CustomChecker::new(&config).check(&client).await;
+        assert_eq!(items.len(), 1);
+        items.remove(0)
+    }
+
+    #[tokio::test]
+    async fn test_custom_check() {
+        let server = mock_server();
+        let config = |path: &str| IUnlockCheck {
+            name: Some("Internal".into()),
+            url: Some(format!("{server}{path}").into()),
+            region_regex: Some(r"loc=(\w+)".into()),
+            ..IUnlockCheck::default()
+        };
+
+        let item = check(config("/ok")).await;
+        assert_eq!(item.status, "Yes");
+        assert_eq!(item.region.as_deref(), Some("🇯🇵JP"));
+
+        let item = check(IUnlockCheck {
+            expected_body: Some("service=blocked".into()),
+            ..config("/ok")
+        })
+        .await;
+        assert_eq!(item.status, "No");
+
+        assert_eq!(check(config("/blocked")).await.status, "No");
+        let item = check(IUnlockCheck {
+            expected_status: Some(vec![403]),
+            expected_body: Some("not available".into()),
+            ..config("/blocked")
+        })
+        .await;
+        assert_eq!(item.status, "Yes");
+        assert_eq!(item.region, None);
+
+        let item = check(IUnlockCheck {
+            region_regex: Some("(".into()),
+            ..config("/ok")
+        })
+        .await;
+        assert_eq!(item.status, "Failed");
+        assert_eq!(check(IUnlockCheck::default()).await.name, "Custom");
+    }
+}
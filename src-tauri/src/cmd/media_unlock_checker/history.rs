@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context as _, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tauri::command;
+use tokio::{fs, sync::Mutex};
+
+use crate::{
+    config::Config,
+    logging,
+    utils::{
+        dirs,
+        logging::Type,
+        notification::{NotificationEvent, notify_event},
+    },
+};
+
+use super::UnlockItem;
+
+/// The node the checks of the current route are recorded under.
+/// The route follows whichever node is selected, so its changes are never alerted on
+pub const CURRENT_ROUTE: &str = "";
+
+const DEFAULT_HISTORY_DAYS: u32 = 30;
+
+/// Serializes the read-modify-write of the history file
+static HISTORY_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// A result that held from `since` until at least `last`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnlockRecord {
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// seconds since epoch
+    pub since: i64,
+    pub last: i64,
+}
+
+impl UnlockRecord {
+    fn is_unlocked(&self) -> bool {
+        self.status.starts_with("Yes")
+    }
+
+    fn same_result(&self, item: &UnlockItem) -> bool {
+        self.status == item.status && self.region == item.region
+    }
+}
+
+/// The history of one check on one node
+#[derive(Debug, Clone, Serialize)]
+pub struct UnlockHistoryEntry {
+    pub node: String,
+    pub name: String,
+    pub records: Vec<UnlockRecord>,
+}
+
+/// A change worth an alert
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnlockChange {
+    Lost {
+        node: String,
+        name: String,
+        status: String,
+    },
+    RegionChanged {
+        node: String,
+        name: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// `node -> check -> records`, oldest first.
+/// A record is only added when the result changes, so repeated checks stay compact
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UnlockHistory(BTreeMap<String, BTreeMap<String, Vec<UnlockRecord>>>);
+
+impl UnlockHistory {
+    /// Add the results of `items` on `node`, returning the changes to alert on.
+    /// Failed and pending checks say nothing about the service and are skipped.
+    /// A different result on [`CURRENT_ROUTE`] may just be another node, so it is no change
+    pub fn record(&mut self, node: &str, items: &[UnlockItem], now: i64) -> Vec<UnlockChange> {
+        let checks = self.0.entry(node.to_string()).or_default();
+        let mut changes = Vec::new();
+        for item in items {
+            if item.status == "Pending" || item.status.starts_with("Failed") {
+                continue;
+            }
+            let records = checks.entry(item.name.clone()).or_default();
+            if let Some(last) = records.last_mut() {
+                if last.same_result(item) {
+                    last.last = now;
+                    continue;
+                }
+                if node != CURRENT_ROUTE
+                    && let Some(change) = Self::change(node, last, item)
+                {
+                    changes.push(change);
+                }
+            }
+            records.push(UnlockRecord {
+                status: item.status.clone(),
+                region: item.region.clone(),
+                since: now,
+                last: now,
+            });
+        }
+        changes
+    }
+
+    fn change(node: &str, previous: &UnlockRecord, item: &UnlockItem) -> Option<UnlockChange> {
+        if !previous.is_unlocked() {
+            return None;
+        }
+        if !item.status.starts_with("Yes") {
+            return Some(UnlockChange::Lost {
+                node: node.to_string(),
+                name: item.name.clone(),
+                status: item.status.clone(),
+            });
+        }
+        match (&previous.region, &item.region) {
+            (Some(from), Some(to)) if from != to => Some(UnlockChange::RegionChanged {
+                node: node.to_string(),
+                name: item.name.clone(),
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Drop the records last seen before `cutoff`
+    pub fn prune(&mut self, cutoff: i64) {
+        for checks in self.0.values_mut() {
+            for records in checks.values_mut() {
+                records.retain(|record| record.last >= cutoff);
+            }
+            checks.retain(|_, records| !records.is_empty());
+        }
+        self.0.retain(|_, checks| !checks.is_empty());
+    }
+
+    pub fn entries(&self, node: Option<&str>, name: Option<&str>) -> Vec<UnlockHistoryEntry> {
+        self.0
+            .iter()
+            .filter(|(n, _)| node.is_none_or(|node| node == n.as_str()))
+            .flat_map(|(n, checks)| {
+                checks
+                    .iter()
+                    .filter(|(c, _)| name.is_none_or(|name| name == c.as_str()))
+                    .map(|(c, records)| UnlockHistoryEntry {
+                        node: n.clone(),
+                        name: c.clone(),
+                        records: records.clone(),
+                    })
+            })
+            .collect()
+    }
+
+    /// A file that can't be parsed is moved aside to `*.corrupt` and an empty history returned
+    async fn load() -> Result<Self> {
+        let path = dirs::unlock_history_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = fs::read(&path)
+            .await
+            .with_context(|| format!("failed to read \"{}\"", path.display()))?;
+        match serde_json::from_slice(&data) {
+            Ok(history) => Ok(history),
+            Err(e) => {
+                let corrupt = path.with_extension("json.corrupt");
+                logging!(
+                    error,
+                    Type::Network,
+                    "解锁历史无法解析, 已移至 {}: {e}",
+                    corrupt.display()
+                );
+                fs::rename(&path, &corrupt)
+                    .await
+                    .with_context(|| format!("failed to move \"{}\"", path.display()))?;
+                Ok(Self::default())
+            }
+        }
+    }
+
+    /// Written to a temp file first so an interrupted write never leaves a partial history
+    async fn save(&self) -> Result<()> {
+        let path = dirs::unlock_history_path()?;
+        let temp = path.with_extension("json.tmp");
+        let data = serde_json::to_vec(self)?;
+        fs::write(&temp, data)
+            .await
+            .with_context(|| format!("failed to write \"{}\"", temp.display()))?;
+        fs::rename(&temp, &path)
+            .await
+            .with_context(|| format!("failed to replace \"{}\"", path.display()))
+    }
+}
+
+/// Store the results of a check on `node` and alert on the services it lost
+pub async fn record_results(node: &str, items: &[UnlockItem]) {
+    let (days, alert) = {
+        let verge = Config::verge().await.latest_arc();
+        (
+            verge.unlock_history_days.unwrap_or(DEFAULT_HISTORY_DAYS),
+            verge.enable_unlock_alert.unwrap_or(true),
+        )
+    };
+
+    let changes = {
+        let _guard = HISTORY_LOCK.lock().await;
+        let mut history = match UnlockHistory::load().await {
+            Ok(history) => history,
+            Err(e) => {
+                // 不覆盖无法读取的历史
+                logging!(warn, Type::Network, "Warning: 读取解锁历史失败: {e}");
+                return;
+            }
+        };
+        let now = Local::now().timestamp();
+        let changes = history.record(node, items, now);
+        history.prune(now - i64::from(days) * 24 * 60 * 60);
+        if let Err(e) = history.save().await {
+            logging!(warn, Type::Network, "Warning: 保存解锁历史失败: {e}");
+        }
+        changes
+    };
+
+    if alert {
+        for change in changes {
+            notify_change(change).await;
+        }
+    }
+}
+
+async fn notify_change(change: UnlockChange) {
+    let target = |node: &str, name: &str| {
+        if node == CURRENT_ROUTE {
+            name.to_string()
+        } else {
+            format!("{name} @ {node}")
+        }
+    };
+    match change {
+        UnlockChange::Lost { node, name, status } => {
+            logging!(
+                info,
+                Type::Network,
+                "{name} 在节点 {node} 上不再解锁: {status}"
+            );
+            notify_event(NotificationEvent::UnlockLost {
+                target: &target(&node, &name),
+                status: &status,
+            })
+            .await;
+        }
+        UnlockChange::RegionChanged {
+            node,
+            name,
+            from,
+            to,
+        } => {
+            logging!(
+                info,
+                Type::Network,
+                "{name} 在节点 {node} 上的区域变为 {to}"
+            );
+            notify_event(NotificationEvent::UnlockRegionChanged {
+                target: &target(&node, &name),
+                from: &from,
+                to: &to,
+            })
+            .await;
+        }
+    }
+}
+
+/// The stored results, optionally only of `node` and of the check `name`.
+/// The checks of the current route are under the empty node
+#[command]
+pub async fn get_unlock_history(
+    node: Option<String>,
+    name: Option<String>,
+) -> Result<Vec<UnlockHistoryEntry>, String> {
+    let _guard = HISTORY_LOCK.lock().await;
+    let history = UnlockHistory::load()
+        .await
+        .map_err(|e| format!("读取解锁历史失败: {e}"))?;
+    Ok(history.entries(node.as_deref(), name.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, status: &str, region: Option<&str>) -> UnlockItem {
+        UnlockItem {
+            name: name.to_string(),
+            status: status.to_string(),
+            region: region.map(ToString::to_string),
+            check_time: None,
+        }
+    }
+
+    #[test]
+    fn test_record_and_prune() {
+        let mut history = UnlockHistory::default();
+        let first = [
+            item("Netflix", "Yes", Some("JP")),
+            item("Claude", "No", None),
+        ];
+        assert!(history.record("node", &first, 100).is_empty());
+        assert!(history.record("node", &first, 200).is_empty());
+        assert_eq!(
+            history.entries(Some("node"), Some("Netflix"))[0]
+                .records
+                .len(),
+            1
+        );
+
+        // 检测失败不代表服务被封锁
+        let failed = [item("Netflix", "Failed (Network Connection)", None)];
+        assert!(history.record("node", &failed, 300).is_empty());
+
+        let changes = history.record(
+            "node",
+            &[
+                item("Netflix", "Yes", Some("US")),
+                item("Claude", "Yes", None),
+            ],
+            400,
+        );
+        assert_eq!(
+            changes,
+            [UnlockChange::RegionChanged {
+                node: "node".into(),
+                name: "Netflix".into(),
+                from: "JP".into(),
+                to: "US".into(),
+            }]
+        );
+
+        let changes = history.record("node", &[item("Claude", "No", None)], 500);
+        assert_eq!(
+            changes,
+            [UnlockChange::Lost {
+                node: "node".into(),
+                name: "Claude".into(),
+                status: "No".into(),
+            }]
+        );
+        assert_eq!(history.entries(None, Some("Claude"))[0].records.len(), 3);
+
+        history.prune(300);
+        let records = &history.entries(None, Some("Netflix"))[0].records;
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].region.as_deref(), Some("US"));
+        history.prune(1000);
+        assert!(history.entries(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_current_route_never_alerts() {
+        let mut history = UnlockHistory::default();
+        let jp = [item("Netflix", "Yes", Some("JP"))];
+        assert!(history.record(CURRENT_ROUTE, &jp, 100).is_empty());
+        let us = [item("Netflix", "Yes", Some("US"))];
+        assert!(history.record(CURRENT_ROUTE, &us, 200).is_empty());
+        let lost = [item("Netflix", "No", None)];
+        assert!(history.record(CURRENT_ROUTE, &lost, 300).is_empty());
+        assert_eq!(
+            history.entries(Some(CURRENT_ROUTE), None)[0].records.len(),
+            3
+        );
+    }
+}
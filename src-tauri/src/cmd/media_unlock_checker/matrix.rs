@@ -0,0 +1,188 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use serde::Serialize;
+use tauri::{Emitter as _, command};
+
+use crate::{
+    core::{
+        handle,
+        node_route::{NODE_ROUTE_GROUP, NodeRoute},
+    },
+    logging,
+    utils::logging::Type,
+};
+
+use super::{
+    UnlockItem, build_client, checker, history, item_names, run_checks,
+    utils::get_local_date_string,
+};
+
+const DEFAULT_CONCURRENCY: usize = 4;
+const PROGRESS_EVENT: &str = "verge://media-unlock-progress";
+
+/// The unlock results of one node of the group
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeUnlock {
+    pub node: String,
+    pub items: Vec<UnlockItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct UnlockProgress {
+    group: String,
+    node: String,
+    item: UnlockItem,
+    completed: usize,
+    total: usize,
+}
+
+/// Emits one progress event per checked cell of the matrix
+#[derive(Clone)]
+struct Progress {
+    group: String,
+    completed: Arc<AtomicUsize>,
+    total: usize,
+}
+
+impl Progress {
+    fn report(&self, node: &str, item: &UnlockItem) {
+        let completed = self.completed.fetch_add(1, Ordering::Relaxed) + 1;
+        let payload = UnlockProgress {
+            group: self.group.clone(),
+            node: node.to_string(),
+            item: item.clone(),
+            completed,
+            total: self.total,
+        };
+        if let Err(e) = handle::Handle::app_handle().emit(PROGRESS_EVENT, payload) {
+            logging!(warn, Type::Network, "Warning: 发送解锁检测进度失败: {e}");
+        }
+    }
+}
+
+/// Run every check for each node of `group`.
+///
+/// The nodes are selected one after another in the hidden [`NODE_ROUTE_GROUP`], whose
+/// listener routes the checks without the rules, so the selection of `group` is left alone
+#[command]
+pub async fn check_media_unlock_matrix(
+    group: String,
+    concurrency: Option<usize>,
+) -> Result<Vec<NodeUnlock>, String> {
+    let route = NodeRoute::open()
+        .await
+        .map_err(|e| format!("准备节点路由失败: {e}"))?;
+    run_matrix(&route, group, concurrency).await
+}
+
+/// Like [`check_media_unlock_matrix`], but gives up when the node route is in use
+pub(super) async fn try_check_media_unlock_matrix(
+    group: String,
+) -> Option<Result<Vec<NodeUnlock>, String>> {
+    let route = match NodeRoute::try_open().await? {
+        Ok(route) => route,
+        Err(e) => return Some(Err(format!("准备节点路由失败: {e}"))),
+    };
+    Some(run_matrix(&route, group, None).await)
+}
+
+async fn run_matrix(
+    route: &NodeRoute,
+    group: String,
+    concurrency: Option<usize>,
+) -> Result<Vec<NodeUnlock>, String> {
+    let nodes = group_nodes(&group).await?;
+    let proxy = route.proxy();
+    let concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+    let checkers = checker::registry().await;
+    let names = item_names(&checkers);
+    let progress = Progress {
+        group: group.clone(),
+        completed: Arc::new(AtomicUsize::new(0)),
+        total: nodes.len() * names.len(),
+    };
+
+    let mut matrix = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        // 每个节点使用新的客户端，避免复用经过上一个节点建立的连接
+        let selected = route
+            .select(&node)
+            .await
+            .map_err(|e| format!("切换节点失败: {e}"))
+            .and_then(|()| build_client(Some(&proxy)));
+        let items = match selected {
+            Ok(client) => {
+                let progress = progress.clone();
+                let reporter = node.clone();
+                let on_item = Arc::new(move |item: &UnlockItem| progress.report(&reporter, item));
+                run_checks(Arc::new(client), &checkers, concurrency, on_item).await
+            }
+            Err(e) => {
+                logging!(
+                    warn,
+                    Type::Network,
+                    "Warning: 节点 {node} 解锁检测失败: {e}"
+                );
+                failed_items(&names, &node, &progress)
+            }
+        };
+        history::record_results(&node, &items).await;
+        matrix.push(NodeUnlock { node, items });
+    }
+
+    Ok(matrix)
+}
+
+/// The nodes of `group` that the node route can select
+async fn group_nodes(group: &str) -> Result<Vec<String>, String> {
+    let proxies = handle::Handle::mihomo()
+        .await
+        .get_proxies()
+        .await
+        .map_err(|e| format!("获取代理组失败: {e:?}"))?;
+    let group_data = proxies
+        .proxies
+        .get(group)
+        .ok_or_else(|| format!("代理组不存在: {group}"))?;
+    let selectable: HashSet<String> = proxies
+        .proxies
+        .get(NODE_ROUTE_GROUP)
+        .ok_or_else(|| "节点路由未加载".to_string())?
+        .all
+        .iter()
+        .flatten()
+        .map(ToString::to_string)
+        .collect();
+    let nodes: Vec<String> = group_data
+        .all
+        .iter()
+        .flatten()
+        .map(ToString::to_string)
+        .filter(|node| selectable.contains(node))
+        .collect();
+    if nodes.is_empty() {
+        return Err(format!("代理组 {group} 中没有可检测的节点"));
+    }
+    Ok(nodes)
+}
+
+fn failed_items(names: &[String], node: &str, progress: &Progress) -> Vec<UnlockItem> {
+    names
+        .iter()
+        .map(|name| {
+            let item = UnlockItem {
+                status: "Failed".to_string(),
+                check_time: Some(get_local_date_string()),
+                ..UnlockItem::pending(name)
+            };
+            progress.report(node, &item);
+            item
+        })
+        .collect()
+}
@@ -1,17 +1,21 @@
 use std::sync::Arc;
 
-use reqwest::Client;
+use reqwest::{Client, Proxy};
 use tauri::command;
-use tokio::{sync::Mutex, task::JoinSet};
+use tokio::{sync::Semaphore, task::JoinSet};
 
-use crate::{logging, utils::logging::Type};
+use crate::{config::Config, logging, utils::logging::Type};
 
 mod bahamut;
 mod bilibili;
 mod chatgpt;
+mod checker;
 mod claude;
+mod custom;
 mod disney_plus;
 mod gemini;
+mod history;
+mod matrix;
 mod netflix;
 mod prime_video;
 mod spotify;
@@ -20,169 +24,119 @@ mod types;
 mod utils;
 mod youtube;
 
+pub use checker::UnlockChecker;
+pub use history::get_unlock_history;
+pub use matrix::check_media_unlock_matrix;
 pub use types::UnlockItem;
 
-use bahamut::check_bahamut_anime;
-use bilibili::{check_bilibili_china_mainland, check_bilibili_hk_mc_tw};
-use chatgpt::check_chatgpt_combined;
-use claude::check_claude;
-use disney_plus::check_disney_plus;
-use gemini::check_gemini;
-use netflix::check_netflix;
-use prime_video::check_prime_video;
-use spotify::check_spotify;
-use tiktok::check_tiktok;
-use youtube::check_youtube_premium;
+/// Called with every item as soon as its check completes
+type OnItem = Arc<dyn Fn(&UnlockItem) + Send + Sync>;
 
 #[command]
 pub async fn get_unlock_items() -> Result<Vec<UnlockItem>, String> {
-    Ok(types::default_unlock_items())
+    Ok(item_names(&checker::registry().await)
+        .iter()
+        .map(|name| UnlockItem::pending(name))
+        .collect())
 }
 
-#[command]
-pub async fn check_media_unlock() -> Result<Vec<UnlockItem>, String> {
-    let client = match Client::builder()
+/// The client the checks run with, going through `proxy` when given
+fn build_client(proxy: Option<&str>) -> Result<Client, String> {
+    let mut builder = Client::builder()
         .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/122.0.0.0 Safari/537.36")
         .timeout(std::time::Duration::from_secs(30))
         .danger_accept_invalid_certs(true)
         .danger_accept_invalid_hostnames(true)
         .tcp_keepalive(std::time::Duration::from_secs(60))
-        .connection_verbose(true)
-        .build() {
-        Ok(client) => client,
-        Err(e) => return Err(format!("创建HTTP客户端失败: {e}")),
-    };
-
-    let results = Arc::new(Mutex::new(Vec::new()));
-    let mut tasks = JoinSet::new();
-    let client_arc = Arc::new(client);
-
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_bilibili_china_mainland(&client).await;
-            results.lock().await.push(result);
-        });
-    }
-
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_bilibili_hk_mc_tw(&client).await;
-            results.lock().await.push(result);
-        });
-    }
-
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let chatgpt_results = check_chatgpt_combined(&client).await;
-            let mut results = results.lock().await;
-            results.extend(chatgpt_results);
-        });
-    }
-
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_claude(&client).await;
-            results.lock().await.push(result);
-        });
-    }
-
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_gemini(&client).await;
-            results.lock().await.push(result);
-        });
-    }
-
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_youtube_premium(&client).await;
-            results.lock().await.push(result);
-        });
-    }
-
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_bahamut_anime(&client).await;
-            results.lock().await.push(result);
-        });
-    }
-
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_netflix(&client).await;
-            results.lock().await.push(result);
-        });
+        .connection_verbose(true);
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(Proxy::all(proxy).map_err(|e| format!("无效的代理地址: {e}"))?);
     }
+    builder
+        .build()
+        .map_err(|e| format!("创建HTTP客户端失败: {e}"))
+}
 
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_disney_plus(&client).await;
-            results.lock().await.push(result);
-        });
-    }
+#[command]
+pub async fn check_media_unlock() -> Result<Vec<UnlockItem>, String> {
+    let client = Arc::new(build_client(None)?);
+    let checkers = checker::registry().await;
+    let concurrency = checkers.len();
+    let items = run_checks(
+        client,
+        &checkers,
+        concurrency,
+        Arc::new(|_: &UnlockItem| {}),
+    )
+    .await;
+    history::record_results(history::CURRENT_ROUTE, &items).await;
+    Ok(items)
+}
 
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_spotify(&client).await;
-            results.lock().await.push(result);
-        });
+/// The background re-check of `unlock_check_group` run by the timer
+/// every `unlock_check_interval` minutes
+pub async fn run_scheduled_unlock_check() {
+    let group = Config::verge()
+        .await
+        .latest_arc()
+        .unlock_check_group
+        .clone();
+    let Some(group) = group.filter(|group| !group.is_empty()) else {
+        logging!(debug, Type::Network, "未设置解锁检测代理组，跳过定时检测");
+        return;
+    };
+    let result = match matrix::try_check_media_unlock_matrix(group.to_string()).await {
+        Some(result) => result.map(|_| ()),
+        None => {
+            logging!(debug, Type::Network, "解锁检测正在进行，跳过定时检测");
+            return;
+        }
+    };
+    if let Err(e) = result {
+        logging!(warn, Type::Network, "Warning: 定时解锁检测失败: {e}");
     }
+}
 
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
-        tasks.spawn(async move {
-            let result = check_tiktok(&client).await;
-            results.lock().await.push(result);
-        });
-    }
+/// The names of the items `checkers` report, in order
+fn item_names(checkers: &[Arc<dyn UnlockChecker>]) -> Vec<String> {
+    checkers
+        .iter()
+        .flat_map(|checker| checker.names())
+        .collect()
+}
 
-    {
-        let client = Arc::clone(&client_arc);
-        let results = Arc::clone(&results);
+/// Run `checkers` through `client`, at most `concurrency` at a time.
+/// The items are returned in the order of `checkers`
+async fn run_checks(
+    client: Arc<Client>,
+    checkers: &[Arc<dyn UnlockChecker>],
+    concurrency: usize,
+    on_item: OnItem,
+) -> Vec<UnlockItem> {
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, checker) in checkers.iter().enumerate() {
+        let client = Arc::clone(&client);
+        let checker = Arc::clone(checker);
+        let semaphore = Arc::clone(&semaphore);
+        let on_item = Arc::clone(&on_item);
         tasks.spawn(async move {
-            let result = check_prime_video(&client).await;
-            results.lock().await.push(result);
+            let _permit = semaphore.acquire_owned().await.ok();
+            let items = checker.check(&client).await;
+            for item in &items {
+                on_item(item);
+            }
+            (index, items)
         });
     }
 
+    let mut results = Vec::with_capacity(checkers.len());
     while let Some(res) = tasks.join_next().await {
-        if let Err(e) = res {
-            eprintln!("任务执行失败: {e}");
+        match res {
+            Ok(result) => results.push(result),
+            Err(e) => logging!(error, Type::Network, "任务执行失败: {e}"),
         }
     }
-
-    let results = match Arc::try_unwrap(results) {
-        Ok(mutex) => mutex.into_inner(),
-        Err(_) => {
-            logging!(
-                error,
-                Type::Network,
-                "Failed to unwrap results Arc, references still exist"
-            );
-            return Err("Failed to collect results".to_string());
-        }
-    };
-
-    Ok(results)
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().flat_map(|(_, items)| items).collect()
 }
@@ -1,6 +1,6 @@
 use super::CmdResult;
 use crate::cmd::StringifyErr as _;
-use crate::core::{EventDrivenProxyManager, async_proxy_query::AsyncProxyQuery};
+use crate::core::{EventDrivenProxyManager, async_proxy_query::AsyncProxyQuery, pac};
 use crate::process::AsyncHandler;
 use crate::{logging, utils::logging::Type};
 use network_interface::NetworkInterface;
@@ -59,6 +59,12 @@ pub async fn get_auto_proxy() -> CmdResult<Mapping> {
     Ok(map)
 }
 
+/// 预览当前提供的 PAC 脚本
+#[tauri::command]
+pub async fn get_pac_preview() -> CmdResult<String> {
+    Ok(pac::render("127.0.0.1").await.into())
+}
+
 /// 获取系统主机名
 #[tauri::command]
 pub fn get_system_hostname() -> CmdResult<String> {
@@ -2,14 +2,21 @@ use super::CmdResult;
 use super::StringifyErr as _;
 use crate::{
     config::{
-        Config, IProfiles, PrfItem, PrfOption,
+        Config, IProfiles, PrfDiff, PrfItem, PrfOption, PrfRevision, ProfileHistory,
         profiles::{
             profiles_append_item_with_filedata_safe, profiles_delete_item_safe,
-            profiles_patch_item_safe, profiles_reorder_safe, profiles_save_file_safe,
+            profiles_ensure_provider_items_safe, profiles_patch_item_safe, profiles_reorder_safe,
+            profiles_save_file_safe,
         },
         profiles_append_item_safe,
     },
-    core::{CoreManager, handle, timer::Timer, tray::Tray},
+    core::{
+        CoreManager, handle,
+        timer::Timer,
+        tray::Tray,
+        validate::CoreConfigValidator,
+        validation_report::{ValidationReport, ValidationSource},
+    },
     feat, logging,
     module::auto_backup::{AutoBackupManager, AutoBackupTrigger},
     process::AsyncHandler,
@@ -419,6 +426,12 @@ pub async fn patch_profiles_config(profiles: IProfiles) -> CmdResult<bool> {
         CURRENT_SWITCHING_PROFILE.store(false, Ordering::Release);
         return Ok(false);
     }
+    // 旧的订阅没有 provider 增强项，切换时补充创建
+    if let Some(uid) = target_profile
+        && let Err(e) = profiles_ensure_provider_items_safe(uid).await
+    {
+        logging!(warn, Type::Cmd, "Warning: 创建 provider 增强项失败: {e}");
+    }
     Config::profiles()
         .await
         .edit_draft(|d| d.patch_config(&profiles));
@@ -441,19 +454,23 @@ pub async fn patch_profiles_config_by_profile_index(profile_index: String) -> Cm
 /// 修改某个profile item的
 #[tauri::command]
 pub async fn patch_profile(index: String, profile: PrfItem) -> CmdResult {
-    // 保存修改前检查是否有更新 update_interval
+    // 保存修改前检查是否有更新 update_interval / update_cron / update_blackout
     let profiles = Config::profiles().await;
     let should_refresh_timer = if let Ok(old_profile) = profiles.latest_arc().get_item(&index)
         && let Some(new_option) = profile.option.as_ref()
     {
-        let old_interval = old_profile.option.as_ref().and_then(|o| o.update_interval);
+        let old_option = old_profile.option.as_ref();
+        let old_interval = old_option.and_then(|o| o.update_interval);
         let new_interval = new_option.update_interval;
-        let old_allow_auto_update = old_profile
-            .option
-            .as_ref()
-            .and_then(|o| o.allow_auto_update);
+        let old_allow_auto_update = old_option.and_then(|o| o.allow_auto_update);
         let new_allow_auto_update = new_option.allow_auto_update;
-        (old_interval != new_interval) || (old_allow_auto_update != new_allow_auto_update)
+        let schedule_changed = old_option.and_then(|o| o.update_cron.as_ref())
+            != new_option.update_cron.as_ref()
+            || old_option.and_then(|o| o.update_blackout.as_ref())
+                != new_option.update_blackout.as_ref();
+        (old_interval != new_interval)
+            || (old_allow_auto_update != new_allow_auto_update)
+            || schedule_changed
     } else {
         false
     };
@@ -523,3 +540,112 @@ pub async fn get_next_update_time(uid: String) -> CmdResult<Option<i64>> {
     let next_time = timer.get_next_update_time(&uid).await;
     Ok(next_time)
 }
+
+/// 获取订阅更新的变化记录
+#[tauri::command]
+pub async fn get_profile_diffs(index: String) -> CmdResult<Vec<PrfDiff>> {
+    let profiles = Config::profiles().await;
+    let profiles_ref = profiles.latest_arc();
+    let item = profiles_ref.get_item(&index).stringify_err()?;
+    Ok(item.diffs.clone().unwrap_or_default())
+}
+
+/// 获取配置文件对应的文件名
+async fn profile_file_by_index(index: &String) -> CmdResult<String> {
+    let profiles = Config::profiles().await;
+    let profiles_ref = profiles.latest_arc();
+    let file = profiles_ref
+        .get_item(index)
+        .stringify_err()?
+        .file
+        .clone()
+        .ok_or("the file field is null")?;
+    Ok(file)
+}
+
+/// 获取配置文件的历史版本
+#[tauri::command]
+pub async fn list_profile_revisions(index: String) -> CmdResult<Vec<PrfRevision>> {
+    let file = profile_file_by_index(&index).await?;
+    ProfileHistory::list(&file).await.stringify_err()
+}
+
+/// 查看配置文件的某个历史版本
+#[tauri::command]
+pub async fn view_profile_revision(index: String, revision: String) -> CmdResult<String> {
+    let file = profile_file_by_index(&index).await?;
+    ProfileHistory::read(&file, &revision).await.stringify_err()
+}
+
+/// 回滚配置文件到某个历史版本
+#[tauri::command]
+pub async fn rollback_profile_revision(
+    index: String,
+    revision: String,
+) -> CmdResult<ValidationReport> {
+    let (file, is_merge_file, in_use) = {
+        let profiles = Config::profiles().await;
+        let profiles_ref = profiles.latest_arc();
+        let item = profiles_ref.get_item(&index).stringify_err()?;
+        let file = item.file.clone().ok_or("the file field is null")?;
+        let is_merge = item.itype.as_ref().is_some_and(|t| t == "merge");
+        (file, is_merge, profiles_ref.is_in_use(&index))
+    };
+    let data = ProfileHistory::read(&file, &revision)
+        .await
+        .stringify_err()?;
+
+    let report = validate_revision(&file, &data, is_merge_file).await?;
+    if !report.is_valid() {
+        logging!(
+            warn,
+            Type::Cmd,
+            "历史版本验证失败，放弃回滚: {}",
+            report.error_message()
+        );
+        if report.source == ValidationSource::Script {
+            crate::cmd::validate::handle_script_validation_notice(&report, "脚本文件");
+        } else {
+            crate::cmd::validate::handle_yaml_validation_notice(&report, "YAML配置文件");
+        }
+        return Ok(report);
+    }
+
+    ProfileHistory::restore(&file, &revision)
+        .await
+        .stringify_err_log(|e| logging!(error, Type::Cmd, "回滚配置文件失败: {}", e))?;
+
+    // 只有使用中的配置需要重新加载
+    if in_use {
+        match CoreManager::global().update_config().await {
+            Ok(_) => handle::Handle::refresh_clash(),
+            Err(e) => {
+                logging!(error, Type::Cmd, "{}", e);
+                return Err(e.to_string().into());
+            }
+        }
+    }
+    handle::Handle::notify_profile_changed(index);
+
+    AutoBackupManager::trigger_backup(AutoBackupTrigger::ProfileChange);
+    Ok(report)
+}
+
+/// 在临时文件中验证历史版本，不改动正在使用的文件
+async fn validate_revision(
+    file: &str,
+    data: &str,
+    is_merge_file: bool,
+) -> CmdResult<ValidationReport> {
+    let path = dirs::app_profiles_dir()
+        .stringify_err()?
+        .join(format!("rollback-{file}"));
+    tokio::fs::write(&path, data).await.stringify_err()?;
+    let report =
+        CoreConfigValidator::validate_config_file(&path.to_string_lossy(), Some(is_merge_file))
+            .await;
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        logging!(warn, Type::Cmd, "Warning: 删除临时验证文件失败: {e}");
+    }
+    report.stringify_err()
+}
@@ -3,6 +3,7 @@ use crate::{
     cmd::StringifyErr as _,
     config::{Config, ConfigType},
     core::CoreManager,
+    enhance::{self, EnhanceStage},
     log_err,
 };
 use anyhow::{Context as _, anyhow};
@@ -45,6 +46,12 @@ pub async fn get_runtime_logs() -> CmdResult<HashMap<String, Vec<(String, String
     Ok(Config::runtime().await.latest_arc().chain_logs.clone())
 }
 
+/// 试运行增强流程，返回每个阶段后的配置，不写入运行时配置也不重载内核
+#[tauri::command]
+pub async fn enhance_dry_run() -> CmdResult<Vec<EnhanceStage>> {
+    Ok(enhance::enhance_dry_run().await)
+}
+
 #[tauri::command]
 pub async fn get_runtime_proxy_chain_config(proxy_chain_exit_node: String) -> CmdResult<String> {
     let runtime = Config::runtime().await;
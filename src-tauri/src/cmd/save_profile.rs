@@ -1,8 +1,12 @@
 use super::CmdResult;
 use crate::{
     cmd::StringifyErr as _,
-    config::{Config, PrfItem},
-    core::{CoreManager, handle, validate::CoreConfigValidator},
+    config::{Config, PrfItem, ProfileHistory, RevisionReason},
+    core::{
+        CoreManager, handle,
+        validate::CoreConfigValidator,
+        validation_report::{ValidationReport, ValidationSource},
+    },
     logging,
     module::auto_backup::{AutoBackupManager, AutoBackupTrigger},
     utils::{dirs, logging::Type},
@@ -11,11 +15,15 @@ use smartstring::alias::String;
 use tokio::fs;
 
 /// 保存profiles的配置
+/// 返回验证报告，没有内容时为 None
 #[tauri::command]
-pub async fn save_profile_file(index: String, file_data: Option<String>) -> CmdResult {
+pub async fn save_profile_file(
+    index: String,
+    file_data: Option<String>,
+) -> CmdResult<Option<ValidationReport>> {
     let file_data = match file_data {
         Some(d) => d,
-        None => return Ok(()),
+        None => return Ok(None),
     };
 
     let backup_trigger = match index.as_str() {
@@ -58,17 +66,30 @@ pub async fn save_profile_file(index: String, file_data: Option<String>) -> CmdR
         is_merge_file
     );
 
-    let changes_applied = if is_merge_file {
+    let report = if is_merge_file {
         handle_merge_file(&file_path_str, &file_path, &original_content).await?
     } else {
         handle_full_validation(&file_path_str, &file_path, &original_content).await?
     };
 
+    let changes_applied = report.is_valid();
+    if changes_applied
+        && let Err(e) = ProfileHistory::record(
+            &rel_path,
+            Some(original_content.as_str()),
+            &file_data,
+            RevisionReason::UserEdit,
+        )
+        .await
+    {
+        logging!(warn, Type::Config, "Warning: 记录历史版本失败: {e}");
+    }
+
     if changes_applied && let Some(trigger) = backup_trigger {
         AutoBackupManager::trigger_backup(trigger);
     }
 
-    Ok(())
+    Ok(Some(report))
 }
 
 async fn restore_original(
@@ -78,18 +99,11 @@ async fn restore_original(
     fs::write(file_path, original_content).await.stringify_err()
 }
 
-fn is_script_error(err: &str, file_path_str: &str) -> bool {
-    file_path_str.ends_with(".js")
-        || err.contains("Script syntax error")
-        || err.contains("Script must contain a main function")
-        || err.contains("Failed to read script file")
-}
-
 async fn handle_merge_file(
     file_path_str: &str,
     file_path: &std::path::Path,
     original_content: &str,
-) -> CmdResult<bool> {
+) -> CmdResult<ValidationReport> {
     logging!(
         info,
         Type::Config,
@@ -97,7 +111,7 @@ async fn handle_merge_file(
     );
 
     match CoreConfigValidator::validate_config_file(file_path_str, Some(true)).await {
-        Ok((true, _)) => {
+        Ok(report) if report.is_valid() => {
             logging!(info, Type::Config, "[cmd配置save] merge文件语法验证通过");
             if let Err(e) = CoreManager::global().update_config().await {
                 logging!(
@@ -109,19 +123,18 @@ async fn handle_merge_file(
             } else {
                 handle::Handle::refresh_clash();
             }
-            Ok(true)
+            Ok(report)
         }
-        Ok((false, error_msg)) => {
+        Ok(report) => {
             logging!(
                 warn,
                 Type::Config,
                 "[cmd配置save] merge文件语法验证失败: {}",
-                error_msg
+                report.error_message()
             );
             restore_original(file_path, original_content).await?;
-            let result = (false, error_msg.clone());
-            crate::cmd::validate::handle_yaml_validation_notice(&result, "合并配置文件");
-            Ok(false)
+            crate::cmd::validate::handle_yaml_validation_notice(&report, "合并配置文件");
+            Ok(report)
         }
         Err(e) => {
             logging!(error, Type::Config, "[cmd配置save] 验证过程发生错误: {}", e);
@@ -135,45 +148,38 @@ async fn handle_full_validation(
     file_path_str: &str,
     file_path: &std::path::Path,
     original_content: &str,
-) -> CmdResult<bool> {
+) -> CmdResult<ValidationReport> {
     match CoreConfigValidator::validate_config_file(file_path_str, None).await {
-        Ok((true, _)) => {
+        Ok(report) if report.is_valid() => {
             logging!(info, Type::Config, "[cmd配置save] 验证成功");
-            Ok(true)
+            Ok(report)
         }
-        Ok((false, error_msg)) => {
-            logging!(warn, Type::Config, "[cmd配置save] 验证失败: {}", error_msg);
+        Ok(report) => {
+            logging!(
+                warn,
+                Type::Config,
+                "[cmd配置save] 验证失败: {}",
+                report.error_message()
+            );
             restore_original(file_path, original_content).await?;
 
-            if error_msg.contains("YAML syntax error")
-                || error_msg.contains("Failed to read file:")
-                || (!file_path_str.ends_with(".js") && !is_script_error(&error_msg, file_path_str))
-            {
-                logging!(
-                    info,
-                    Type::Config,
-                    "[cmd配置save] YAML配置文件验证失败，发送通知"
-                );
-                let result = (false, error_msg.to_owned());
-                crate::cmd::validate::handle_yaml_validation_notice(&result, "YAML配置文件");
-            } else if is_script_error(&error_msg, file_path_str) {
+            if report.source == ValidationSource::Script {
                 logging!(
                     info,
                     Type::Config,
                     "[cmd配置save] 脚本文件验证失败，发送通知"
                 );
-                let result = (false, error_msg.to_owned());
-                crate::cmd::validate::handle_script_validation_notice(&result, "脚本文件");
+                crate::cmd::validate::handle_script_validation_notice(&report, "脚本文件");
             } else {
                 logging!(
                     info,
                     Type::Config,
-                    "[cmd配置save] 其他类型验证失败，发送一般通知"
+                    "[cmd配置save] YAML配置文件验证失败，发送通知"
                 );
-                handle::Handle::notice_message("config_validate::error", error_msg.to_owned());
+                crate::cmd::validate::handle_yaml_validation_notice(&report, "YAML配置文件");
             }
 
-            Ok(false)
+            Ok(report)
         }
         Err(e) => {
             logging!(error, Type::Config, "[cmd配置save] 验证过程发生错误: {}", e);
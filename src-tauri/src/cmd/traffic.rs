@@ -0,0 +1,24 @@
+use super::CmdResult;
+use crate::{
+    cmd::StringifyErr as _,
+    core::traffic_store::{self, TrafficDay},
+};
+use smartstring::alias::String;
+
+/// Get the stored traffic of the days from `start` to `end` (`YYYY-MM-DD`, inclusive)
+#[tauri::command]
+pub async fn get_traffic_stats(start: String, end: String) -> CmdResult<Vec<TrafficDay>> {
+    traffic_store::query(&start, &end).await.stringify_err()
+}
+
+/// Export the traffic of the days from `start` to `end` as CSV to `destination`
+#[tauri::command]
+pub async fn export_traffic_csv(start: String, end: String, destination: String) -> CmdResult<()> {
+    let days = traffic_store::query(&start, &end).await.stringify_err()?;
+    tokio::fs::write(
+        destination.as_str(),
+        traffic_store::to_csv(&days).as_bytes(),
+    )
+    .await
+    .stringify_err()
+}
@@ -1,6 +1,10 @@
 use super::CmdResult;
 use crate::{
-    core::{handle, validate::CoreConfigValidator},
+    core::{
+        handle,
+        validate::CoreConfigValidator,
+        validation_report::{ValidationReport, ValidationSource},
+    },
     logging,
     utils::logging::Type,
 };
@@ -15,38 +19,35 @@ pub async fn script_validate_notice(status: String, msg: String) -> CmdResult {
 
 /// 处理脚本验证相关的所有消息通知
 /// 统一通知接口，保持消息类型一致性
-pub fn handle_script_validation_notice(result: &(bool, String), file_type: &str) {
-    if !result.0 {
-        let error_msg = &result.1;
+pub fn handle_script_validation_notice(report: &ValidationReport, file_type: &str) {
+    let Some(issue) = report.first_error() else {
+        return;
+    };
+    // 错误信息包含行列号，便于定位
+    let error_msg = report.error_message();
 
-        // 根据错误消息内容判断错误类型
-        let status = if error_msg.starts_with("File not found:") {
-            "config_validate::file_not_found"
-        } else if error_msg.starts_with("Failed to read script file:") {
-            "config_validate::script_error"
-        } else if error_msg.starts_with("Script syntax error:") {
-            "config_validate::script_syntax_error"
-        } else if error_msg == "Script must contain a main function" {
-            "config_validate::script_missing_main"
-        } else {
-            // 如果是其他类型错误，作为一般脚本错误处理
-            "config_validate::script_error"
-        };
+    // 根据错误码判断错误类型
+    let status = match issue.code.as_str() {
+        "file_not_found" => "config_validate::file_not_found",
+        "script_syntax_error" => "config_validate::script_syntax_error",
+        "script_missing_main" => "config_validate::script_missing_main",
+        // 如果是其他类型错误，作为一般脚本错误处理
+        _ => "config_validate::script_error",
+    };
 
-        logging!(warn, Type::Config, "{} 验证失败: {}", file_type, error_msg);
-        handle::Handle::notice_message(status, error_msg.to_owned());
-    }
+    logging!(warn, Type::Config, "{} 验证失败: {}", file_type, error_msg);
+    handle::Handle::notice_message(status, error_msg);
 }
 
 /// 验证指定脚本文件
 #[tauri::command]
-pub async fn validate_script_file(file_path: String) -> CmdResult<bool> {
+pub async fn validate_script_file(file_path: String) -> CmdResult<ValidationReport> {
     logging!(info, Type::Config, "验证脚本文件: {}", file_path);
 
     match CoreConfigValidator::validate_config_file(&file_path, None).await {
-        Ok(result) => {
-            handle_script_validation_notice(&result, "脚本文件");
-            Ok(result.0) // 返回验证结果布尔值
+        Ok(report) => {
+            handle_script_validation_notice(&report, "脚本文件");
+            Ok(report)
         }
         Err(e) => {
             let error_msg = e.to_string();
@@ -57,67 +58,61 @@ pub async fn validate_script_file(file_path: String) -> CmdResult<bool> {
                 error_msg
             );
             handle::Handle::notice_message("config_validate::process_terminated", &error_msg);
-            Ok(false)
+            Err(error_msg.into())
         }
     }
 }
 
 /// 处理YAML验证相关的所有消息通知
 /// 统一通知接口，保持消息类型一致性
-pub fn handle_yaml_validation_notice(result: &(bool, String), file_type: &str) {
-    if !result.0 {
-        let error_msg = &result.1;
-        logging!(
-            info,
-            Type::Config,
-            "[通知] 处理{}验证错误: {}",
-            file_type,
-            error_msg
-        );
+pub fn handle_yaml_validation_notice(report: &ValidationReport, file_type: &str) {
+    let Some(issue) = report.first_error() else {
+        return;
+    };
+    // 错误信息包含行列号，便于定位
+    let error_msg = report.error_message();
+    logging!(
+        info,
+        Type::Config,
+        "[通知] 处理{}验证错误: {}",
+        file_type,
+        error_msg
+    );
 
-        // 检查是否为merge文件
-        let is_merge_file = file_type.contains("合并");
+    // 检查是否为merge文件
+    let is_merge_file = report.source == ValidationSource::Merge;
 
-        // 根据错误消息内容判断错误类型
-        let status = if error_msg.starts_with("File not found:") {
-            "config_validate::file_not_found"
-        } else if error_msg.starts_with("Failed to read file:") {
-            "config_validate::yaml_read_error"
-        } else if error_msg.starts_with("YAML syntax error:") {
-            if is_merge_file {
-                "config_validate::merge_syntax_error"
-            } else {
-                "config_validate::yaml_syntax_error"
-            }
-        } else if error_msg.contains("mapping values are not allowed") {
-            if is_merge_file {
-                "config_validate::merge_mapping_error"
-            } else {
-                "config_validate::yaml_mapping_error"
-            }
-        } else if error_msg.contains("did not find expected key") {
-            if is_merge_file {
-                "config_validate::merge_key_error"
-            } else {
-                "config_validate::yaml_key_error"
-            }
-        } else {
-            // 如果是其他类型错误，根据文件类型作为一般错误处理
-            if is_merge_file {
-                "config_validate::merge_error"
-            } else {
-                "config_validate::yaml_error"
-            }
-        };
+    // 根据错误码判断错误类型，内核输出的错误码不区分YAML错误，需要检查内容
+    let code = match issue.code.as_str() {
+        "core_fatal" | "core_error" if issue.message.contains("mapping values are not allowed") => {
+            "yaml_mapping_error"
+        }
+        "core_fatal" | "core_error" if issue.message.contains("did not find expected key") => {
+            "yaml_key_error"
+        }
+        code => code,
+    };
+    let status = match code {
+        "file_not_found" => "config_validate::file_not_found",
+        "yaml_read_error" => "config_validate::yaml_read_error",
+        "yaml_syntax_error" if is_merge_file => "config_validate::merge_syntax_error",
+        "yaml_syntax_error" => "config_validate::yaml_syntax_error",
+        "yaml_mapping_error" if is_merge_file => "config_validate::merge_mapping_error",
+        "yaml_mapping_error" => "config_validate::yaml_mapping_error",
+        "yaml_key_error" if is_merge_file => "config_validate::merge_key_error",
+        "yaml_key_error" => "config_validate::yaml_key_error",
+        // 如果是其他类型错误，根据文件类型作为一般错误处理
+        _ if is_merge_file => "config_validate::merge_error",
+        _ => "config_validate::yaml_error",
+    };
 
-        logging!(warn, Type::Config, "{} 验证失败: {}", file_type, error_msg);
-        logging!(
-            info,
-            Type::Config,
-            "[通知] 发送通知: status={}, msg={}",
-            status,
-            error_msg
-        );
-        handle::Handle::notice_message(status, error_msg.to_owned());
-    }
+    logging!(warn, Type::Config, "{} 验证失败: {}", file_type, error_msg);
+    logging!(
+        info,
+        Type::Config,
+        "[通知] 发送通知: status={}, msg={}",
+        status,
+        error_msg
+    );
+    handle::Handle::notice_message(status, error_msg);
 }
@@ -1,5 +1,6 @@
 use super::CmdResult;
 use crate::{cmd::StringifyErr as _, config::IVerge, feat, utils::draft::SharedBox};
+use smartstring::alias::String;
 
 /// 获取Verge配置
 #[tauri::command]
@@ -12,3 +13,15 @@ pub async fn get_verge_config() -> CmdResult<SharedBox<IVerge>> {
 pub async fn patch_verge_config(payload: IVerge) -> CmdResult {
     feat::patch_verge(&payload, false).await.stringify_err()
 }
+
+/// 重新生成本地 REST API 的访问令牌
+#[tauri::command]
+pub async fn regenerate_rest_api_token() -> CmdResult<String> {
+    let token: String = nanoid::nanoid!(32).into();
+    let patch = IVerge {
+        rest_api_token: Some(token.clone()),
+        ..IVerge::default()
+    };
+    feat::patch_verge(&patch, false).await.stringify_err()?;
+    Ok(token)
+}
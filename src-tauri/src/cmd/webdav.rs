@@ -2,7 +2,8 @@ use super::CmdResult;
 use crate::{
     cmd::StringifyErr as _,
     config::{Config, IVerge},
-    core, feat,
+    core,
+    feat::{self, RestoreOptions, RestorePlan},
 };
 use reqwest_dav::list_cmd::ListFile;
 use smartstring::alias::String;
@@ -45,8 +46,34 @@ pub async fn delete_webdav_backup(filename: String) -> CmdResult<()> {
     feat::delete_webdav_backup(filename).await.stringify_err()
 }
 
-/// 从 WebDAV 恢复备份文件
+/// 预览从 WebDAV 恢复备份会改动哪些文件
 #[tauri::command]
-pub async fn restore_webdav_backup(filename: String) -> CmdResult<()> {
-    feat::restore_webdav_backup(filename).await.stringify_err()
+pub async fn preview_webdav_backup_restore(
+    filename: String,
+    passphrase: Option<String>,
+    options: Option<RestoreOptions>,
+) -> CmdResult<RestorePlan> {
+    feat::preview_webdav_backup_restore(
+        filename,
+        passphrase,
+        options.unwrap_or_else(RestoreOptions::all),
+    )
+    .await
+    .stringify_err()
+}
+
+/// 从 WebDAV 恢复备份文件，加密的备份需要提供口令，未指定 options 时全部恢复
+#[tauri::command]
+pub async fn restore_webdav_backup(
+    filename: String,
+    passphrase: Option<String>,
+    options: Option<RestoreOptions>,
+) -> CmdResult<RestorePlan> {
+    feat::restore_webdav_backup(
+        filename,
+        passphrase,
+        options.unwrap_or_else(RestoreOptions::all),
+    )
+    .await
+    .stringify_err()
 }
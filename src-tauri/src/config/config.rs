@@ -1,7 +1,7 @@
 use super::{IClashTemp, IProfiles, IRuntime, IVerge};
 use crate::{
     cmd,
-    config::{PrfItem, profiles_append_item_safe},
+    config::{PrfItem, profiles_append_item_safe, profiles_ensure_provider_items_safe},
     constants::{files, timing},
     core::{CoreManager, handle, service, tray, validate::CoreConfigValidator},
     enhance, logging, logging_error,
@@ -74,6 +74,7 @@ impl Config {
     /// 初始化订阅
     pub async fn init_config() -> Result<()> {
         Self::ensure_default_profile_items().await?;
+        Self::ensure_provider_items().await;
 
         // init Tun mode
         if !cmd::system::is_admin().unwrap_or_default()
@@ -115,6 +116,16 @@ impl Config {
         Ok(())
     }
 
+    // 旧的订阅没有 provider 增强项，启动时为当前订阅补充创建
+    async fn ensure_provider_items() {
+        let current = Self::profiles().await.latest_arc().get_current().cloned();
+        if let Some(uid) = current
+            && let Err(e) = profiles_ensure_provider_items_safe(&uid).await
+        {
+            logging!(warn, Type::Config, "Warning: 创建 provider 增强项失败: {e}");
+        }
+    }
+
     async fn generate_and_validate() -> Result<Option<(&'static str, String)>> {
         // 生成运行时配置
         if let Err(err) = Self::generate().await {
@@ -131,7 +142,8 @@ impl Config {
             logging!(info, Type::Config, "开始验证配置");
 
             match CoreConfigValidator::global().validate_config().await {
-                Ok((is_valid, error_msg)) => {
+                Ok(report) => {
+                    let (is_valid, error_msg) = report.to_legacy();
                     if !is_valid {
                         logging!(
                             warn,
@@ -0,0 +1,508 @@
+use crate::{
+    config::Config,
+    logging,
+    utils::{dirs, logging::Type},
+};
+use anyhow::{Context as _, Result, bail};
+use serde::{Deserialize, Serialize};
+use smartstring::alias::String;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// history dir under the profiles dir
+pub const PROFILE_HISTORY_DIR: &str = "history";
+
+/// default count of revisions kept for every profile file
+pub const DEFAULT_HISTORY_LIMIT: usize = 10;
+
+/// why a revision was recorded
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionReason {
+    /// the content before the first tracked change
+    Initial,
+    UserEdit,
+    AutoUpdate,
+    Restore,
+    /// the content found on disk when it differs from the newest revision,
+    /// e.g. after the file was edited in an external editor
+    External,
+}
+
+impl RevisionReason {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::Initial => "initial",
+            Self::UserEdit => "user_edit",
+            Self::AutoUpdate => "auto_update",
+            Self::Restore => "restore",
+            Self::External => "external",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "initial" => Some(Self::Initial),
+            "user_edit" => Some(Self::UserEdit),
+            "auto_update" => Some(Self::AutoUpdate),
+            "restore" => Some(Self::Restore),
+            "external" => Some(Self::External),
+            _ => None,
+        }
+    }
+}
+
+/// a stored revision of a profile file
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrfRevision {
+    /// `{timestamp}-{reason}`
+    pub id: String,
+    /// the profile file this revision belongs to
+    pub file: String,
+    /// milliseconds since epoch
+    pub timestamp: i64,
+    pub reason: RevisionReason,
+    pub size: u64,
+}
+
+/// Revisions of the files under `app_profiles_dir`
+/// stored as `history/{file}/{timestamp}-{reason}`
+pub struct ProfileHistory;
+
+impl ProfileHistory {
+    pub fn history_dir() -> Result<PathBuf> {
+        Ok(dirs::app_profiles_dir()?.join(PROFILE_HISTORY_DIR))
+    }
+
+    async fn store() -> Result<HistoryStore> {
+        let limit = Config::verge()
+            .await
+            .latest_arc()
+            .profile_history_limit
+            .unwrap_or(DEFAULT_HISTORY_LIMIT);
+        Ok(HistoryStore::new(dirs::app_profiles_dir()?, limit))
+    }
+
+    /// Record `data` as the newest revision of `file`.
+    /// `previous` is the content before this change; it is kept as the
+    /// `initial` revision when the file has no history yet, or as an
+    /// `external` one when it differs from the newest revision.
+    pub async fn record(
+        file: &str,
+        previous: Option<&str>,
+        data: &str,
+        reason: RevisionReason,
+    ) -> Result<()> {
+        Self::store()
+            .await?
+            .record(file, previous, data, reason)
+            .await
+    }
+
+    /// list the revisions of a file, newest first
+    pub async fn list(file: &str) -> Result<Vec<PrfRevision>> {
+        Self::store().await?.list(file).await
+    }
+
+    /// read the content of a revision
+    pub async fn read(file: &str, id: &str) -> Result<String> {
+        Self::store().await?.read(file, id).await
+    }
+
+    /// write a revision back to the profile file, recording it as a `restore`
+    pub async fn restore(file: &str, id: &str) -> Result<()> {
+        Self::store().await?.restore(file, id).await?;
+        logging!(info, Type::Config, "已将 {} 回滚到版本 {}", file, id);
+        Ok(())
+    }
+
+    /// files that have a history dir
+    pub fn tracked_files() -> Result<Vec<String>> {
+        HistoryStore::new(dirs::app_profiles_dir()?, DEFAULT_HISTORY_LIMIT).tracked_files()
+    }
+
+    /// remove the whole history of a file
+    pub async fn remove(file: &str) -> Result<()> {
+        Self::store().await?.remove(file).await
+    }
+}
+
+/// The history of the profile files in `profiles_dir`, keeping `limit` revisions per file
+struct HistoryStore {
+    profiles_dir: PathBuf,
+    limit: usize,
+}
+
+impl HistoryStore {
+    fn new(profiles_dir: PathBuf, limit: usize) -> Self {
+        Self {
+            profiles_dir,
+            limit: limit.max(1),
+        }
+    }
+
+    fn history_dir(&self) -> PathBuf {
+        self.profiles_dir.join(PROFILE_HISTORY_DIR)
+    }
+
+    fn file_history_dir(&self, file: &str) -> Result<PathBuf> {
+        if file.is_empty() || file.contains(['/', '\\']) || file.starts_with('.') {
+            bail!("invalid profile file name \"{file}\"");
+        }
+        Ok(self.history_dir().join(file))
+    }
+
+    async fn record(
+        &self,
+        file: &str,
+        previous: Option<&str>,
+        data: &str,
+        reason: RevisionReason,
+    ) -> Result<()> {
+        let dir = self.file_history_dir(file)?;
+        fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("failed to create history dir \"{}\"", dir.display()))?;
+
+        let revisions = self.list(file).await?;
+        // revisions are ordered by timestamp, so every new one has to be strictly newer
+        let mut next = i64::MIN;
+        match revisions.first() {
+            Some(latest) => {
+                let latest_data = fs::read_to_string(dir.join(latest.id.as_str()))
+                    .await
+                    .unwrap_or_default();
+                if latest_data == data {
+                    return Ok(());
+                }
+                next = latest.timestamp + 1;
+                // 保存在记录之外被修改的内容，以便回滚
+                if let Some(previous) = previous
+                    && previous != latest_data
+                    && previous != data
+                {
+                    next = Self::write_revision(&dir, previous, RevisionReason::External, next)
+                        .await?
+                        + 1;
+                }
+            }
+            None => {
+                if let Some(previous) = previous
+                    && previous != data
+                {
+                    next = Self::write_revision(&dir, previous, RevisionReason::Initial, next)
+                        .await?
+                        + 1;
+                }
+            }
+        }
+
+        Self::write_revision(&dir, data, reason, next).await?;
+        self.prune(file).await
+    }
+
+    /// write a revision stamped no earlier than `min_timestamp`, returning its timestamp
+    async fn write_revision(
+        dir: &Path,
+        data: &str,
+        reason: RevisionReason,
+        min_timestamp: i64,
+    ) -> Result<i64> {
+        let timestamp = chrono::Local::now().timestamp_millis().max(min_timestamp);
+        let path = dir.join(format!("{timestamp}-{}", reason.as_str()));
+        fs::write(&path, data.as_bytes())
+            .await
+            .with_context(|| format!("failed to write revision \"{}\"", path.display()))?;
+        Ok(timestamp)
+    }
+
+    async fn list(&self, file: &str) -> Result<Vec<PrfRevision>> {
+        let dir = self.file_history_dir(file)?;
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut revisions = vec![];
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+            let Some((timestamp, reason)) = name.split_once('-') else {
+                continue;
+            };
+            let (Ok(timestamp), Some(reason)) =
+                (timestamp.parse::<i64>(), RevisionReason::parse(reason))
+            else {
+                continue;
+            };
+            let size = entry.metadata().await.map(|m| m.len()).unwrap_or(0);
+            revisions.push(PrfRevision {
+                id: name.into(),
+                file: file.into(),
+                timestamp,
+                reason,
+                size,
+            });
+        }
+
+        revisions.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        Ok(revisions)
+    }
+
+    async fn read(&self, file: &str, id: &str) -> Result<String> {
+        let path = self.revision_path(file, id).await?;
+        let content = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("failed to read revision \"{id}\""))?;
+        Ok(content.into())
+    }
+
+    async fn restore(&self, file: &str, id: &str) -> Result<()> {
+        let data = self.read(file, id).await?;
+        let path = self.profiles_dir.join(file);
+        let current = fs::read_to_string(&path).await.ok();
+
+        self.record(file, current.as_deref(), &data, RevisionReason::Restore)
+            .await?;
+        fs::write(&path, data.as_bytes())
+            .await
+            .with_context(|| format!("failed to restore \"{file}\""))
+    }
+
+    /// only ids listed for `file` are accepted, so `id` can't point outside its history dir
+    async fn revision_path(&self, file: &str, id: &str) -> Result<PathBuf> {
+        let revisions = self.list(file).await?;
+        if !revisions.iter().any(|r| r.id == id) {
+            bail!("failed to find revision \"{id}\" of \"{file}\"");
+        }
+        Ok(self.file_history_dir(file)?.join(id))
+    }
+
+    /// keep the newest `limit` revisions
+    async fn prune(&self, file: &str) -> Result<()> {
+        let dir = self.file_history_dir(file)?;
+        for revision in self.list(file).await?.iter().skip(self.limit) {
+            if let Err(e) = fs::remove_file(dir.join(revision.id.as_str())).await {
+                logging!(
+                    warn,
+                    Type::Config,
+                    "Warning: 清理历史版本失败: {} - {e}",
+                    revision.id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn tracked_files(&self) -> Result<Vec<String>> {
+        let dir = self.history_dir();
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut files = vec![];
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.path().is_dir()
+                && let Some(name) = entry.file_name().to_str()
+            {
+                files.push(name.into());
+            }
+        }
+        Ok(files)
+    }
+
+    async fn remove(&self, file: &str) -> Result<()> {
+        let dir = self.file_history_dir(file)?;
+        if dir.exists() {
+            fs::remove_dir_all(&dir).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(limit: usize) -> HistoryStore {
+        let dir = std::env::temp_dir().join(format!("rv-verge-history-{}", nanoid::nanoid!()));
+        HistoryStore::new(dir, limit)
+    }
+
+    #[allow(clippy::unwrap_used)]
+    async fn read(store: &HistoryStore, revision: &PrfRevision) -> std::string::String {
+        store
+            .read(&revision.file, &revision.id)
+            .await
+            .unwrap()
+            .to_string()
+    }
+
+    fn reasons(revisions: &[PrfRevision]) -> Vec<RevisionReason> {
+        revisions.iter().map(|r| r.reason).collect()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn test_record_list_and_read() {
+        let store = temp_store(10);
+
+        store
+            .record("a.yaml", Some("v0"), "v1", RevisionReason::UserEdit)
+            .await
+            .unwrap();
+        // 内容未变化时不新增版本
+        store
+            .record("a.yaml", Some("v1"), "v1", RevisionReason::UserEdit)
+            .await
+            .unwrap();
+        store
+            .record("a.yaml", Some("v1"), "v2", RevisionReason::AutoUpdate)
+            .await
+            .unwrap();
+
+        let revisions = store.list("a.yaml").await.unwrap();
+        assert_eq!(
+            reasons(&revisions),
+            [
+                RevisionReason::AutoUpdate,
+                RevisionReason::UserEdit,
+                RevisionReason::Initial,
+            ]
+        );
+        assert!(revisions.iter().all(|r| r.file.as_str() == "a.yaml"));
+        assert_eq!(revisions[0].size, 2);
+        assert_eq!(read(&store, &revisions[0]).await, "v2");
+        assert_eq!(read(&store, &revisions[2]).await, "v0");
+        assert!(store.list("b.yaml").await.unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(&store.profiles_dir).await;
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn test_record_keeps_external_changes() {
+        let store = temp_store(10);
+        store
+            .record("a.yaml", None, "v1", RevisionReason::UserEdit)
+            .await
+            .unwrap();
+        // 文件在外部被改为 v1-edited 后再保存
+        store
+            .record("a.yaml", Some("v1-edited"), "v2", RevisionReason::UserEdit)
+            .await
+            .unwrap();
+
+        let revisions = store.list("a.yaml").await.unwrap();
+        assert_eq!(
+            reasons(&revisions),
+            [
+                RevisionReason::UserEdit,
+                RevisionReason::External,
+                RevisionReason::UserEdit,
+            ]
+        );
+        assert_eq!(read(&store, &revisions[0]).await, "v2");
+        assert_eq!(read(&store, &revisions[1]).await, "v1-edited");
+
+        let _ = fs::remove_dir_all(&store.profiles_dir).await;
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn test_prune_keeps_newest() {
+        let store = temp_store(2);
+        for data in ["v1", "v2", "v3", "v4"] {
+            store
+                .record("a.yaml", None, data, RevisionReason::UserEdit)
+                .await
+                .unwrap();
+        }
+
+        let revisions = store.list("a.yaml").await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(read(&store, &revisions[0]).await, "v4");
+        assert_eq!(read(&store, &revisions[1]).await, "v3");
+
+        let _ = fs::remove_dir_all(&store.profiles_dir).await;
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn test_restore_records_revision() {
+        let store = temp_store(10);
+        fs::create_dir_all(&store.profiles_dir).await.unwrap();
+        let path = store.profiles_dir.join("a.yaml");
+
+        fs::write(&path, "v2").await.unwrap();
+        store
+            .record("a.yaml", Some("v1"), "v2", RevisionReason::UserEdit)
+            .await
+            .unwrap();
+        let initial = store.list("a.yaml").await.unwrap()[1].id.clone();
+
+        store.restore("a.yaml", &initial).await.unwrap();
+        assert_eq!(fs::read_to_string(&path).await.unwrap(), "v1");
+        let revisions = store.list("a.yaml").await.unwrap();
+        assert_eq!(revisions[0].reason, RevisionReason::Restore);
+        assert_eq!(read(&store, &revisions[0]).await, "v1");
+
+        let _ = fs::remove_dir_all(&store.profiles_dir).await;
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn test_tracked_files_and_remove() {
+        let store = temp_store(10);
+        assert!(store.tracked_files().unwrap().is_empty());
+        for file in ["a.yaml", "b.js"] {
+            store
+                .record(file, None, "data", RevisionReason::UserEdit)
+                .await
+                .unwrap();
+        }
+
+        let mut files = store.tracked_files().unwrap();
+        files.sort();
+        assert_eq!(files, [String::from("a.yaml"), String::from("b.js")]);
+
+        store.remove("a.yaml").await.unwrap();
+        assert_eq!(store.tracked_files().unwrap(), [String::from("b.js")]);
+        assert!(store.list("a.yaml").await.unwrap().is_empty());
+        // 没有历史时删除也不报错
+        store.remove("a.yaml").await.unwrap();
+
+        let _ = fs::remove_dir_all(&store.profiles_dir).await;
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn test_rejects_invalid_ids_and_files() {
+        let store = temp_store(10);
+        store
+            .record("a.yaml", None, "data", RevisionReason::UserEdit)
+            .await
+            .unwrap();
+        fs::write(store.profiles_dir.join("secret"), "secret")
+            .await
+            .unwrap();
+
+        for id in ["", "../../secret", "1-user_edit", "not-a-revision"] {
+            assert!(store.read("a.yaml", id).await.is_err());
+            assert!(store.restore("a.yaml", id).await.is_err());
+        }
+        for file in ["", "../a.yaml", "sub/a.yaml", "sub\\a.yaml", ".hidden"] {
+            assert!(store.list(file).await.is_err());
+            assert!(
+                store
+                    .record(file, None, "data", RevisionReason::UserEdit)
+                    .await
+                    .is_err()
+            );
+            assert!(store.remove(file).await.is_err());
+        }
+
+        let _ = fs::remove_dir_all(&store.profiles_dir).await;
+    }
+}
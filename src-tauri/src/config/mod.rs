@@ -2,14 +2,20 @@ mod clash;
 #[allow(clippy::module_inception)]
 mod config;
 mod encrypt;
+mod history;
+mod prfdiff;
 mod prfitem;
 pub mod profiles;
 mod runtime;
+mod subscription;
 mod verge;
 
-pub use self::{clash::*, config::*, encrypt::*, prfitem::*, profiles::*, runtime::*, verge::*};
+pub use self::{
+    clash::*, config::*, encrypt::*, history::*, prfdiff::*, prfitem::*, profiles::*, runtime::*,
+    verge::*,
+};
 
 pub const DEFAULT_PAC: &str = r#"function FindProxyForURL(url, host) {
-  return "PROXY 127.0.0.1:%mixed-port%; SOCKS5 127.0.0.1:%mixed-port%; DIRECT;";
+  return "PROXY %proxy-host%:%mixed-port%; SOCKS5 %proxy-host%:%mixed-port%; DIRECT;";
 }
 "#;
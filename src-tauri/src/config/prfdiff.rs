@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::{Mapping, Value};
+use smartstring::alias::String;
+use std::collections::{BTreeSet, HashMap};
+
+/// how many update diffs are kept for every profile item
+pub const PRF_DIFF_HISTORY_LIMIT: usize = 10;
+
+/// Changes between two versions of a subscription
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PrfDiff {
+    /// updated time
+    pub updated: usize,
+
+    #[serde(default)]
+    pub proxies_added: Vec<String>,
+
+    #[serde(default)]
+    pub proxies_removed: Vec<String>,
+
+    /// proxies (matched by `name`) whose fields have changed
+    #[serde(default)]
+    pub proxies_changed: Vec<PrfDiffProxy>,
+
+    #[serde(default)]
+    pub groups_added: Vec<String>,
+
+    #[serde(default)]
+    pub groups_removed: Vec<String>,
+
+    /// membership changes of the groups that exist in both versions
+    #[serde(default)]
+    pub groups_changed: Vec<PrfDiffGroup>,
+
+    #[serde(default)]
+    pub rules_before: usize,
+
+    #[serde(default)]
+    pub rules_after: usize,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PrfDiffProxy {
+    pub name: String,
+    /// the keys whose value differs
+    pub fields: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PrfDiffGroup {
+    pub name: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl PrfDiff {
+    /// Compare the old and the new profile mapping
+    pub fn compute(old: &Mapping, new: &Mapping) -> Self {
+        let old_proxies = named_items(old, "proxies");
+        let new_proxies = named_items(new, "proxies");
+
+        let mut proxies_changed = vec![];
+        for (name, new_proxy) in new_proxies.iter() {
+            if let Some(old_proxy) = old_proxies.get(name) {
+                let fields = changed_fields(old_proxy, new_proxy);
+                if !fields.is_empty() {
+                    proxies_changed.push(PrfDiffProxy {
+                        name: name.clone(),
+                        fields,
+                    });
+                }
+            }
+        }
+        proxies_changed.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let old_groups = named_items(old, "proxy-groups");
+        let new_groups = named_items(new, "proxy-groups");
+
+        let mut groups_changed = vec![];
+        for (name, new_group) in new_groups.iter() {
+            if let Some(old_group) = old_groups.get(name) {
+                let old_members = group_members(old_group);
+                let new_members = group_members(new_group);
+                let added: Vec<String> = new_members.difference(&old_members).cloned().collect();
+                let removed: Vec<String> = old_members.difference(&new_members).cloned().collect();
+                if !added.is_empty() || !removed.is_empty() {
+                    groups_changed.push(PrfDiffGroup {
+                        name: name.clone(),
+                        added,
+                        removed,
+                    });
+                }
+            }
+        }
+        groups_changed.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            updated: chrono::Local::now().timestamp() as usize,
+            proxies_added: missing_keys(&new_proxies, &old_proxies),
+            proxies_removed: missing_keys(&old_proxies, &new_proxies),
+            proxies_changed,
+            groups_added: missing_keys(&new_groups, &old_groups),
+            groups_removed: missing_keys(&old_groups, &new_groups),
+            groups_changed,
+            rules_before: rules_count(old),
+            rules_after: rules_count(new),
+        }
+    }
+
+    /// whether nothing has changed
+    pub const fn is_empty(&self) -> bool {
+        self.proxies_added.is_empty()
+            && self.proxies_removed.is_empty()
+            && self.proxies_changed.is_empty()
+            && self.groups_added.is_empty()
+            && self.groups_removed.is_empty()
+            && self.groups_changed.is_empty()
+            && self.rules_before == self.rules_after
+    }
+
+    /// short report used for notices and logs
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!(
+            "proxies +{} -{} ~{}",
+            self.proxies_added.len(),
+            self.proxies_removed.len(),
+            self.proxies_changed.len()
+        )];
+        if !self.groups_added.is_empty()
+            || !self.groups_removed.is_empty()
+            || !self.groups_changed.is_empty()
+        {
+            parts.push(format!(
+                "groups +{} -{} ~{}",
+                self.groups_added.len(),
+                self.groups_removed.len(),
+                self.groups_changed.len()
+            ));
+        }
+        if self.rules_before != self.rules_after {
+            parts.push(format!(
+                "rules {} -> {}",
+                self.rules_before, self.rules_after
+            ));
+        }
+        parts.join(", ").into()
+    }
+}
+
+/// collect the mappings of a sequence field by their `name`
+fn named_items<'a>(config: &'a Mapping, field: &str) -> HashMap<String, &'a Mapping> {
+    config
+        .get(field)
+        .and_then(Value::as_sequence)
+        .map(|seq| {
+            seq.iter()
+                .filter_map(Value::as_mapping)
+                .filter_map(|item| {
+                    let name = item.get("name").and_then(Value::as_str)?;
+                    Some((name.into(), item))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn missing_keys(
+    from: &HashMap<String, &Mapping>,
+    other: &HashMap<String, &Mapping>,
+) -> Vec<String> {
+    let mut keys: Vec<String> = from
+        .keys()
+        .filter(|key| !other.contains_key(*key))
+        .cloned()
+        .collect();
+    keys.sort();
+    keys
+}
+
+fn changed_fields(old: &Mapping, new: &Mapping) -> Vec<String> {
+    let mut fields = BTreeSet::new();
+    for (key, value) in new.iter() {
+        if old.get(key) != Some(value) {
+            fields.insert(key_name(key));
+        }
+    }
+    for key in old.keys() {
+        if !new.contains_key(key) {
+            fields.insert(key_name(key));
+        }
+    }
+    fields.into_iter().collect()
+}
+
+fn key_name(key: &Value) -> String {
+    match key {
+        Value::String(s) => s.as_str().into(),
+        other => serde_yaml_ng::to_string(other)
+            .unwrap_or_default()
+            .trim()
+            .into(),
+    }
+}
+
+fn group_members(group: &Mapping) -> BTreeSet<String> {
+    group
+        .get("proxies")
+        .and_then(Value::as_sequence)
+        .map(|seq| {
+            seq.iter()
+                .filter_map(Value::as_str)
+                .map(Into::into)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn rules_count(config: &Mapping) -> usize {
+    config
+        .get("rules")
+        .and_then(Value::as_sequence)
+        .map_or(0, Vec::len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_profile_diff() {
+        let old: Mapping = serde_yaml_ng::from_str(
+            r"
+proxies:
+  - { name: a, type: ss, server: 1.1.1.1, port: 443 }
+  - { name: b, type: ss, server: 2.2.2.2, port: 443 }
+  - { name: c, type: ss, server: 3.3.3.3, port: 443 }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [a, b, c] }
+  - { name: OLD, type: select, proxies: [a] }
+rules:
+  - MATCH,PROXY
+",
+        )
+        .expect("Failed to parse old config");
+        let new: Mapping = serde_yaml_ng::from_str(
+            r"
+proxies:
+  - { name: a, type: ss, server: 1.1.1.1, port: 443 }
+  - { name: b, type: ss, server: 9.9.9.9, port: 8443 }
+  - { name: d, type: ss, server: 4.4.4.4, port: 443 }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [a, b, d] }
+rules:
+  - DOMAIN,example.com,DIRECT
+  - MATCH,PROXY
+",
+        )
+        .expect("Failed to parse new config");
+
+        let diff = PrfDiff::compute(&old, &new);
+        assert_eq!(diff.proxies_added, vec![String::from("d")]);
+        assert_eq!(diff.proxies_removed, vec![String::from("c")]);
+        assert_eq!(
+            diff.proxies_changed,
+            vec![PrfDiffProxy {
+                name: "b".into(),
+                fields: vec!["port".into(), "server".into()],
+            }]
+        );
+        assert_eq!(diff.groups_removed, vec![String::from("OLD")]);
+        assert_eq!(
+            diff.groups_changed,
+            vec![PrfDiffGroup {
+                name: "PROXY".into(),
+                added: vec!["d".into()],
+                removed: vec!["c".into()],
+            }]
+        );
+        assert_eq!((diff.rules_before, diff.rules_after), (1, 2));
+        assert!(!diff.is_empty());
+        assert!(PrfDiff::compute(&old, &old).is_empty());
+    }
+}
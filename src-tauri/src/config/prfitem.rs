@@ -1,14 +1,17 @@
 use crate::{
-    config::profiles,
+    config::{
+        PrfDiff, ProfileHistory, RevisionReason, profiles, subscription::convert_subscription,
+    },
     logging,
     utils::{
         dirs, help,
         logging::Type,
-        network::{NetworkManager, ProxyType},
+        network::{CacheValidators, NetworkManager, ProxyType},
         tmpl,
     },
 };
 use anyhow::{Context as _, Result, bail};
+use isahc::http::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_yaml_ng::Mapping;
 use smartstring::alias::String;
@@ -57,6 +60,30 @@ pub struct PrfItem {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub home: Option<String>,
 
+    /// changes reported by the latest subscription updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diffs: Option<Vec<PrfDiff>>,
+
+    /// failed subscription updates in a row
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_failures: Option<u32>,
+
+    /// the subscription failed to update too many times in a row
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale: Option<bool>,
+
+    /// `ETag` of the last downloaded subscription
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+
+    /// `Last-Modified` of the last downloaded subscription
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+
+    /// sha256 of the profile file written by the last update
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+
     /// the file data
     #[serde(skip)]
     pub file_data: Option<String>,
@@ -93,9 +120,40 @@ pub struct PrfOption {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub self_proxy: Option<bool>,
 
+    /// local HTTP proxy port the download goes through, only set while updating
+    #[serde(skip)]
+    pub proxy_port: Option<u16>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub update_interval: Option<u64>,
 
+    /// cron expression for automatic updates, e.g. `0 3 * * *` or `03:00 daily`
+    /// takes precedence over `update_interval`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_cron: Option<String>,
+
+    /// daily `HH:MM-HH:MM` window in which no automatic update runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_blackout: Option<String>,
+
+    /// for `remote` profile
+    /// transports tried in order when updating: `direct`, `self_proxy`, `system_proxy`,
+    /// `core:<group>` or `core:<group>/<node>`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_transports: Option<Vec<String>>,
+
+    /// for `remote` profile
+    /// extra attempts per transport, with exponential backoff
+    /// default is 2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub update_retries: Option<u32>,
+
+    /// for `remote` profile
+    /// failed updates in a row before the subscription is marked stale
+    /// default is 3
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stale_after_failures: Option<u32>,
+
     /// for `remote` profile
     /// HTTP request timeout in seconds
     /// default is 60 seconds
@@ -121,6 +179,12 @@ pub struct PrfOption {
     pub proxies: Option<String>,
 
     pub groups: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_providers: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy_providers: Option<String>,
 }
 
 impl PrfOption {
@@ -136,11 +200,20 @@ impl PrfOption {
                     .or(result.danger_accept_invalid_certs);
                 result.allow_auto_update = b_ref.allow_auto_update.or(result.allow_auto_update);
                 result.update_interval = b_ref.update_interval.or(result.update_interval);
+                result.update_cron = b_ref.update_cron.clone().or(result.update_cron);
+                result.update_blackout = b_ref.update_blackout.clone().or(result.update_blackout);
+                result.update_transports =
+                    b_ref.update_transports.clone().or(result.update_transports);
+                result.update_retries = b_ref.update_retries.or(result.update_retries);
+                result.stale_after_failures =
+                    b_ref.stale_after_failures.or(result.stale_after_failures);
                 result.merge = b_ref.merge.clone().or(result.merge);
                 result.script = b_ref.script.clone().or(result.script);
                 result.rules = b_ref.rules.clone().or(result.rules);
                 result.proxies = b_ref.proxies.clone().or(result.proxies);
                 result.groups = b_ref.groups.clone().or(result.groups);
+                result.rule_providers = b_ref.rule_providers.clone().or(result.rule_providers);
+                result.proxy_providers = b_ref.proxy_providers.clone().or(result.proxy_providers);
                 result.timeout_seconds = b_ref.timeout_seconds.or(result.timeout_seconds);
                 Some(result)
             }
@@ -192,15 +265,23 @@ impl PrfItem {
         file_data: Option<String>,
         option: Option<&PrfOption>,
     ) -> Result<Self> {
+        let file_data = match file_data {
+            Some(data) => Some(Self::convert_share_links(&data)?.unwrap_or(data)),
+            None => None,
+        };
         let uid = help::get_uid("L").into();
         let file = format!("{uid}.yaml").into();
         let opt_ref = option.as_ref();
         let update_interval = opt_ref.and_then(|o| o.update_interval);
+        let update_cron = opt_ref.and_then(|o| o.update_cron.clone());
+        let update_blackout = opt_ref.and_then(|o| o.update_blackout.clone());
         let mut merge = opt_ref.and_then(|o| o.merge.clone());
         let mut script = opt_ref.and_then(|o| o.script.clone());
         let mut rules = opt_ref.and_then(|o| o.rules.clone());
         let mut proxies = opt_ref.and_then(|o| o.proxies.clone());
         let mut groups = opt_ref.and_then(|o| o.groups.clone());
+        let mut rule_providers = opt_ref.and_then(|o| o.rule_providers.clone());
+        let mut proxy_providers = opt_ref.and_then(|o| o.proxy_providers.clone());
 
         if merge.is_none() {
             let merge_item = &mut Self::from_merge(None)?;
@@ -227,6 +308,16 @@ impl PrfItem {
             profiles::profiles_append_item_safe(groups_item).await?;
             groups = groups_item.uid.clone();
         }
+        if rule_providers.is_none() {
+            let rule_providers_item = &mut Self::from_rule_providers()?;
+            profiles::profiles_append_item_safe(rule_providers_item).await?;
+            rule_providers = rule_providers_item.uid.clone();
+        }
+        if proxy_providers.is_none() {
+            let proxy_providers_item = &mut Self::from_proxy_providers()?;
+            profiles::profiles_append_item_safe(proxy_providers_item).await?;
+            proxy_providers = proxy_providers_item.uid.clone();
+        }
         Ok(Self {
             uid: Some(uid),
             itype: Some("local".into()),
@@ -238,14 +329,24 @@ impl PrfItem {
             extra: None,
             option: Some(PrfOption {
                 update_interval,
+                update_cron,
+                update_blackout,
                 merge,
                 script,
                 rules,
                 proxies,
                 groups,
+                rule_providers,
+                proxy_providers,
                 ..PrfOption::default()
             }),
             home: None,
+            diffs: None,
+            update_failures: None,
+            stale: None,
+            etag: None,
+            last_modified: None,
+            content_hash: None,
             updated: Some(chrono::Local::now().timestamp() as usize),
             file_data: Some(file_data.unwrap_or_else(|| tmpl::ITEM_LOCAL.into())),
         })
@@ -258,23 +359,54 @@ impl PrfItem {
         name: Option<&String>,
         desc: Option<&String>,
         option: Option<&PrfOption>,
+    ) -> Result<Self> {
+        Self::fetch_remote(url, name, desc, option, None).await
+    }
+
+    /// ## Remote type
+    /// download the subscription again, revalidating against the `cached` item.
+    /// When the content did not change the returned item has no `file_data`
+    pub async fn from_url_cached(
+        url: &str,
+        option: Option<&PrfOption>,
+        cached: Option<&Self>,
+    ) -> Result<Self> {
+        Self::fetch_remote(url, None, None, option, cached).await
+    }
+
+    async fn fetch_remote(
+        url: &str,
+        name: Option<&String>,
+        desc: Option<&String>,
+        option: Option<&PrfOption>,
+        cached: Option<&Self>,
     ) -> Result<Self> {
         let with_proxy = option.is_some_and(|o| o.with_proxy.unwrap_or(false));
         let self_proxy = option.is_some_and(|o| o.self_proxy.unwrap_or(false));
+        let proxy_port = option.and_then(|o| o.proxy_port);
         let accept_invalid_certs =
             option.is_some_and(|o| o.danger_accept_invalid_certs.unwrap_or(false));
         let allow_auto_update = option.map(|o| o.allow_auto_update.unwrap_or(true));
         let user_agent = option.and_then(|o| o.user_agent.clone());
         let update_interval = option.and_then(|o| o.update_interval);
+        let update_cron = option.and_then(|o| o.update_cron.clone());
+        let update_blackout = option.and_then(|o| o.update_blackout.clone());
+        let update_transports = option.and_then(|o| o.update_transports.clone());
+        let update_retries = option.and_then(|o| o.update_retries);
+        let stale_after_failures = option.and_then(|o| o.stale_after_failures);
         let timeout = option.and_then(|o| o.timeout_seconds).unwrap_or(20);
         let mut merge = option.and_then(|o| o.merge.clone());
         let mut script = option.and_then(|o| o.script.clone());
         let mut rules = option.and_then(|o| o.rules.clone());
         let mut proxies = option.and_then(|o| o.proxies.clone());
         let mut groups = option.and_then(|o| o.groups.clone());
+        let mut rule_providers = option.and_then(|o| o.rule_providers.clone());
+        let mut proxy_providers = option.and_then(|o| o.proxy_providers.clone());
 
         // 选择代理类型
-        let proxy_type = if self_proxy {
+        let proxy_type = if let Some(port) = proxy_port {
+            ProxyType::Port(port)
+        } else if self_proxy {
             ProxyType::Localhost
         } else if with_proxy {
             ProxyType::System
@@ -282,18 +414,30 @@ impl PrfItem {
             ProxyType::None
         };
 
+        // 本地文件仍是上次更新写入的内容时才做缓存校验，
+        // 恢复历史版本或手动编辑后需要完整下载
+        let cached = match cached {
+            Some(item) if item.is_file_unmodified().await => Some(item),
+            _ => None,
+        };
+        let validators = CacheValidators {
+            etag: cached.and_then(|item| item.etag.as_deref()),
+            last_modified: cached.and_then(|item| item.last_modified.as_deref()),
+        };
+
         // 使用网络管理器发送请求
         logging!(info, Type::Network, "[导入订阅] 准备发送网络请求: url={}, proxy_type={:?}, timeout={}s", 
             url, proxy_type, timeout);
         let request_start = std::time::Instant::now();
         
         let resp = match NetworkManager::new()
-            .get_with_interrupt(
+            .get_conditional(
                 url,
                 proxy_type,
                 Some(timeout),
                 user_agent.clone(),
                 accept_invalid_certs,
+                validators,
             )
             .await
         {
@@ -313,7 +457,8 @@ impl PrfItem {
 
         let status_code = resp.status();
         logging!(debug, Type::Network, "[导入订阅] 检查响应状态码: {}", status_code);
-        if !status_code.is_success() {
+        let not_modified = status_code == StatusCode::NOT_MODIFIED && cached.is_some();
+        if !status_code.is_success() && !not_modified {
             logging!(error, Type::Network, "[导入订阅] HTTP 状态码错误: {}", status_code);
             bail!("failed to fetch remote profile with status {status_code}")
         }
@@ -388,6 +533,28 @@ impl PrfItem {
             None => None,
         };
 
+        let header_value =
+            |key: &str| -> Option<String> { header.get(key)?.to_str().ok().map(Into::into) };
+        let etag = header_value("ETag");
+        let last_modified = header_value("Last-Modified");
+
+        // 订阅内容未变化时只更新元数据，不重写文件
+        let unchanged = |cached: &Self| Self {
+            extra: extra.or(cached.extra),
+            home: home.clone().or_else(|| cached.home.clone()),
+            etag: etag.clone().or_else(|| cached.etag.clone()),
+            last_modified: last_modified
+                .clone()
+                .or_else(|| cached.last_modified.clone()),
+            content_hash: cached.content_hash.clone(),
+            updated: Some(chrono::Local::now().timestamp() as usize),
+            ..Self::default()
+        };
+        if not_modified && let Some(cached) = cached {
+            logging!(info, Type::Network, "[导入订阅] 订阅未修改 (304)");
+            return Ok(unchanged(cached));
+        }
+
         let uid = help::get_uid("R").into();
         let file = format!("{uid}.yaml").into();
         let name = name.map(|s| s.to_owned()).unwrap_or_else(|| {
@@ -408,6 +575,22 @@ impl PrfItem {
         // process the charset "UTF-8 with BOM"
         let data = data.trim_start_matches('\u{feff}');
 
+        // share-link / base64 subscriptions are converted into a clash profile first
+        let converted = Self::convert_share_links(data)?;
+        let data = converted.as_deref().unwrap_or(data);
+
+        let content_hash: String = help::sha256_hex(data.as_bytes()).into();
+        if let Some(cached) = cached
+            && cached.content_hash.as_ref() == Some(&content_hash)
+        {
+            logging!(
+                info,
+                Type::Config,
+                "[导入订阅] 订阅内容与本地一致，跳过解析"
+            );
+            return Ok(unchanged(cached));
+        }
+
         logging!(debug, Type::Config, "[导入订阅] 开始解析 YAML 格式...");
         let yaml_start = std::time::Instant::now();
         // check the data whether the valid yaml format
@@ -458,6 +641,16 @@ impl PrfItem {
             profiles::profiles_append_item_safe(groups_item).await?;
             groups = groups_item.uid.clone();
         }
+        if rule_providers.is_none() {
+            let rule_providers_item = &mut Self::from_rule_providers()?;
+            profiles::profiles_append_item_safe(rule_providers_item).await?;
+            rule_providers = rule_providers_item.uid.clone();
+        }
+        if proxy_providers.is_none() {
+            let proxy_providers_item = &mut Self::from_proxy_providers()?;
+            profiles::profiles_append_item_safe(proxy_providers_item).await?;
+            proxy_providers = proxy_providers_item.uid.clone();
+        }
 
         Ok(Self {
             uid: Some(uid),
@@ -470,20 +663,89 @@ impl PrfItem {
             extra,
             option: Some(PrfOption {
                 update_interval,
+                update_cron,
+                update_blackout,
+                update_transports,
+                update_retries,
+                stale_after_failures,
                 merge,
                 script,
                 rules,
                 proxies,
                 groups,
+                rule_providers,
+                proxy_providers,
                 allow_auto_update,
                 ..PrfOption::default()
             }),
             home,
+            diffs: None,
+            update_failures: None,
+            stale: None,
+            etag,
+            last_modified,
+            content_hash: Some(content_hash),
             updated: Some(chrono::Local::now().timestamp() as usize),
             file_data: Some(data.into()),
         })
     }
 
+    /// whether the profile file still has the content written by the last update
+    async fn is_file_unmodified(&self) -> bool {
+        let (Some(file), Some(content_hash)) = (&self.file, &self.content_hash) else {
+            return false;
+        };
+        let Ok(dir) = dirs::app_profiles_dir() else {
+            return false;
+        };
+        fs::read(dir.join(file.as_str()))
+            .await
+            .is_ok_and(|data| help::sha256_hex(&data) == content_hash.as_str())
+    }
+
+    /// convert a share-link subscription into clash yaml
+    /// returns `None` when the data does not need to be converted
+    fn convert_share_links(data: &str) -> Result<Option<String>> {
+        let Some(converted) = convert_subscription(data) else {
+            return Ok(None);
+        };
+
+        if !converted.failures.is_empty() {
+            let summary = converted.failure_summary();
+            logging!(
+                warn,
+                Type::Config,
+                "Warning: [订阅转换] {} 行无法解析: {}",
+                converted.failures.len(),
+                summary
+            );
+            if converted.proxy_count > 0 {
+                crate::core::handle::Handle::notice_message(
+                    "import_sub_url::convert_partial",
+                    summary,
+                );
+            }
+        }
+
+        if converted.proxy_count == 0 {
+            bail!(
+                "subscription does not contain any valid share link: {}",
+                converted.failure_summary()
+            );
+        }
+
+        logging!(
+            info,
+            Type::Config,
+            "[订阅转换] 已将 {} 个分享链接转换为 Clash 配置",
+            converted.proxy_count
+        );
+
+        let yaml = serde_yaml_ng::to_string(&converted.config)
+            .context("failed to serialize the converted subscription")?;
+        Ok(Some(yaml.into()))
+    }
+
     /// ## Merge type (enhance)
     /// create the enhanced item by using `merge` rule
     pub fn from_merge(uid: Option<String>) -> Result<Self> {
@@ -568,6 +830,36 @@ impl PrfItem {
         })
     }
 
+    /// ## Rule providers type (enhance)
+    pub fn from_rule_providers() -> Result<Self> {
+        let uid = help::get_uid("rp").into();
+        let file = format!("{uid}.yaml").into(); // yaml ext
+
+        Ok(Self {
+            uid: Some(uid),
+            itype: Some("rule-providers".into()),
+            file: Some(file),
+            updated: Some(chrono::Local::now().timestamp() as usize),
+            file_data: Some(tmpl::ITEM_RULE_PROVIDERS.into()),
+            ..Default::default()
+        })
+    }
+
+    /// ## Proxy providers type (enhance)
+    pub fn from_proxy_providers() -> Result<Self> {
+        let uid = help::get_uid("pp").into();
+        let file = format!("{uid}.yaml").into(); // yaml ext
+
+        Ok(Self {
+            uid: Some(uid),
+            itype: Some("proxy-providers".into()),
+            file: Some(file),
+            updated: Some(chrono::Local::now().timestamp() as usize),
+            file_data: Some(tmpl::ITEM_PROXY_PROVIDERS.into()),
+            ..Default::default()
+        })
+    }
+
     /// get the file data
     pub async fn read_file(&self) -> Result<String> {
         let file = self
@@ -581,8 +873,8 @@ impl PrfItem {
         Ok(content.into())
     }
 
-    /// save the file data
-    pub async fn save_file(&self, data: String) -> Result<()> {
+    /// save the file data, recording the previous content as a revision made for `reason`
+    pub async fn save_file(&self, data: String, reason: RevisionReason) -> Result<()> {
         let file = self
             .file
             .as_ref()
@@ -597,6 +889,10 @@ impl PrfItem {
                 )
             })?;
         let path = profiles_dir.join(file.as_str());
+        let previous = fs::read_to_string(&path).await.ok();
+        if let Err(e) = ProfileHistory::record(file, previous.as_deref(), &data, reason).await {
+            logging!(warn, Type::Config, "Warning: 记录历史版本失败: {e}");
+        }
         fs::write(path, data.as_bytes())
             .await
             .context("failed to save the file")
@@ -628,6 +924,16 @@ impl PrfItem {
     pub fn current_groups(&self) -> Option<String> {
         self.option.as_ref().and_then(|o| o.groups.clone())
     }
+
+    /// 获取current指向的订阅的rule-providers
+    pub fn current_rule_providers(&self) -> Option<String> {
+        self.option.as_ref().and_then(|o| o.rule_providers.clone())
+    }
+
+    /// 获取current指向的订阅的proxy-providers
+    pub fn current_proxy_providers(&self) -> Option<String> {
+        self.option.as_ref().and_then(|o| o.proxy_providers.clone())
+    }
 }
 
 // 向前兼容，默认为订阅启用自动更新
@@ -1,4 +1,7 @@
-use super::{PrfOption, prfitem::PrfItem};
+use super::{
+    PRF_DIFF_HISTORY_LIMIT, PROFILE_HISTORY_DIR, PrfOption, ProfileHistory, RevisionReason,
+    prfitem::PrfItem,
+};
 use crate::utils::{
     dirs::{self, PathBufExec as _},
     help,
@@ -118,6 +121,32 @@ impl IProfiles {
         }
     }
 
+    /// 合并恢复的 item，uid 相同的原地替换，其余追加到末尾
+    /// 当前 current 无效时使用恢复的 current
+    pub fn merge_items(&mut self, restored: Vec<PrfItem>, current: Option<String>) {
+        let items = self.items.get_or_insert_with(Vec::new);
+        for item in restored {
+            match items
+                .iter_mut()
+                .find(|e| e.uid.is_some() && e.uid == item.uid)
+            {
+                Some(existing) => *existing = item,
+                None => items.push(item),
+            }
+        }
+
+        let current_valid = self
+            .current
+            .as_ref()
+            .is_some_and(|uid| items.iter().any(|e| e.uid.as_ref() == Some(uid)));
+        if !current_valid
+            && let Some(uid) = current
+            && items.iter().any(|e| e.uid.as_ref() == Some(&uid))
+        {
+            self.current = Some(uid);
+        }
+    }
+
     pub const fn get_current(&self) -> Option<&String> {
         self.current.as_ref()
     }
@@ -207,6 +236,40 @@ impl IProfiles {
         Ok(())
     }
 
+    /// create the provider chain items of a profile made before they existed
+    /// returns `true` when the profile was changed
+    pub async fn ensure_provider_items(&mut self, uid: &String) -> Result<bool> {
+        let option = self.get_item(uid)?.option.clone().unwrap_or_default();
+        let mut rule_providers = option.rule_providers;
+        let mut proxy_providers = option.proxy_providers;
+        if rule_providers.is_some() && proxy_providers.is_some() {
+            return Ok(false);
+        }
+
+        if rule_providers.is_none() {
+            let item = &mut PrfItem::from_rule_providers()?;
+            self.append_item(item).await?;
+            rule_providers = item.uid.clone();
+        }
+        if proxy_providers.is_none() {
+            let item = &mut PrfItem::from_proxy_providers()?;
+            self.append_item(item).await?;
+            proxy_providers = item.uid.clone();
+        }
+
+        if let Some(item) = self
+            .items
+            .iter_mut()
+            .flatten()
+            .find(|item| item.uid.as_ref() == Some(uid))
+        {
+            let option = item.option.get_or_insert_with(PrfOption::default);
+            option.rule_providers = rule_providers;
+            option.proxy_providers = proxy_providers;
+        }
+        Ok(true)
+    }
+
     /// reorder items
     pub async fn reorder(&mut self, active_id: &String, over_id: &String) -> Result<()> {
         let mut items = self.items.take().unwrap_or_default();
@@ -258,7 +321,7 @@ impl IProfiles {
     }
 
     /// be used to update the remote item
-    /// only patch `updated` `extra` `file_data`
+    /// only patch `updated` `extra` `file_data` and append `diffs`
     pub async fn update_item(&mut self, uid: &String, item: &mut PrfItem) -> Result<()> {
         if self.items.is_none() {
             self.items = Some(vec![]);
@@ -275,7 +338,20 @@ impl IProfiles {
                     each.extra = item.extra;
                     each.updated = item.updated;
                     each.home = item.home.to_owned();
+                    // 更新成功，清除失败计数
+                    each.update_failures = None;
+                    each.stale = None;
+                    each.etag = item.etag.take();
+                    each.last_modified = item.last_modified.take();
+                    each.content_hash = item.content_hash.take();
                     each.option = PrfOption::merge(each.option.as_ref(), item.option.as_ref());
+                    if let Some(diffs) = item.diffs.take() {
+                        let mut history = each.diffs.take().unwrap_or_default();
+                        history.extend(diffs);
+                        let overflow = history.len().saturating_sub(PRF_DIFF_HISTORY_LIMIT);
+                        history.drain(..overflow);
+                        each.diffs = Some(history);
+                    }
                     // save the file data
                     // move the field value after save
                     if let Some(file_data) = item.file_data.take() {
@@ -291,6 +367,18 @@ impl IProfiles {
 
                         let path = dirs::app_profiles_dir()?.join(file.as_str());
 
+                        let previous = fs::read_to_string(&path).await.ok();
+                        if let Err(e) = ProfileHistory::record(
+                            &file,
+                            previous.as_deref(),
+                            &file_data,
+                            RevisionReason::AutoUpdate,
+                        )
+                        .await
+                        {
+                            logging!(warn, Type::Config, "Warning: 记录订阅历史版本失败: {e}");
+                        }
+
                         fs::write(&path, file_data.as_bytes())
                             .await
                             .with_context(|| format!("failed to write to file \"{file}\""))?;
@@ -304,6 +392,28 @@ impl IProfiles {
         self.save_file().await
     }
 
+    /// count a failed subscription update, the item is marked stale after `stale_after` failures in a row
+    /// returns whether the item just became stale
+    pub async fn record_update_failure(&mut self, uid: &String, stale_after: u32) -> Result<bool> {
+        let Some(item) = self
+            .items
+            .as_mut()
+            .and_then(|items| items.iter_mut().find(|each| each.uid.as_ref() == Some(uid)))
+        else {
+            bail!("failed to find the profile item \"uid:{uid}\"");
+        };
+
+        let failures = item.update_failures.unwrap_or(0).saturating_add(1);
+        let was_stale = item.stale.unwrap_or(false);
+        item.update_failures = Some(failures);
+        if failures >= stale_after {
+            item.stale = Some(true);
+        }
+
+        self.save_file().await?;
+        Ok(!was_stale && failures >= stale_after)
+    }
+
     /// delete item
     /// if delete the current then return true
     pub async fn delete_item(&mut self, uid: &String) -> Result<bool> {
@@ -315,6 +425,8 @@ impl IProfiles {
         let rules_uid = item.option.as_ref().and_then(|e| e.rules.clone());
         let proxies_uid = item.option.as_ref().and_then(|e| e.proxies.clone());
         let groups_uid = item.option.as_ref().and_then(|e| e.groups.clone());
+        let rule_providers_uid = item.option.as_ref().and_then(|e| e.rule_providers.clone());
+        let proxy_providers_uid = item.option.as_ref().and_then(|e| e.proxy_providers.clone());
         let mut items = self.items.take().unwrap_or_default();
 
         // remove the main item (if exists) and delete its file
@@ -325,7 +437,7 @@ impl IProfiles {
                 .await;
         }
 
-        // remove related extension items (merge, script, rules, proxies, groups)
+        // remove related extension items (merge, script, rules, proxies, groups, providers)
         if let Some(file) = Self::take_item_file_by_uid(&mut items, merge_uid.clone()) {
             let _ = dirs::app_profiles_dir()?
                 .join(file.as_str())
@@ -356,6 +468,18 @@ impl IProfiles {
                 .remove_if_exists()
                 .await;
         }
+        if let Some(file) = Self::take_item_file_by_uid(&mut items, rule_providers_uid.clone()) {
+            let _ = dirs::app_profiles_dir()?
+                .join(file.as_str())
+                .remove_if_exists()
+                .await;
+        }
+        if let Some(file) = Self::take_item_file_by_uid(&mut items, proxy_providers_uid.clone()) {
+            let _ = dirs::app_profiles_dir()?
+                .join(file.as_str())
+                .remove_if_exists()
+                .await;
+        }
         // delete the original uid
         if current == *uid {
             self.current = None;
@@ -394,6 +518,32 @@ impl IProfiles {
         self.current.as_ref() == Some(index)
     }
 
+    /// 判断profile是否在使用中：current指向的订阅、它的增强项或全局增强项
+    pub fn is_in_use(&self, index: &String) -> bool {
+        if self.is_current_profile_index(index) || matches!(index.as_str(), "Merge" | "Script") {
+            return true;
+        }
+        let Some(item) = self
+            .current
+            .as_ref()
+            .and_then(|current| self.get_item(current).ok())
+        else {
+            return false;
+        };
+        [
+            item.current_merge(),
+            item.current_script(),
+            item.current_rules(),
+            item.current_proxies(),
+            item.current_groups(),
+            item.current_rule_providers(),
+            item.current_proxy_providers(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|uid| &uid == index)
+    }
+
     /// 获取所有的profiles(uid，名称)
     pub fn all_profile_uid_and_name(&self) -> Option<Vec<(&String, &String)>> {
         self.items.as_ref().map(|items| {
@@ -449,6 +599,7 @@ impl IProfiles {
             let entry = entry?;
             let path = entry.path();
 
+            // 跳过目录，包括历史版本目录
             if !path.is_file() {
                 continue;
             }
@@ -484,6 +635,27 @@ impl IProfiles {
             }
         }
 
+        // 历史版本随对应的文件保留，文件已不存在时一并清理
+        for file in ProfileHistory::tracked_files()? {
+            if active_files.contains(file.as_str()) || protected_files.contains(&file) {
+                continue;
+            }
+            match ProfileHistory::remove(&file).await {
+                Ok(_) => {
+                    logging!(info, Type::Config, "已清理冗余历史版本: {file}");
+                    deleted_files.push(format!("{PROFILE_HISTORY_DIR}/{file}").into());
+                }
+                Err(e) => {
+                    failed_deletions.push(format!("{PROFILE_HISTORY_DIR}/{file}: {e}").into());
+                    logging!(
+                        warn,
+                        Type::Config,
+                        "Warning: 清理历史版本失败: {file} - {e}"
+                    );
+                }
+            }
+        }
+
         let result = CleanupResult {
             total_files,
             deleted_files,
@@ -563,6 +735,17 @@ impl IProfiles {
                     {
                         active_files.insert(file);
                     }
+
+                    for uid in [&option.rule_providers, &option.proxy_providers]
+                        .into_iter()
+                        .flatten()
+                    {
+                        if let Ok(item) = self.get_item(uid)
+                            && let Some(file) = &item.file
+                        {
+                            active_files.insert(file);
+                        }
+                    }
                 }
             }
         }
@@ -580,6 +763,8 @@ impl IProfiles {
         // r12345678.yaml (rules)
         // p12345678.yaml (proxies)
         // g12345678.yaml (groups)
+        // rp12345678.yaml (rule-providers)
+        // pp12345678.yaml (proxy-providers)
 
         let patterns = [
             r"^[RL][a-zA-Z0-9]+\.yaml$",  // Remote/Local profiles
@@ -647,6 +832,20 @@ pub async fn profiles_reorder_safe(active_id: &String, over_id: &String) -> Resu
         .await
 }
 
+pub async fn profiles_ensure_provider_items_safe(uid: &String) -> Result<()> {
+    let changed = Config::profiles()
+        .await
+        .with_data_modify(|mut profiles| async move {
+            let changed = profiles.ensure_provider_items(uid).await?;
+            Ok((profiles, changed))
+        })
+        .await?;
+    if changed {
+        profiles_save_file_safe().await?;
+    }
+    Ok(())
+}
+
 pub async fn profiles_save_file_safe() -> Result<()> {
     Config::profiles()
         .await
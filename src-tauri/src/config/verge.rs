@@ -1,6 +1,6 @@
 use crate::config::Config;
 use crate::{
-    config::{DEFAULT_PAC, deserialize_encrypted, serialize_encrypted},
+    config::{DEFAULT_HISTORY_LIMIT, DEFAULT_PAC, deserialize_encrypted, serialize_encrypted},
     logging,
     utils::{dirs, help, i18n, logging::Type},
 };
@@ -8,6 +8,7 @@ use anyhow::Result;
 use log::LevelFilter;
 use serde::{Deserialize, Serialize};
 use smartstring::alias::String;
+use std::collections::BTreeMap;
 
 /// ### `verge.yaml` schema
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
@@ -102,9 +103,21 @@ pub struct IVerge {
     /// use pac mode
     pub proxy_auto_config: Option<bool>,
 
-    /// pac script content
+    /// pac script content, `%mixed-port%` and `%proxy-host%` are replaced when served
     pub pac_file_content: Option<String>,
 
+    /// generate the pac script from the rules of the running config instead of `pac_file_content`
+    pub pac_from_rules: Option<bool>,
+
+    /// also serve the pac script to other devices on the LAN
+    pub pac_lan_enabled: Option<bool>,
+
+    /// port of the LAN pac server
+    pub pac_lan_port: Option<u16>,
+
+    /// seconds clients may cache the pac script
+    pub pac_cache_max_age: Option<u64>,
+
     /// proxy host address
     pub proxy_host: Option<String>,
 
@@ -167,6 +180,37 @@ pub struct IVerge {
     /// Create backups automatically when critical configs change
     pub auto_backup_on_change: Option<bool>,
 
+    /// Deflate the files in backup archives instead of storing them
+    pub backup_compress: Option<bool>,
+
+    /// Backup retention for local and WebDAV backups, 0 or unset disables a rule:
+    /// keep the newest N backups
+    pub backup_keep_last: Option<usize>,
+
+    /// keep the newest backup of each of the last N days
+    pub backup_keep_daily: Option<usize>,
+
+    /// keep the newest backup of each of the last N weeks
+    pub backup_keep_weekly: Option<usize>,
+
+    /// keep the newest backup of each of the last N months
+    pub backup_keep_monthly: Option<usize>,
+
+    /// upper bound of the kept backups in MB
+    pub backup_max_total_size_mb: Option<u64>,
+
+    /// how many revisions are kept for every profile file
+    pub profile_history_limit: Option<usize>,
+
+    /// max iterations of a single loop in enhance scripts
+    pub script_max_loop_iterations: Option<u64>,
+
+    /// max run time of an enhance script in seconds
+    pub script_timeout_seconds: Option<u64>,
+
+    /// max memory an enhance script may allocate in MB, `0` to disable
+    pub script_max_memory_mb: Option<u64>,
+
     /// verge 的各种 port 用于覆盖 clash 的各种 port
     #[cfg(not(target_os = "windows"))]
     pub verge_redir_port: Option<u16>,
@@ -217,7 +261,56 @@ pub struct IVerge {
     )]
     pub webdav_password: Option<String>,
 
-    #[serde(skip)]
+    /// 备份加密口令，设置后备份文件会被加密 (加密存储)
+    #[serde(
+        serialize_with = "serialize_encrypted",
+        deserialize_with = "deserialize_encrypted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub backup_passphrase: Option<String>,
+
+    /// 启用本地 REST API
+    pub rest_api_enabled: Option<bool>,
+
+    /// 本地 REST API 的访问令牌 (加密存储)
+    #[serde(
+        serialize_with = "serialize_encrypted",
+        deserialize_with = "deserialize_encrypted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub rest_api_token: Option<String>,
+
+    /// 额外的远程备份目标
+    pub backup_targets: Option<Vec<IBackupTarget>>,
+
+    /// 自定义的流媒体解锁检测
+    pub unlock_checks: Option<Vec<IUnlockCheck>>,
+
+    /// 后台重新检测流媒体解锁的间隔（分钟），0 为不检测
+    pub unlock_check_interval: Option<u64>,
+
+    /// 后台检测该代理组的每个节点，为空时不进行后台检测。
+    /// 当前线路随所选节点变化，无法据此判断解锁状态是否改变
+    pub unlock_check_group: Option<String>,
+
+    /// 解锁状态变化时发送通知
+    pub enable_unlock_alert: Option<bool>,
+
+    /// 解锁历史保留的天数
+    pub unlock_history_days: Option<u32>,
+
+    /// 记录每日流量统计
+    pub enable_traffic_stats: Option<bool>,
+
+    /// 流量统计保留的天数
+    pub traffic_stats_days: Option<u32>,
+
+    /// 订阅流量用量达到该百分比时提醒，0 为不提醒
+    pub traffic_quota_warn_percent: Option<u8>,
+
+    /// 在托盘显示网速
     pub enable_tray_speed: Option<bool>,
 
     // pub enable_tray_icon: Option<bool>,
@@ -248,6 +341,96 @@ pub struct IVergeTestItem {
     pub url: Option<String>,
 }
 
+/// A user-defined media unlock check
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct IUnlockCheck {
+    pub name: Option<String>,
+    pub url: Option<String>,
+
+    /// HTTP method, `GET` when empty
+    pub method: Option<String>,
+
+    pub headers: Option<BTreeMap<String, String>>,
+
+    /// status codes counted as unlocked, any 2xx when empty
+    pub expected_status: Option<Vec<u16>>,
+
+    /// regex the response body has to match to count as unlocked
+    pub expected_body: Option<String>,
+
+    /// regex extracting the region from the response body, the first capture group if it has one
+    pub region_regex: Option<String>,
+}
+
+/// A remote or local place backups can be pushed to
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct IBackupTarget {
+    pub uid: Option<String>,
+    pub name: Option<String>,
+
+    /// enum value: webdav | s3 | local | sftp
+    #[serde(rename = "type")]
+    pub itype: Option<String>,
+
+    /// WebDAV url, S3 endpoint, local directory or `host:port` of the SFTP server
+    pub url: Option<String>,
+
+    /// S3 bucket
+    pub bucket: Option<String>,
+
+    /// S3 region, `us-east-1` when empty
+    pub region: Option<String>,
+
+    /// directory or key prefix on the target
+    pub path: Option<String>,
+
+    /// username or access key (加密存储)
+    #[serde(
+        serialize_with = "serialize_encrypted",
+        deserialize_with = "deserialize_encrypted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub username: Option<String>,
+
+    /// password or secret key (加密存储)
+    #[serde(
+        serialize_with = "serialize_encrypted",
+        deserialize_with = "deserialize_encrypted",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub password: Option<String>,
+
+    /// SFTP host key fingerprint, e.g. `SHA256:...`, checked instead of `~/.ssh/known_hosts`
+    pub host_key: Option<String>,
+
+    /// retention policy of this target, unset keeps all backups
+    pub retention: Option<IBackupRetention>,
+
+    /// push automatic backups to this target
+    pub auto_upload: Option<bool>,
+}
+
+/// Retention of a backup target, 0 or unset disables a rule
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct IBackupRetention {
+    /// keep the newest N backups
+    pub keep_last: Option<usize>,
+
+    /// keep the newest backup of each of the last N days
+    pub keep_daily: Option<usize>,
+
+    /// keep the newest backup of each of the last N weeks
+    pub keep_weekly: Option<usize>,
+
+    /// keep the newest backup of each of the last N months
+    pub keep_monthly: Option<usize>,
+
+    /// upper bound of the kept backups in MB
+    pub max_total_size_mb: Option<u64>,
+}
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct IVergeTheme {
     pub primary_color: Option<String>,
@@ -427,6 +610,10 @@ impl IVerge {
             enable_system_proxy: Some(false),
             proxy_auto_config: Some(false),
             pac_file_content: Some(DEFAULT_PAC.into()),
+            pac_from_rules: Some(false),
+            pac_lan_enabled: Some(false),
+            pac_lan_port: Some(crate::constants::network::ports::DEFAULT_PAC_LAN),
+            pac_cache_max_age: None,
             proxy_host: Some("127.0.0.1".into()),
             #[cfg(not(target_os = "windows"))]
             verge_redir_port: Some(7895),
@@ -451,9 +638,31 @@ impl IVerge {
             enable_auto_backup_schedule: Some(false),
             auto_backup_interval_hours: Some(24),
             auto_backup_on_change: Some(true),
+            backup_compress: Some(false),
+            backup_keep_last: None,
+            backup_keep_daily: None,
+            backup_keep_weekly: None,
+            backup_keep_monthly: None,
+            backup_max_total_size_mb: None,
+            profile_history_limit: Some(DEFAULT_HISTORY_LIMIT),
+            script_max_loop_iterations: Some(10_000_000),
+            script_timeout_seconds: Some(10),
+            script_max_memory_mb: Some(256),
             webdav_url: None,
             webdav_username: None,
             webdav_password: None,
+            backup_passphrase: None,
+            rest_api_enabled: Some(false),
+            rest_api_token: None,
+            backup_targets: None,
+            unlock_checks: None,
+            unlock_check_interval: Some(0),
+            unlock_check_group: None,
+            enable_unlock_alert: Some(true),
+            unlock_history_days: Some(30),
+            enable_traffic_stats: Some(false),
+            traffic_stats_days: Some(90),
+            traffic_quota_warn_percent: Some(90),
             enable_tray_speed: Some(false),
             // enable_tray_icon: Some(true),
             tray_inline_proxy_groups: Some(true),
@@ -530,6 +739,10 @@ impl IVerge {
         patch!(proxy_guard_duration);
         patch!(proxy_auto_config);
         patch!(pac_file_content);
+        patch!(pac_from_rules);
+        patch!(pac_lan_enabled);
+        patch!(pac_lan_port);
+        patch!(pac_cache_max_age);
         patch!(proxy_host);
         patch!(theme_setting);
         patch!(web_ui_list);
@@ -549,10 +762,32 @@ impl IVerge {
         patch!(enable_auto_backup_schedule);
         patch!(auto_backup_interval_hours);
         patch!(auto_backup_on_change);
+        patch!(backup_compress);
+        patch!(backup_keep_last);
+        patch!(backup_keep_daily);
+        patch!(backup_keep_weekly);
+        patch!(backup_keep_monthly);
+        patch!(backup_max_total_size_mb);
+        patch!(profile_history_limit);
+        patch!(script_max_loop_iterations);
+        patch!(script_timeout_seconds);
+        patch!(script_max_memory_mb);
 
         patch!(webdav_url);
         patch!(webdav_username);
         patch!(webdav_password);
+        patch!(backup_passphrase);
+        patch!(rest_api_enabled);
+        patch!(rest_api_token);
+        patch!(backup_targets);
+        patch!(unlock_checks);
+        patch!(unlock_check_interval);
+        patch!(unlock_check_group);
+        patch!(enable_unlock_alert);
+        patch!(unlock_history_days);
+        patch!(enable_traffic_stats);
+        patch!(traffic_stats_days);
+        patch!(traffic_quota_warn_percent);
         patch!(enable_tray_speed);
         // patch!(enable_tray_icon);
         patch!(tray_inline_proxy_groups);
@@ -1,6 +1,10 @@
 use crate::constants::files::DNS_CONFIG;
 use crate::{
     config::Config,
+    core::{
+        backup_crypto,
+        backup_manifest::{self, BackupManifest},
+    },
     logging,
     process::AsyncHandler,
     utils::{dirs, logging::Type},
@@ -58,6 +62,8 @@ impl Operation {
 pub struct WebDavClient {
     config: Arc<ArcSwapOption<WebDavConfig>>,
     clients: Arc<ArcSwap<HashMap<Operation, reqwest_dav::Client>>>,
+    /// fixed config of a `backup_targets` entry, the global client reads verge instead
+    preset: Option<Arc<WebDavConfig>>,
 }
 
 impl WebDavClient {
@@ -66,9 +72,24 @@ impl WebDavClient {
         WEBDAV_CLIENT.get_or_init(|| Self {
             config: Arc::new(ArcSwapOption::new(None)),
             clients: Arc::new(ArcSwap::new(Arc::new(HashMap::new()))),
+            preset: None,
         })
     }
 
+    /// A client for a WebDAV server other than the one in the verge config
+    pub fn with_config(url: String, username: String, password: String) -> Self {
+        let preset = Arc::new(WebDavConfig {
+            url: url.trim_end_matches('/').into(),
+            username,
+            password,
+        });
+        Self {
+            config: Arc::new(ArcSwapOption::new(Some(Arc::clone(&preset)))),
+            clients: Arc::new(ArcSwap::new(Arc::new(HashMap::new()))),
+            preset: Some(preset),
+        }
+    }
+
     async fn get_client(&self, op: Operation) -> Result<reqwest_dav::Client, Error> {
         // 先尝试从缓存获取
         {
@@ -174,7 +195,7 @@ impl WebDavClient {
     }
 
     pub fn reset(&self) {
-        self.config.store(None);
+        self.config.store(self.preset.clone());
         self.clients.store(Arc::new(HashMap::new()));
     }
 
@@ -272,6 +293,14 @@ impl WebDavClient {
 }
 
 pub async fn create_backup() -> Result<(String, PathBuf), Error> {
+    let (compress, passphrase) = {
+        let verge = Config::verge().await.latest_arc();
+        (
+            verge.backup_compress.unwrap_or(false),
+            verge.backup_passphrase.clone().filter(|p| !p.is_empty()),
+        )
+    };
+
     let now = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
     let zip_file_name: String = format!("{OS}-backup-{now}.zip").into();
     let zip_path = temp_dir().join(zip_file_name.as_str());
@@ -280,7 +309,13 @@ pub async fn create_backup() -> Result<(String, PathBuf), Error> {
     let file = AsyncHandler::spawn_blocking(move || std::fs::File::create(&value)).await??;
     let mut zip = zip::ZipWriter::new(file);
     zip.add_directory("profiles/", SimpleFileOptions::default())?;
-    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let method = if compress {
+        zip::CompressionMethod::Deflated
+    } else {
+        zip::CompressionMethod::Stored
+    };
+    let options = SimpleFileOptions::default().compression_method(method);
+    let mut manifest = BackupManifest::default();
 
     if let Ok(mut entries) = fs::read_dir(dirs::app_profiles_dir()?).await {
         while let Some(entry) = entries.next_entry().await? {
@@ -291,14 +326,17 @@ pub async fn create_backup() -> Result<(String, PathBuf), Error> {
                     .to_str()
                     .ok_or_else(|| anyhow::Error::msg("Invalid file name encoding"))?;
                 let backup_path = format!("profiles/{}", file_name);
-                zip.start_file(backup_path, options)?;
+                zip.start_file(backup_path.as_str(), options)?;
                 let file_content = fs::read(&path).await?;
                 zip.write_all(&file_content)?;
+                manifest.add_file(&backup_path, &file_content);
             }
         }
     }
+    let clash_content = fs::read(dirs::clash_path()?).await?;
     zip.start_file(dirs::CLASH_CONFIG, options)?;
-    zip.write_all(fs::read(dirs::clash_path()?).await?.as_slice())?;
+    zip.write_all(&clash_content)?;
+    manifest.add_file(dirs::CLASH_CONFIG, &clash_content);
 
     let verge_text = fs::read_to_string(dirs::verge_path()?).await?;
     let mut verge_config: serde_json::Value = serde_yaml_ng::from_str(&verge_text)?;
@@ -306,18 +344,48 @@ pub async fn create_backup() -> Result<(String, PathBuf), Error> {
         obj.remove("webdav_username");
         obj.remove("webdav_password");
         obj.remove("webdav_url");
+        obj.remove("backup_passphrase");
+        obj.remove("backup_targets");
+        obj.remove("rest_api_token");
     }
+    let verge_content = serde_yaml_ng::to_string(&verge_config)?;
     zip.start_file(dirs::VERGE_CONFIG, options)?;
-    zip.write_all(serde_yaml_ng::to_string(&verge_config)?.as_bytes())?;
+    zip.write_all(verge_content.as_bytes())?;
+    manifest.add_file(dirs::VERGE_CONFIG, verge_content.as_bytes());
 
     let dns_config_path = dirs::app_home_dir()?.join(DNS_CONFIG);
     if dns_config_path.exists() {
+        let dns_content = fs::read(&dns_config_path).await?;
         zip.start_file(DNS_CONFIG, options)?;
-        zip.write_all(fs::read(&dns_config_path).await?.as_slice())?;
+        zip.write_all(&dns_content)?;
+        manifest.add_file(DNS_CONFIG, &dns_content);
     }
 
+    let profiles_content = fs::read(dirs::profiles_path()?).await?;
     zip.start_file(dirs::PROFILE_YAML, options)?;
-    zip.write_all(fs::read(dirs::profiles_path()?).await?.as_slice())?;
+    zip.write_all(&profiles_content)?;
+    manifest.add_file(dirs::PROFILE_YAML, &profiles_content);
+
+    // 清单最后写入，包含以上所有文件的校验值
+    zip.start_file(backup_manifest::MANIFEST_FILE, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
     zip.finish()?;
+
+    // 加密整个压缩包，文件头用于恢复时识别
+    if let Some(passphrase) = passphrase {
+        let data = fs::read(&zip_path).await?;
+        let encrypted = AsyncHandler::spawn_blocking(move || {
+            backup_crypto::encrypt_archive(&data, passphrase.as_str())
+        })
+        .await??;
+        fs::write(&zip_path, encrypted).await?;
+    }
     Ok((zip_file_name, zip_path))
 }
+
+/// Read a backup archive, decrypting it when it is encrypted
+pub async fn read_backup(path: PathBuf, passphrase: Option<String>) -> Result<Vec<u8>, Error> {
+    let data = fs::read(&path).await?;
+    AsyncHandler::spawn_blocking(move || backup_crypto::open_archive(data, passphrase.as_deref()))
+        .await?
+}
@@ -0,0 +1,193 @@
+use aes_gcm::{
+    Aes256Gcm,
+    aead::{Aead as _, KeyInit as _, Payload},
+};
+use anyhow::{Result, anyhow};
+use argon2::{Algorithm, Argon2, Params, Version};
+use std::fmt;
+
+/// Encrypted archive layout:
+/// `MAGIC | version | memory KiB | iterations | parallelism | salt | nonce | ciphertext`,
+/// integers are little endian and the whole header is authenticated as associated data.
+const MAGIC: &[u8; 8] = b"RVBAKENC";
+const FORMAT_VERSION: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const KEY_LENGTH: usize = 32;
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 4 * 3 + SALT_LENGTH + NONCE_LENGTH;
+
+// Argon2id with 64 MiB, 3 passes, 1 lane
+const KDF_MEMORY_KIB: u32 = 64 * 1024;
+const KDF_ITERATIONS: u32 = 3;
+const KDF_PARALLELISM: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupCryptoError {
+    /// the archive is encrypted and no passphrase was given
+    PassphraseRequired,
+    /// wrong passphrase or a tampered archive
+    WrongPassphrase,
+    /// the header is truncated, of an unknown version or has unexpected KDF params
+    InvalidHeader,
+}
+
+impl fmt::Display for BackupCryptoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PassphraseRequired => write!(f, "Backup is encrypted, passphrase required"),
+            Self::WrongPassphrase => write!(f, "Wrong backup passphrase or corrupted backup"),
+            Self::InvalidHeader => write!(f, "Unsupported encrypted backup format"),
+        }
+    }
+}
+
+impl std::error::Error for BackupCryptoError {}
+
+/// Whether the data starts with the encrypted archive header
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    memory: u32,
+    iterations: u32,
+    parallelism: u32,
+) -> Result<[u8; KEY_LENGTH]> {
+    let params = Params::new(memory, iterations, parallelism, Some(KEY_LENGTH))
+        .map_err(|e| anyhow!("Invalid key derivation params: {e}"))?;
+    let mut key = [0u8; KEY_LENGTH];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow!("Key derivation failed: {e}"))?;
+    Ok(key)
+}
+
+/// Encrypt a whole backup archive with a key derived from the passphrase
+#[allow(deprecated)]
+pub fn encrypt_archive(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    getrandom::fill(&mut salt).map_err(|e| anyhow!("Failed to generate salt: {e}"))?;
+    getrandom::fill(&mut nonce).map_err(|e| anyhow!("Failed to generate nonce: {e}"))?;
+
+    let mut header = Vec::with_capacity(HEADER_LENGTH);
+    header.extend_from_slice(MAGIC);
+    header.push(FORMAT_VERSION);
+    header.extend_from_slice(&KDF_MEMORY_KIB.to_le_bytes());
+    header.extend_from_slice(&KDF_ITERATIONS.to_le_bytes());
+    header.extend_from_slice(&KDF_PARALLELISM.to_le_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let key = derive_key(
+        passphrase,
+        &salt,
+        KDF_MEMORY_KIB,
+        KDF_ITERATIONS,
+        KDF_PARALLELISM,
+    )?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| anyhow!("Invalid key: {e}"))?;
+    let ciphertext = cipher
+        .encrypt(
+            nonce.as_slice().into(),
+            Payload {
+                msg: data,
+                aad: &header,
+            },
+        )
+        .map_err(|e| anyhow!("Encryption failed: {e}"))?;
+
+    let mut output = header;
+    output.extend(ciphertext);
+    Ok(output)
+}
+
+/// Decrypt an archive produced by [`encrypt_archive`]
+#[allow(deprecated)]
+pub fn decrypt_archive(data: &[u8], passphrase: &str) -> Result<Vec<u8>> {
+    if !is_encrypted(data) || data.len() < HEADER_LENGTH || data[MAGIC.len()] != FORMAT_VERSION {
+        return Err(BackupCryptoError::InvalidHeader.into());
+    }
+    let (header, ciphertext) = data.split_at(HEADER_LENGTH);
+
+    let read_u32 = |offset: usize| -> Result<u32> {
+        let bytes = header
+            .get(offset..offset + 4)
+            .and_then(|b| <[u8; 4]>::try_from(b).ok())
+            .ok_or(BackupCryptoError::InvalidHeader)?;
+        Ok(u32::from_le_bytes(bytes))
+    };
+    let params_offset = MAGIC.len() + 1;
+    let memory = read_u32(params_offset)?;
+    let iterations = read_u32(params_offset + 4)?;
+    let parallelism = read_u32(params_offset + 8)?;
+    // 参数由版本决定，不信任文件中的值，避免构造的备份耗尽内存或 CPU
+    if (memory, iterations, parallelism) != (KDF_MEMORY_KIB, KDF_ITERATIONS, KDF_PARALLELISM) {
+        return Err(BackupCryptoError::InvalidHeader.into());
+    }
+    let salt_offset = params_offset + 12;
+    let salt = &header[salt_offset..salt_offset + SALT_LENGTH];
+    let nonce = &header[salt_offset + SALT_LENGTH..];
+
+    let key = derive_key(passphrase, salt, memory, iterations, parallelism)?;
+    let cipher = Aes256Gcm::new_from_slice(&key).map_err(|e| anyhow!("Invalid key: {e}"))?;
+    cipher
+        .decrypt(
+            nonce.into(),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| BackupCryptoError::WrongPassphrase.into())
+}
+
+/// Return the zip bytes of a backup, decrypting it when needed
+pub fn open_archive(data: Vec<u8>, passphrase: Option<&str>) -> Result<Vec<u8>> {
+    if !is_encrypted(&data) {
+        return Ok(data);
+    }
+    match passphrase {
+        Some(passphrase) if !passphrase.is_empty() => decrypt_archive(&data, passphrase),
+        _ => Err(BackupCryptoError::PassphraseRequired.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_encrypt_roundtrip() {
+        let data = b"PK\x03\x04 backup content";
+        let encrypted = encrypt_archive(data, "secret").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(open_archive(data.to_vec(), None).unwrap(), data);
+        assert_eq!(
+            open_archive(encrypted.clone(), Some("secret")).unwrap(),
+            data
+        );
+
+        let err = open_archive(encrypted.clone(), None).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<BackupCryptoError>(),
+            Some(&BackupCryptoError::PassphraseRequired)
+        );
+        let err = open_archive(encrypted.clone(), Some("wrong")).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<BackupCryptoError>(),
+            Some(&BackupCryptoError::WrongPassphrase)
+        );
+
+        let mut tampered = encrypted;
+        tampered[MAGIC.len() + 1..MAGIC.len() + 5].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = open_archive(tampered, Some("secret")).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<BackupCryptoError>(),
+            Some(&BackupCryptoError::InvalidHeader)
+        );
+    }
+}
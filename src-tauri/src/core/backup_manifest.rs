@@ -0,0 +1,182 @@
+use crate::utils::help;
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::{Mapping, Value};
+use smartstring::alias::String;
+use std::{collections::BTreeMap, env::consts::OS};
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Bump when the manifest layout changes in an incompatible way
+const MANIFEST_VERSION: u32 = 1;
+/// Bump together with a migration in [`migrate_verge`] when `verge.yaml` changes
+pub const VERGE_SCHEMA_VERSION: u32 = 1;
+/// Bump when `profiles.yaml` changes
+pub const PROFILES_SCHEMA_VERSION: u32 = 1;
+
+const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+// 默认端口，和 IVerge::template 保持一致
+const DEFAULT_REDIR_PORT: u64 = 7895;
+const DEFAULT_TPROXY_PORT: u64 = 7896;
+
+/// Metadata stored as `manifest.json` in every backup archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub manifest_version: u32,
+    pub app_version: String,
+    pub os: String,
+    pub created_at: String,
+    pub verge_schema: u32,
+    pub profiles_schema: u32,
+    /// path inside the archive -> hex encoded SHA-256
+    pub files: BTreeMap<String, String>,
+}
+
+impl Default for BackupManifest {
+    /// an empty manifest for a backup made by this app on this OS
+    fn default() -> Self {
+        Self {
+            manifest_version: MANIFEST_VERSION,
+            app_version: APP_VERSION.into(),
+            os: OS.into(),
+            created_at: chrono::Local::now().to_rfc3339().into(),
+            verge_schema: VERGE_SCHEMA_VERSION,
+            profiles_schema: PROFILES_SCHEMA_VERSION,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+impl BackupManifest {
+    pub fn add_file(&mut self, path: &str, content: &[u8]) {
+        self.files
+            .insert(path.into(), help::sha256_hex(content).into());
+    }
+
+    /// Check that the archive has exactly the listed files with matching checksums
+    pub fn verify(&self, files: &BTreeMap<String, Vec<u8>>) -> Result<()> {
+        if self.manifest_version > MANIFEST_VERSION {
+            bail!(
+                "Backup was made by a newer version ({}), please upgrade first",
+                self.app_version
+            );
+        }
+        for (path, checksum) in &self.files {
+            let Some(content) = files.get(path) else {
+                bail!("Backup verification failed: missing file {path}");
+            };
+            if help::sha256_hex(content) != checksum.as_str() {
+                bail!("Backup verification failed: checksum mismatch for {path}");
+            }
+        }
+        if let Some(path) = files.keys().find(|path| !self.files.contains_key(*path)) {
+            bail!("Backup verification failed: unexpected file {path}");
+        }
+        Ok(())
+    }
+
+    /// Whether `verge.yaml` from this backup has to be migrated before use
+    pub fn needs_migration(&self) -> bool {
+        self.os != OS || self.verge_schema < VERGE_SCHEMA_VERSION
+    }
+}
+
+/// Adapt a `verge.yaml` from another OS or an older schema to the current platform
+pub fn migrate_verge(verge: &mut Mapping, target_os: &str) {
+    let remove = |verge: &mut Mapping, keys: &[&str]| {
+        for key in keys {
+            verge.remove(*key);
+        }
+    };
+
+    if target_os != "macos" {
+        remove(verge, &["tray_icon"]);
+    }
+    // shell 和启动脚本路径无法跨平台使用
+    if verge
+        .get("env_type")
+        .and_then(Value::as_str)
+        .is_some_and(|env| (env == "powershell" || env == "cmd") != (target_os == "windows"))
+    {
+        let env_type = if target_os == "windows" {
+            "powershell"
+        } else {
+            "bash"
+        };
+        verge.insert("env_type".into(), env_type.into());
+        remove(verge, &["startup_script"]);
+    }
+
+    let redir = ["verge_redir_port", "verge_redir_enabled"];
+    let tproxy = ["verge_tproxy_port", "verge_tproxy_enabled"];
+    match target_os {
+        "windows" => {
+            remove(verge, &redir);
+            remove(verge, &tproxy);
+        }
+        "linux" => {
+            fill_port(verge, redir, DEFAULT_REDIR_PORT);
+            fill_port(verge, tproxy, DEFAULT_TPROXY_PORT);
+        }
+        _ => {
+            fill_port(verge, redir, DEFAULT_REDIR_PORT);
+            remove(verge, &tproxy);
+        }
+    }
+}
+
+/// Add a missing `[port, enabled]` pair, and disable it when the port is taken by another listener
+fn fill_port(verge: &mut Mapping, [port_key, enabled_key]: [&str; 2], default_port: u64) {
+    if !verge.contains_key(port_key) {
+        verge.insert(port_key.into(), default_port.into());
+        verge.insert(enabled_key.into(), false.into());
+    }
+    let port = verge.get(port_key).and_then(Value::as_u64);
+    let taken = [
+        "verge_mixed_port",
+        "verge_socks_port",
+        "verge_port",
+        "verge_redir_port",
+        "verge_tproxy_port",
+    ]
+    .into_iter()
+    .filter(|key| *key != port_key)
+    .any(|key| port.is_some() && verge.get(key).and_then(Value::as_u64) == port);
+    if taken {
+        verge.insert(enabled_key.into(), false.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_verify_and_migrate() {
+        let mut manifest = BackupManifest::default();
+        manifest.add_file("verge.yaml", b"a: 1");
+        let mut files = BTreeMap::new();
+        files.insert(String::from("verge.yaml"), b"a: 1".to_vec());
+        assert!(manifest.verify(&files).is_ok());
+        files.insert(String::from("verge.yaml"), b"a: 2".to_vec());
+        assert!(manifest.verify(&files).is_err());
+
+        let mut verge: Mapping = serde_yaml_ng::from_str(
+            "env_type: powershell\nstartup_script: C:\\run.ps1\ntray_icon: monochrome\nverge_mixed_port: 7895\n",
+        )
+        .unwrap();
+        migrate_verge(&mut verge, "linux");
+        assert_eq!(verge.get("env_type"), Some(&Value::from("bash")));
+        assert!(verge.get("startup_script").is_none());
+        assert!(verge.get("tray_icon").is_none());
+        assert_eq!(verge.get("verge_redir_port"), Some(&Value::from(7895)));
+        assert_eq!(verge.get("verge_redir_enabled"), Some(&Value::from(false)));
+        assert_eq!(verge.get("verge_tproxy_port"), Some(&Value::from(7896)));
+
+        migrate_verge(&mut verge, "windows");
+        assert!(verge.get("verge_redir_port").is_none());
+        assert!(verge.get("verge_tproxy_enabled").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use super::backup_target::{BackupFileInfo, BackupTarget};
+use crate::config::{IBackupRetention, IVerge};
+use anyhow::Result;
+use chrono::{DateTime, Datelike as _, NaiveDateTime};
+use smartstring::alias::String;
+use std::collections::HashSet;
+
+const BACKUP_MARKER: &str = "-backup-";
+const TIME_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+
+/// Maps a backup time to the day, week or month it belongs to
+type Period = fn(&NaiveDateTime) -> (i32, u32);
+
+/// Which backups to keep, GFS style: the newest `keep_last`, plus the newest backup
+/// of each of the last `keep_daily` days, `keep_weekly` weeks and `keep_monthly` months.
+/// Zero means the rule is not used
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    pub keep_last: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+    /// upper bound of the kept backups in bytes, the newest backup is always kept
+    pub max_total_size: u64,
+}
+
+impl RetentionPolicy {
+    pub fn from_verge(verge: &IVerge) -> Self {
+        Self {
+            keep_last: verge.backup_keep_last.unwrap_or(0),
+            keep_daily: verge.backup_keep_daily.unwrap_or(0),
+            keep_weekly: verge.backup_keep_weekly.unwrap_or(0),
+            keep_monthly: verge.backup_keep_monthly.unwrap_or(0),
+            max_total_size: verge
+                .backup_max_total_size_mb
+                .unwrap_or(0)
+                .saturating_mul(1024 * 1024),
+        }
+    }
+
+    pub fn from_target(retention: &IBackupRetention) -> Self {
+        Self {
+            keep_last: retention.keep_last.unwrap_or(0),
+            keep_daily: retention.keep_daily.unwrap_or(0),
+            keep_weekly: retention.keep_weekly.unwrap_or(0),
+            keep_monthly: retention.keep_monthly.unwrap_or(0),
+            max_total_size: retention
+                .max_total_size_mb
+                .unwrap_or(0)
+                .saturating_mul(1024 * 1024),
+        }
+    }
+
+    pub const fn is_enabled(&self) -> bool {
+        self.keep_last > 0
+            || self.keep_daily > 0
+            || self.keep_weekly > 0
+            || self.keep_monthly > 0
+            || self.max_total_size > 0
+    }
+
+    const fn has_count_rules(&self) -> bool {
+        self.keep_last > 0 || self.keep_daily > 0 || self.keep_weekly > 0 || self.keep_monthly > 0
+    }
+
+    /// The backups that fall outside the policy, newest first
+    pub fn select_expired(&self, files: &[BackupFileInfo]) -> Vec<BackupFileInfo> {
+        if !self.is_enabled() {
+            return vec![];
+        }
+
+        let mut files: Vec<(NaiveDateTime, &BackupFileInfo)> = files
+            .iter()
+            .filter(|file| file.filename.contains(BACKUP_MARKER))
+            .map(|file| (backup_time(file), file))
+            .collect();
+        files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| b.1.filename.cmp(&a.1.filename)));
+
+        let mut keep = vec![!self.has_count_rules(); files.len()];
+        for flag in keep.iter_mut().take(self.keep_last) {
+            *flag = true;
+        }
+        let tiers: [(usize, Period); 3] = [
+            (self.keep_daily, |t| (t.year(), t.ordinal())),
+            (self.keep_weekly, |t| {
+                let week = t.iso_week();
+                (week.year(), week.week())
+            }),
+            (self.keep_monthly, |t| (t.year(), t.month())),
+        ];
+        for (count, period) in tiers {
+            let mut seen = HashSet::new();
+            for (index, (time, _)) in files.iter().enumerate() {
+                if seen.len() >= count {
+                    break;
+                }
+                // 每个周期只保留最新的一个备份
+                if seen.insert(period(time))
+                    && let Some(flag) = keep.get_mut(index)
+                {
+                    *flag = true;
+                }
+            }
+        }
+
+        if self.max_total_size > 0 {
+            let mut total = 0u64;
+            let mut kept_any = false;
+            for ((_, file), flag) in files.iter().zip(keep.iter_mut()) {
+                if !*flag {
+                    continue;
+                }
+                total = total.saturating_add(file.content_length);
+                if kept_any && total > self.max_total_size {
+                    *flag = false;
+                }
+                kept_any = true;
+            }
+        }
+
+        files
+            .into_iter()
+            .zip(keep)
+            .filter(|(_, keep)| !keep)
+            .map(|((_, file), _)| file.clone())
+            .collect()
+    }
+}
+
+/// When the backup was made, from its file name or else its modification time
+fn backup_time(file: &BackupFileInfo) -> NaiveDateTime {
+    file.filename
+        .split_once(BACKUP_MARKER)
+        .and_then(|(_, rest)| rest.get(..19))
+        .and_then(|time| NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok())
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(&file.last_modified)
+                .ok()
+                .map(|time| time.naive_local())
+        })
+        .unwrap_or_default()
+}
+
+/// List the backups on `target` that `policy` would delete
+pub async fn preview(
+    target: &dyn BackupTarget,
+    policy: &RetentionPolicy,
+) -> Result<Vec<BackupFileInfo>> {
+    if !policy.is_enabled() {
+        return Ok(vec![]);
+    }
+    Ok(policy.select_expired(&target.list().await?))
+}
+
+/// Delete the backups on `target` that fall outside `policy`, returning the removed backups
+pub async fn apply(
+    target: &dyn BackupTarget,
+    policy: &RetentionPolicy,
+) -> Result<Vec<BackupFileInfo>> {
+    let expired = preview(target, policy).await?;
+    for file in &expired {
+        target.delete(file.filename.clone()).await?;
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(time: &str, size: u64) -> BackupFileInfo {
+        BackupFileInfo {
+            filename: format!("linux-backup-{time}.zip").into(),
+            last_modified: String::new(),
+            content_length: size,
+        }
+    }
+
+    fn names(files: &[BackupFileInfo]) -> Vec<&str> {
+        files.iter().map(|file| file.filename.as_str()).collect()
+    }
+
+    #[test]
+    fn test_select_expired() {
+        let files = vec![
+            file("2025-03-10_12-00-00", 10),
+            file("2025-03-10_08-00-00", 10),
+            file("2025-03-09_08-00-00", 10),
+            file("2025-03-02_08-00-00", 10),
+            file("2025-02-15_08-00-00", 10),
+            file("2025-01-15_08-00-00", 10),
+        ];
+
+        assert!(RetentionPolicy::default().select_expired(&files).is_empty());
+
+        let expired = RetentionPolicy {
+            keep_last: 2,
+            ..Default::default()
+        }
+        .select_expired(&files);
+        assert_eq!(
+            names(&expired),
+            vec![
+                "linux-backup-2025-03-09_08-00-00.zip",
+                "linux-backup-2025-03-02_08-00-00.zip",
+                "linux-backup-2025-02-15_08-00-00.zip",
+                "linux-backup-2025-01-15_08-00-00.zip",
+            ]
+        );
+
+        // 每日保留 2 个，每月保留 3 个
+        let policy = RetentionPolicy {
+            keep_daily: 2,
+            keep_monthly: 3,
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            names(&policy.select_expired(&files)),
+            vec![
+                "linux-backup-2025-03-10_08-00-00.zip",
+                "linux-backup-2025-03-02_08-00-00.zip",
+            ]
+        );
+
+        let policy = RetentionPolicy {
+            keep_weekly: 2,
+            max_total_size: 15,
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(policy.select_expired(&files).len(), 5);
+    }
+}
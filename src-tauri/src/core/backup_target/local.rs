@@ -0,0 +1,65 @@
+use super::{BackupFileInfo, BackupTarget, checked_file_name};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use smartstring::alias::String;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// A plain directory, e.g. a second disk or a mounted NFS share
+pub struct LocalDirTarget {
+    dir: PathBuf,
+}
+
+impl LocalDirTarget {
+    pub const fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+}
+
+#[async_trait]
+impl BackupTarget for LocalDirTarget {
+    async fn list(&self) -> Result<Vec<BackupFileInfo>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut files = vec![];
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let Some(filename) = entry.file_name().to_str().map(String::from) else {
+                continue;
+            };
+            let last_modified = metadata
+                .modified()
+                .map(|time| chrono::DateTime::<Utc>::from(time).to_rfc3339())
+                .unwrap_or_default();
+            files.push(BackupFileInfo {
+                filename,
+                last_modified: last_modified.into(),
+                content_length: metadata.len(),
+            });
+        }
+        Ok(files)
+    }
+
+    async fn upload(&self, file_path: PathBuf, file_name: String) -> Result<()> {
+        let target = self.dir.join(checked_file_name(&file_name)?);
+        fs::create_dir_all(&self.dir).await?;
+        fs::copy(&file_path, target).await?;
+        Ok(())
+    }
+
+    async fn download(&self, file_name: String, storage_path: PathBuf) -> Result<()> {
+        fs::copy(self.dir.join(checked_file_name(&file_name)?), &storage_path).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, file_name: String) -> Result<()> {
+        fs::remove_file(self.dir.join(checked_file_name(&file_name)?)).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+mod local;
+mod s3;
+#[cfg(feature = "sftp")]
+mod sftp;
+
+use crate::{
+    config::IBackupTarget,
+    core::{
+        backup::WebDavClient,
+        backup_retention::{self, RetentionPolicy},
+    },
+    logging,
+    utils::logging::Type,
+};
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use serde::Serialize;
+use smartstring::alias::String;
+use std::path::PathBuf;
+
+pub use local::LocalDirTarget;
+pub use s3::S3Target;
+#[cfg(feature = "sftp")]
+pub use sftp::SftpTarget;
+
+/// A backup file stored on a target
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupFileInfo {
+    pub filename: String,
+    pub last_modified: String,
+    pub content_length: u64,
+}
+
+/// Somewhere backup archives can be listed, uploaded, downloaded and deleted
+#[async_trait]
+pub trait BackupTarget: Send + Sync {
+    async fn list(&self) -> Result<Vec<BackupFileInfo>>;
+    async fn upload(&self, file_path: PathBuf, file_name: String) -> Result<()>;
+    async fn download(&self, file_name: String, storage_path: PathBuf) -> Result<()>;
+    async fn delete(&self, file_name: String) -> Result<()>;
+}
+
+#[async_trait]
+impl BackupTarget for WebDavClient {
+    async fn list(&self) -> Result<Vec<BackupFileInfo>> {
+        let files = Self::list(self).await?;
+        Ok(files
+            .into_iter()
+            .map(|file| BackupFileInfo {
+                filename: file
+                    .href
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .into(),
+                last_modified: file.last_modified.to_rfc3339().into(),
+                content_length: u64::try_from(file.content_length).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    async fn upload(&self, file_path: PathBuf, file_name: String) -> Result<()> {
+        Self::upload(self, file_path, file_name).await
+    }
+
+    async fn download(&self, file_name: String, storage_path: PathBuf) -> Result<()> {
+        Self::download(self, file_name, storage_path).await
+    }
+
+    async fn delete(&self, file_name: String) -> Result<()> {
+        Self::delete(self, file_name).await
+    }
+}
+
+/// The name of a backup file on a target, which must not leave the target directory
+pub fn checked_file_name(file_name: &str) -> Result<&str> {
+    if file_name.is_empty() || file_name.contains(['/', '\\']) || file_name.contains("..") {
+        bail!("Invalid backup file name: {file_name}");
+    }
+    Ok(file_name)
+}
+
+/// Create the target described by a `backup_targets` entry
+pub fn build_target(config: &IBackupTarget) -> Result<Box<dyn BackupTarget>> {
+    let url = config
+        .url
+        .clone()
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| anyhow!("Backup target url is empty"))?;
+    let username = config.username.clone().unwrap_or_default();
+    let password = config.password.clone().unwrap_or_default();
+
+    match config.itype.as_deref() {
+        Some("webdav") => Ok(Box::new(WebDavClient::with_config(url, username, password))),
+        Some("s3") => Ok(Box::new(S3Target::new(
+            &url,
+            config.bucket.clone().unwrap_or_default(),
+            config.region.clone(),
+            config.path.clone(),
+            username,
+            password,
+        )?)),
+        Some("local") => Ok(Box::new(LocalDirTarget::new(PathBuf::from(url.as_str())))),
+        #[cfg(feature = "sftp")]
+        Some("sftp") => Ok(Box::new(SftpTarget::new(
+            url,
+            config.path.clone(),
+            username,
+            password,
+            config.host_key.clone(),
+        ))),
+        #[cfg(not(feature = "sftp"))]
+        Some("sftp") => bail!("SFTP backup targets are not supported by this build"),
+        other => bail!("Unknown backup target type: {}", other.unwrap_or_default()),
+    }
+}
+
+/// Upload a backup to the target and apply its retention policy
+pub async fn push_backup(
+    config: &IBackupTarget,
+    file_path: PathBuf,
+    file_name: String,
+) -> Result<()> {
+    let target = build_target(config)?;
+    target.upload(file_path, file_name).await?;
+
+    let policy = config
+        .retention
+        .as_ref()
+        .map(RetentionPolicy::from_target)
+        .unwrap_or_default();
+    if policy.is_enabled() {
+        let removed = backup_retention::apply(target.as_ref(), &policy).await?;
+        if !removed.is_empty() {
+            logging!(
+                info,
+                Type::Backup,
+                "Removed {} old backups from {}",
+                removed.len(),
+                config.name.as_deref().unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IBackupRetention;
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn test_local_target_retention() {
+        let root = std::env::temp_dir().join(format!("rv-verge-target-{}", nanoid::nanoid!()));
+        let source = std::env::temp_dir().join(format!("rv-verge-source-{}", nanoid::nanoid!()));
+        tokio::fs::write(&source, b"backup").await.unwrap();
+
+        let config = IBackupTarget {
+            itype: Some("local".into()),
+            url: Some(root.to_string_lossy().into()),
+            retention: Some(IBackupRetention {
+                keep_last: Some(2),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        for name in [
+            "linux-backup-2025-01-01_00-00-00.zip",
+            "linux-backup-2025-01-02_00-00-00.zip",
+            "linux-backup-2025-01-03_00-00-00.zip",
+        ] {
+            push_backup(&config, source.clone(), name.into())
+                .await
+                .unwrap();
+        }
+
+        let target = build_target(&config).unwrap();
+        let mut names: Vec<String> = target
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|file| file.filename)
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                String::from("linux-backup-2025-01-02_00-00-00.zip"),
+                String::from("linux-backup-2025-01-03_00-00-00.zip"),
+            ]
+        );
+
+        let restored = root.join("restored.zip");
+        target
+            .download(names[0].clone(), restored.clone())
+            .await
+            .unwrap();
+        assert_eq!(tokio::fs::read(&restored).await.unwrap(), b"backup");
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        let _ = tokio::fs::remove_file(&source).await;
+    }
+
+    #[tokio::test]
+    #[allow(clippy::unwrap_used)]
+    async fn test_local_target_rejects_escaping_names() {
+        let root = std::env::temp_dir().join(format!("rv-verge-target-{}", nanoid::nanoid!()));
+        let source = std::env::temp_dir().join(format!("rv-verge-source-{}", nanoid::nanoid!()));
+        tokio::fs::write(&source, b"backup").await.unwrap();
+
+        let target = LocalDirTarget::new(root.join("backups"));
+        for name in [
+            "../escaped.zip",
+            "..",
+            "sub/backup.zip",
+            "sub\\backup.zip",
+            "",
+        ] {
+            assert!(target.upload(source.clone(), name.into()).await.is_err());
+            assert!(target.delete(name.into()).await.is_err());
+            assert!(
+                target
+                    .download(name.into(), root.join("restored.zip"))
+                    .await
+                    .is_err()
+            );
+        }
+        assert!(!root.join("escaped.zip").exists());
+
+        let _ = tokio::fs::remove_dir_all(&root).await;
+        let _ = tokio::fs::remove_file(&source).await;
+    }
+}
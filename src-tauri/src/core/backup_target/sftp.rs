@@ -0,0 +1,193 @@
+use super::{BackupFileInfo, BackupTarget, checked_file_name};
+use crate::process::AsyncHandler;
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose::STANDARD_NO_PAD};
+use smartstring::alias::String;
+use ssh2::{CheckResult, HashType, KnownHostFileKind, Session, Sftp};
+use std::{
+    io::{Read as _, Write as _},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// A directory on an SFTP server, authenticated by password
+pub struct SftpTarget {
+    inner: Arc<SftpConfig>,
+}
+
+struct SftpConfig {
+    /// `host:port`
+    address: String,
+    /// remote directory, always `/`-separated whatever the local platform
+    dir: String,
+    username: String,
+    password: String,
+    /// pinned `SHA256:` fingerprint of the server key
+    host_key: Option<String>,
+}
+
+impl SftpConfig {
+    fn connect(&self) -> Result<Sftp> {
+        let (host, port) = match self.address.rsplit_once(':') {
+            Some((host, port)) if !host.ends_with(':') => (
+                host.trim_start_matches('[').trim_end_matches(']'),
+                port.parse()
+                    .map_err(|_| anyhow!("Invalid SFTP port `{port}`"))?,
+            ),
+            _ => (self.address.as_str(), 22),
+        };
+        let mut session = Session::new()?;
+        session.set_tcp_stream(TcpStream::connect((host, port))?);
+        session.handshake()?;
+        // 认证前校验主机密钥，避免把密码发给中间人
+        self.verify_host_key(&session, host, port)?;
+        session.userauth_password(&self.username, &self.password)?;
+        Ok(session.sftp()?)
+    }
+
+    /// Accept the server only if its key matches the pinned fingerprint,
+    /// or `~/.ssh/known_hosts` when none is pinned
+    fn verify_host_key(&self, session: &Session, host: &str, port: u16) -> Result<()> {
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| anyhow!("SFTP server {host} sent no host key"))?;
+        let fingerprint = session
+            .host_key_hash(HashType::Sha256)
+            .map(|hash| format!("SHA256:{}", STANDARD_NO_PAD.encode(hash)))
+            .ok_or_else(|| anyhow!("Failed to hash the host key of {host}"))?;
+
+        if let Some(pinned) = self.host_key.as_deref().map(str::trim)
+            && !pinned.is_empty()
+        {
+            if pinned.trim_start_matches("SHA256:") == fingerprint.trim_start_matches("SHA256:") {
+                return Ok(());
+            }
+            bail!("SFTP host key of {host} changed: expected {pinned}, got {fingerprint}");
+        }
+
+        let mut known_hosts = session.known_hosts()?;
+        if let Some(path) = known_hosts_path()
+            && path.exists()
+        {
+            known_hosts.read_file(&path, KnownHostFileKind::OpenSSH)?;
+        }
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => {
+                bail!("SFTP host key of {host} ({fingerprint}) does not match known_hosts")
+            }
+            CheckResult::NotFound => bail!(
+                "Unknown SFTP host {host}, pin its host key {fingerprint} or add it to known_hosts"
+            ),
+            CheckResult::Failure => bail!("Failed to check the SFTP host key of {host}"),
+        }
+    }
+}
+
+fn known_hosts_path() -> Option<PathBuf> {
+    let home = std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })?;
+    Some(PathBuf::from(home).join(".ssh").join("known_hosts"))
+}
+
+impl SftpTarget {
+    pub fn new(
+        address: String,
+        dir: Option<String>,
+        username: String,
+        password: String,
+        host_key: Option<String>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(SftpConfig {
+                address,
+                dir: dir
+                    .filter(|dir| !dir.is_empty())
+                    .unwrap_or_else(|| ".".into()),
+                username,
+                password,
+                host_key,
+            }),
+        }
+    }
+
+    /// ssh2 is blocking, run every operation on the blocking pool
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Sftp, &Path) -> Result<T> + Send + 'static,
+    {
+        let config = Arc::clone(&self.inner);
+        AsyncHandler::spawn_blocking(move || {
+            let sftp = config.connect()?;
+            f(&sftp, Path::new(config.dir.as_str()))
+        })
+        .await?
+    }
+
+    /// The remote path of `file_name`, joined with `/` since `Path::join` uses `\` on Windows
+    fn remote_path(&self, file_name: &str) -> Result<PathBuf> {
+        let file_name = checked_file_name(file_name)?;
+        let dir = self.inner.dir.trim_end_matches('/');
+        Ok(PathBuf::from(format!("{dir}/{file_name}")))
+    }
+}
+
+#[async_trait]
+impl BackupTarget for SftpTarget {
+    async fn list(&self) -> Result<Vec<BackupFileInfo>> {
+        self.run(|sftp, dir| {
+            let files = sftp
+                .readdir(dir)?
+                .into_iter()
+                .filter(|(_, stat)| stat.is_file())
+                .filter_map(|(path, stat)| {
+                    let filename = path.file_name()?.to_str()?.into();
+                    let last_modified = stat
+                        .mtime
+                        .and_then(|t| chrono::DateTime::from_timestamp(i64::try_from(t).ok()?, 0))
+                        .map(|t| t.to_rfc3339())
+                        .unwrap_or_default();
+                    Some(BackupFileInfo {
+                        filename,
+                        last_modified: last_modified.into(),
+                        content_length: stat.size.unwrap_or_default(),
+                    })
+                })
+                .collect();
+            Ok(files)
+        })
+        .await
+    }
+
+    async fn upload(&self, file_path: PathBuf, file_name: String) -> Result<()> {
+        let path = self.remote_path(&file_name)?;
+        let content = tokio::fs::read(&file_path).await?;
+        self.run(move |sftp, _| {
+            let mut remote = sftp.create(&path)?;
+            remote.write_all(&content)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn download(&self, file_name: String, storage_path: PathBuf) -> Result<()> {
+        let path = self.remote_path(&file_name)?;
+        let content = self
+            .run(move |sftp, _| {
+                let mut remote = sftp.open(&path)?;
+                let mut content = Vec::new();
+                remote.read_to_end(&mut content)?;
+                Ok(content)
+            })
+            .await?;
+        tokio::fs::write(&storage_path, content).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, file_name: String) -> Result<()> {
+        let path = self.remote_path(&file_name)?;
+        self.run(move |sftp, _| Ok(sftp.unlink(&path)?)).await
+    }
+}
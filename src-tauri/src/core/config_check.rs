@@ -0,0 +1,424 @@
+use serde::Serialize;
+use serde_yaml_ng::{Mapping, Value};
+use smartstring::alias::String;
+use std::collections::{HashMap, HashSet};
+
+/// policies that are always available to groups and rules
+const BUILTIN_POLICIES: [&str; 6] = [
+    "DIRECT",
+    "REJECT",
+    "REJECT-DROP",
+    "PASS",
+    "COMPATIBLE",
+    "GLOBAL",
+];
+
+/// rule types supported by mihomo
+const RULE_TYPES: [&str; 36] = [
+    "DOMAIN",
+    "DOMAIN-SUFFIX",
+    "DOMAIN-KEYWORD",
+    "DOMAIN-REGEX",
+    "DOMAIN-WILDCARD",
+    "GEOSITE",
+    "GEOIP",
+    "SRC-GEOIP",
+    "IP-ASN",
+    "SRC-IP-ASN",
+    "IP-CIDR",
+    "IP-CIDR6",
+    "IP-SUFFIX",
+    "SRC-IP-CIDR",
+    "SRC-IP-SUFFIX",
+    "DST-PORT",
+    "SRC-PORT",
+    "IN-PORT",
+    "IN-TYPE",
+    "IN-USER",
+    "IN-NAME",
+    "PROCESS-PATH",
+    "PROCESS-PATH-REGEX",
+    "PROCESS-PATH-WILDCARD",
+    "PROCESS-NAME",
+    "PROCESS-NAME-REGEX",
+    "PROCESS-NAME-WILDCARD",
+    "UID",
+    "NETWORK",
+    "DSCP",
+    "RULE-SET",
+    "AND",
+    "OR",
+    "NOT",
+    "SUB-RULE",
+    "MATCH",
+];
+
+/// inbound port fields of the core config
+const PORT_FIELDS: [&str; 5] = [
+    "mixed-port",
+    "socks-port",
+    "port",
+    "redir-port",
+    "tproxy-port",
+];
+
+/// A problem found by [`check_config`]
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ConfigIssue {
+    /// machine readable kind, e.g. `duplicate_proxy_name`
+    pub code: &'static str,
+    /// key path, e.g. `proxy-groups[1].proxies[0]`
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}: {}", self.code, self.path, self.message)
+    }
+}
+
+/// Semantic checks of a core config that don't need the core binary
+pub fn check_config(config: &Mapping) -> Vec<ConfigIssue> {
+    let mut checker = Checker::new(config);
+    checker.check_ports();
+    checker.check_names();
+    checker.check_groups();
+    checker.check_rules();
+    checker.check_dialer_cycles();
+    checker.issues
+}
+
+struct Checker<'a> {
+    proxies: Vec<&'a Mapping>,
+    groups: Vec<&'a Mapping>,
+    config: &'a Mapping,
+    issues: Vec<ConfigIssue>,
+}
+
+fn name_of(item: &Mapping) -> Option<&str> {
+    item.get("name").and_then(Value::as_str)
+}
+
+fn mappings<'a>(config: &'a Mapping, field: &str) -> Vec<&'a Mapping> {
+    config
+        .get(field)
+        .and_then(Value::as_sequence)
+        .map(|seq| seq.iter().filter_map(Value::as_mapping).collect())
+        .unwrap_or_default()
+}
+
+fn parse_port(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+impl<'a> Checker<'a> {
+    fn new(config: &'a Mapping) -> Self {
+        Self {
+            proxies: mappings(config, "proxies"),
+            groups: mappings(config, "proxy-groups"),
+            config,
+            issues: vec![],
+        }
+    }
+
+    fn push(&mut self, code: &'static str, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ConfigIssue {
+            code,
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    fn policies(&self) -> HashSet<&'a str> {
+        self.proxies
+            .iter()
+            .chain(self.groups.iter())
+            .filter_map(|item| name_of(item))
+            .chain(BUILTIN_POLICIES)
+            .collect()
+    }
+
+    fn check_ports(&mut self) {
+        let mut used: HashMap<u64, &str> = HashMap::new();
+        for field in PORT_FIELDS {
+            let Some(value) = self.config.get(field) else {
+                continue;
+            };
+            match parse_port(value).filter(|port| *port <= 65535) {
+                Some(0) => {}
+                Some(port) => {
+                    if let Some(other) = used.get(&port) {
+                        let message = format!("port {port} is also used by `{other}`");
+                        self.push("port_conflict", field, message);
+                    } else {
+                        used.insert(port, field);
+                    }
+                }
+                None => self.push("invalid_port", field, "port should be between 0 and 65535"),
+            }
+        }
+
+        for (index, proxy) in self.proxies.clone().into_iter().enumerate() {
+            if let Some(value) = proxy.get("port")
+                && !parse_port(value).is_some_and(|port| (1..=65535).contains(&port))
+            {
+                let path = format!("proxies[{index}].port");
+                self.push("invalid_port", path, "port should be between 1 and 65535");
+            }
+        }
+    }
+
+    fn check_names(&mut self) {
+        let mut seen: HashMap<&str, String> = HashMap::new();
+        let proxies = self.proxies.clone().into_iter().map(|p| ("proxies", p));
+        let groups = self.groups.clone().into_iter().map(|g| ("proxy-groups", g));
+        let mut indexes: HashMap<&str, usize> = HashMap::new();
+        for (field, item) in proxies.chain(groups) {
+            let index = indexes.entry(field).or_default();
+            let path = format!("{field}[{index}].name");
+            *index += 1;
+            let Some(name) = name_of(item) else {
+                self.push("missing_name", path, "item has no `name`");
+                continue;
+            };
+            if let Some(first) = seen.get(name) {
+                let code = if field == "proxies" {
+                    "duplicate_proxy_name"
+                } else {
+                    "duplicate_group_name"
+                };
+                let message = format!("`{name}` is already defined at `{first}`");
+                self.push(code, path, message);
+            } else {
+                seen.insert(name, path.into());
+            }
+        }
+    }
+
+    fn check_groups(&mut self) {
+        let policies = self.policies();
+        let providers: HashSet<&str> = self
+            .config
+            .get("proxy-providers")
+            .and_then(Value::as_mapping)
+            .map(|m| m.keys().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        for (index, group) in self.groups.clone().into_iter().enumerate() {
+            let members = group.get("proxies").and_then(Value::as_sequence);
+            for (i, member) in members.into_iter().flatten().enumerate() {
+                if let Some(member) = member.as_str()
+                    && !policies.contains(member)
+                {
+                    let path = format!("proxy-groups[{index}].proxies[{i}]");
+                    let message = format!("unknown proxy or group `{member}`");
+                    self.push("unknown_proxy", path, message);
+                }
+            }
+            let uses = group.get("use").and_then(Value::as_sequence);
+            for (i, provider) in uses.into_iter().flatten().enumerate() {
+                if let Some(provider) = provider.as_str()
+                    && !providers.contains(provider)
+                {
+                    let path = format!("proxy-groups[{index}].use[{i}]");
+                    let message = format!("unknown proxy-provider `{provider}`");
+                    self.push("unknown_provider", path, message);
+                }
+            }
+        }
+    }
+
+    fn check_rules(&mut self) {
+        let policies = self.policies();
+        let sub_rules: HashMap<&str, &Vec<Value>> = self
+            .config
+            .get("sub-rules")
+            .and_then(Value::as_mapping)
+            .map(|m| {
+                m.iter()
+                    .filter_map(|(k, v)| Some((k.as_str()?, v.as_sequence()?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let rules = self.config.get("rules").and_then(Value::as_sequence);
+        let sub = sub_rules.iter().flat_map(|(name, rules)| {
+            rules
+                .iter()
+                .enumerate()
+                .map(move |(i, r)| (format!("sub-rules.{name}[{i}]"), r))
+        });
+        let top = rules
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(i, r)| (format!("rules[{i}]"), r));
+
+        for (path, rule) in top.chain(sub) {
+            let Some(rule) = rule.as_str() else {
+                self.push("invalid_rule", path, "rule should be a string");
+                continue;
+            };
+            let Some((rule_type, target)) = split_rule(rule) else {
+                let message = format!("malformed rule `{rule}`");
+                self.push("invalid_rule", path, message);
+                continue;
+            };
+            if !RULE_TYPES.contains(&rule_type) {
+                let message = format!("unknown rule type `{rule_type}`");
+                self.push("invalid_rule_type", path, message);
+                continue;
+            }
+            if rule_type == "SUB-RULE" {
+                if !sub_rules.contains_key(target) {
+                    let message = format!("unknown sub-rule `{target}`");
+                    self.push("unknown_sub_rule", path, message);
+                }
+            } else if !policies.contains(target) {
+                let message = format!("unknown policy `{target}`");
+                self.push("unknown_policy", path, message);
+            }
+        }
+    }
+
+    fn check_dialer_cycles(&mut self) {
+        let policies = self.policies();
+        // proxy -> dialer-proxy, a group only picks one of its members so it is not followed
+        let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (index, proxy) in self.proxies.clone().into_iter().enumerate() {
+            let (Some(name), Some(dialer)) = (
+                name_of(proxy),
+                proxy.get("dialer-proxy").and_then(Value::as_str),
+            ) else {
+                continue;
+            };
+            if !policies.contains(dialer) {
+                let path = format!("proxies[{index}].dialer-proxy");
+                let message = format!("unknown proxy or group `{dialer}`");
+                self.push("unknown_proxy", path, message);
+            }
+            edges.entry(name).or_default().push(dialer);
+        }
+
+        let mut reported: HashSet<Vec<&str>> = HashSet::new();
+        for (index, proxy) in self.proxies.clone().into_iter().enumerate() {
+            let Some(name) = name_of(proxy) else {
+                continue;
+            };
+            if proxy.get("dialer-proxy").is_none() {
+                continue;
+            }
+            if let Some(cycle) = find_cycle(&edges, name) {
+                let mut key = cycle.clone();
+                key.sort_unstable();
+                key.dedup();
+                if reported.insert(key) {
+                    let path = format!("proxies[{index}].dialer-proxy");
+                    let message = format!("dialer-proxy loop: {}", cycle.join(" -> "));
+                    self.push("dialer_proxy_cycle", path, message);
+                }
+            }
+        }
+    }
+}
+
+/// `(type, target)` of a rule like `DOMAIN,example.com,PROXY,no-resolve`
+fn split_rule(rule: &str) -> Option<(&str, &str)> {
+    let rule_type = rule.split(',').next()?.trim();
+    let target = match rule_type {
+        "MATCH" => rule.split(',').nth(1),
+        // logic rules: AND,((DOMAIN,a),(NETWORK,UDP)),TARGET
+        "AND" | "OR" | "NOT" | "SUB-RULE" => {
+            let (_, rest) = rule.rsplit_once(')')?;
+            rest.trim_start().strip_prefix(',')?.split(',').next()
+        }
+        _ => rule.split(',').nth(2),
+    }?;
+    let target = target.trim();
+    (!target.is_empty()).then_some((rule_type, target))
+}
+
+/// the cycle reachable from `start` that returns to `start`, if any
+fn find_cycle<'a>(edges: &HashMap<&'a str, Vec<&'a str>>, start: &'a str) -> Option<Vec<&'a str>> {
+    let mut path = vec![start];
+    let mut visited = HashSet::new();
+    dfs(edges, start, start, &mut path, &mut visited).then_some(path)
+}
+
+fn dfs<'a>(
+    edges: &HashMap<&'a str, Vec<&'a str>>,
+    start: &'a str,
+    node: &'a str,
+    path: &mut Vec<&'a str>,
+    visited: &mut HashSet<&'a str>,
+) -> bool {
+    for next in edges.get(node).into_iter().flatten() {
+        if *next == start {
+            path.push(next);
+            return true;
+        }
+        if visited.insert(next) {
+            path.push(next);
+            if dfs(edges, start, next, path, visited) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_check_config() {
+        let config: Mapping = serde_yaml_ng::from_str(
+            r"
+mixed-port: 7897
+socks-port: 7897
+port: 70000
+proxies:
+  - { name: a, type: ss, server: 1.1.1.1, port: 443, dialer-proxy: b }
+  - { name: b, type: ss, server: 2.2.2.2, port: 443, dialer-proxy: a }
+  - { name: a, type: ss, server: 3.3.3.3, port: abc }
+  - { name: c, type: ss, server: 4.4.4.4, port: 443, dialer-proxy: chain }
+proxy-groups:
+  - { name: chain, type: select, proxies: [c, a, missing, GLOBAL] }
+rules:
+  - DOMAIN,example.com,chain
+  - FOO,bar,DIRECT
+  - AND,((NETWORK,UDP),(DST-PORT,443)),REJECT
+  - DOMAIN,global.example.com,GLOBAL
+  - MATCH,nowhere
+",
+        )
+        .expect("Failed to parse test config YAML");
+
+        let issues = check_config(&config);
+        let codes: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|issue| (issue.code, issue.path.as_str()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                ("port_conflict", "socks-port"),
+                ("invalid_port", "port"),
+                ("invalid_port", "proxies[2].port"),
+                ("duplicate_proxy_name", "proxies[2].name"),
+                ("unknown_proxy", "proxy-groups[0].proxies[2]"),
+                ("invalid_rule_type", "rules[1]"),
+                ("unknown_policy", "rules[4]"),
+                ("dialer_proxy_cycle", "proxies[0].dialer-proxy"),
+            ]
+        );
+    }
+}
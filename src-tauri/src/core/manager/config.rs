@@ -62,14 +62,14 @@ impl CoreManager {
         Config::generate().await?;
 
         match CoreConfigValidator::global().validate_config().await {
-            Ok((true, _)) => {
+            Ok(report) if report.is_valid() => {
                 let run_path = Config::generate_file(ConfigType::Run).await?;
                 self.apply_config(run_path).await?;
                 Ok((true, String::new()))
             }
-            Ok((false, error_msg)) => {
+            Ok(report) => {
                 Config::runtime().await.discard();
-                Ok((false, error_msg))
+                Ok((false, report.error_message()))
             }
             Err(e) => {
                 Config::runtime().await.discard();
@@ -1,16 +1,27 @@
 pub mod async_proxy_query;
 pub mod backup;
+pub mod backup_crypto;
+pub mod backup_manifest;
+pub mod backup_retention;
+pub mod backup_target;
+pub mod config_check;
 pub mod event_driven_proxy;
 pub mod handle;
 pub mod hotkey;
 pub mod logger;
 pub mod manager;
+pub mod node_route;
 mod notification;
+pub mod pac;
 pub mod service;
 pub mod sysopt;
 pub mod timer;
+pub mod traffic_store;
 pub mod tray;
+pub mod update_policy;
+pub mod update_schedule;
 pub mod validate;
+pub mod validation_report;
 pub mod win_uwp;
 
 pub use self::{event_driven_proxy::EventDrivenProxyManager, manager::CoreManager, timer::Timer};
@@ -0,0 +1,223 @@
+//! A hidden selector and a loopback listener routed to it, added to the runtime config on demand,
+//! so local requests can go out through one node without touching the groups of the user
+
+use crate::{
+    config::{Config, IVerge},
+    constants::timing,
+    core::{CoreManager, handle},
+    logging,
+    process::AsyncHandler,
+    utils::logging::Type,
+};
+use anyhow::{Context as _, Result, anyhow, bail};
+use serde_yaml_ng::Value;
+use smartstring::alias::String;
+use std::{
+    io::{Read as _, Write as _},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    sync::atomic::{AtomicBool, AtomicU16, Ordering},
+    time::Duration,
+};
+use tokio::{
+    sync::{Mutex, MutexGuard},
+    time::sleep,
+};
+
+/// Name of the hidden selector and of its listener
+pub const NODE_ROUTE_GROUP: &str = "verge-node-route";
+
+/// the route is kept this long after its last use, so back-to-back checks reload the core once
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// reloads with a new port when the listener cannot be confirmed
+const CONFIRM_ATTEMPTS: usize = 3;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The group has a single selection, so the route is used by one caller at a time
+static ROUTE_LOCK: Mutex<()> = Mutex::const_new(());
+/// whether a [`NodeRoute`] is open
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static PORT: AtomicU16 = AtomicU16::new(0);
+
+/// The listener port while the route belongs in the runtime config
+pub async fn injected_port() -> Option<u16> {
+    let wanted =
+        ACTIVE.load(Ordering::Acquire) || is_scheduled(&Config::verge().await.latest_arc());
+    if !wanted {
+        return None;
+    }
+    match PORT.load(Ordering::Acquire) {
+        0 => pick_port(),
+        port => Some(port),
+    }
+}
+
+/// The scheduled unlock matrix uses the route all the time
+fn is_scheduled(verge: &IVerge) -> bool {
+    verge.unlock_check_interval.unwrap_or(0) > 0
+        && verge
+            .unlock_check_group
+            .as_ref()
+            .is_some_and(|group| !group.is_empty())
+}
+
+fn pick_port() -> Option<u16> {
+    let port = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .and_then(|listener| listener.local_addr())
+        .ok()?
+        .port();
+    PORT.store(port, Ordering::Release);
+    Some(port)
+}
+
+/// Exclusive use of the route, which stays in the runtime config until a while after it is dropped
+pub struct NodeRoute {
+    port: u16,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl NodeRoute {
+    /// Wait for the route, adding it to the runtime config when needed
+    pub async fn open() -> Result<Self> {
+        Self::activate(ROUTE_LOCK.lock().await).await
+    }
+
+    /// Like [`NodeRoute::open`], but `None` when the route is in use
+    pub async fn try_open() -> Option<Result<Self>> {
+        let guard = ROUTE_LOCK.try_lock().ok()?;
+        Some(Self::activate(guard).await)
+    }
+
+    async fn activate(guard: MutexGuard<'static, ()>) -> Result<Self> {
+        ACTIVE.store(true, Ordering::Release);
+        match Self::confirmed_port().await {
+            Ok(port) => Ok(Self {
+                port,
+                _guard: guard,
+            }),
+            Err(err) => {
+                ACTIVE.store(false, Ordering::Release);
+                Err(err)
+            }
+        }
+    }
+
+    /// 端口可能在内核监听前被其他程序占用，需要确认监听器由内核启动
+    async fn confirmed_port() -> Result<u16> {
+        for attempt in 0..=CONFIRM_ATTEMPTS {
+            let port = PORT.load(Ordering::Acquire);
+            if port != 0 && confirm_listener(port).await {
+                return Ok(port);
+            }
+            if attempt == CONFIRM_ATTEMPTS {
+                break;
+            }
+            if attempt > 0 || port == 0 {
+                pick_port();
+            }
+            let (applied, message) = CoreManager::global().update_config().await?;
+            if !applied {
+                bail!("failed to apply the node route: {message}");
+            }
+            sleep(timing::CONFIG_UPDATE_DEBOUNCE).await;
+        }
+        Err(anyhow!("the core did not start the node route listener"))
+    }
+
+    /// Send the requests through the route out of `node`
+    pub async fn select(&self, node: &str) -> Result<()> {
+        handle::Handle::mihomo()
+            .await
+            .select_node_for_group(NODE_ROUTE_GROUP, node)
+            .await
+            .map_err(|err| anyhow!("failed to select {node}: {err:?}"))
+    }
+
+    /// Port of the HTTP proxy of the route
+    pub const fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The HTTP proxy of the route
+    pub fn proxy(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port).into()
+    }
+}
+
+impl Drop for NodeRoute {
+    fn drop(&mut self) {
+        ACTIVE.store(false, Ordering::Release);
+        AsyncHandler::spawn(|| async {
+            sleep(IDLE_TIMEOUT).await;
+            if ACTIVE.load(Ordering::Acquire)
+                || is_scheduled(&Config::verge().await.latest_arc())
+                || !is_loaded().await
+            {
+                return;
+            }
+            if let Err(err) = CoreManager::global().update_config().await {
+                logging!(warn, Type::Core, "Warning: 移除节点路由失败: {err}");
+            }
+        });
+    }
+}
+
+/// whether the running config has the route
+async fn is_loaded() -> bool {
+    let runtime = Config::runtime().await.latest_arc();
+    runtime
+        .config
+        .as_ref()
+        .and_then(|config| config.get("proxy-groups"))
+        .and_then(Value::as_sequence)
+        .is_some_and(|groups| {
+            groups
+                .iter()
+                .any(|group| group.get("name").and_then(Value::as_str) == Some(NODE_ROUTE_GROUP))
+        })
+}
+
+/// Open a tunnel through the listener on `port` and check that the core reports it in the group
+async fn confirm_listener(port: u16) -> bool {
+    if handle::Handle::mihomo()
+        .await
+        .select_node_for_group(NODE_ROUTE_GROUP, "DIRECT")
+        .await
+        .is_err()
+    {
+        return false;
+    }
+    let tunnel = AsyncHandler::spawn_blocking(move || open_tunnel(port)).await;
+    let Ok(Ok(_tunnel)) = tunnel else {
+        return false;
+    };
+    let Ok(connections) = handle::Handle::mihomo().await.get_connections().await else {
+        return false;
+    };
+    connections
+        .connections
+        .iter()
+        .flatten()
+        .any(|connection| connection.chains.iter().any(|c| c == NODE_ROUTE_GROUP))
+}
+
+/// `CONNECT` through the listener on `port` to a local socket of our own, which needs nothing but
+/// the route to be up. Both ends are returned to keep the tunnel open
+fn open_tunnel(port: u16) -> Result<(TcpStream, TcpListener)> {
+    let target = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let target_port = target.local_addr()?.port();
+    let mut stream =
+        TcpStream::connect_timeout(&(Ipv4Addr::LOCALHOST, port).into(), PROBE_TIMEOUT)?;
+    stream.set_read_timeout(Some(PROBE_TIMEOUT))?;
+    write!(
+        stream,
+        "CONNECT 127.0.0.1:{target_port} HTTP/1.1\r\nHost: 127.0.0.1:{target_port}\r\n\r\n"
+    )?;
+    let mut head = [0u8; 12];
+    stream
+        .read_exact(&mut head)
+        .context("no response from the node route listener")?;
+    if !head.starts_with(b"HTTP/1.") || &head[9..12] != b"200" {
+        bail!("the node route listener refused the tunnel");
+    }
+    Ok((stream, target))
+}
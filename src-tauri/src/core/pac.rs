@@ -0,0 +1,214 @@
+use crate::config::{Config, DEFAULT_PAC};
+use serde_yaml_ng::{Mapping, Value};
+use smartstring::alias::String;
+use std::{fmt::Write as _, net::Ipv4Addr};
+
+/// Seconds clients may cache the pac script when `pac_cache_max_age` is not set
+pub const DEFAULT_CACHE_MAX_AGE: u64 = 60;
+
+/// Discard port, rejected requests fail fast instead of leaking out directly
+const REJECT: &str = "PROXY 127.0.0.1:9";
+
+const SCRIPT_HEAD: &str = r"  host = host.toLowerCase();
+  var resolved;
+  function ip(resolve) {
+    if (/^\d{1,3}(\.\d{1,3}){3}$/.test(host)) return host;
+    if (!resolve) return null;
+    if (resolved === undefined) resolved = dnsResolve(host);
+    return resolved;
+  }
+  function inNet(address, net, mask) {
+    return address !== null && isInNet(address, net, mask);
+  }
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Direct,
+    Reject,
+    Proxy,
+}
+
+impl Target {
+    fn parse(target: &str) -> Self {
+        match target.trim() {
+            "DIRECT" => Self::Direct,
+            "REJECT" | "REJECT-DROP" => Self::Reject,
+            _ => Self::Proxy,
+        }
+    }
+
+    const fn js(self) -> &'static str {
+        match self {
+            Self::Direct => "\"DIRECT\"",
+            Self::Reject => "REJECT",
+            Self::Proxy => "PROXY",
+        }
+    }
+}
+
+/// The pac script served to a client that reached the server through `host`
+pub async fn render(host: &str) -> String {
+    let verge = Config::verge().await.latest_arc();
+    let port = match verge.verge_mixed_port {
+        Some(port) => port,
+        None => Config::clash().await.latest_arc().get_mixed_port(),
+    };
+    let proxy = format!("{host}:{port}");
+
+    if verge.pac_from_rules.unwrap_or(false) {
+        let runtime = Config::runtime().await.latest_arc();
+        let empty = Mapping::new();
+        return generate(runtime.config.as_ref().unwrap_or(&empty), &proxy);
+    }
+    verge
+        .pac_file_content
+        .as_deref()
+        .unwrap_or(DEFAULT_PAC)
+        .replace("%mixed-port%", &port.to_string())
+        .replace("%proxy-host%", host)
+        .into()
+}
+
+/// Build a pac script from the `mode` and `rules` of a clash config.
+///
+/// `DOMAIN`, `DOMAIN-SUFFIX`, `DOMAIN-KEYWORD` and `IP-CIDR` rules become branches in their
+/// original order. From the first rule a pac script can't evaluate on, everything is sent to
+/// `proxy` where the core applies the full rules
+pub fn generate(config: &Mapping, proxy: &str) -> String {
+    let mut script = String::new();
+    let _ = writeln!(script, "function FindProxyForURL(url, host) {{");
+    let _ = writeln!(
+        script,
+        "  var PROXY = \"PROXY {proxy}; SOCKS5 {proxy}; DIRECT\";"
+    );
+    let _ = writeln!(script, "  var REJECT = \"{REJECT}\";");
+
+    match config.get("mode").and_then(Value::as_str) {
+        Some("direct") => {
+            let _ = writeln!(script, "  return \"DIRECT\";");
+            let _ = writeln!(script, "}}");
+            return script;
+        }
+        Some("global") => {}
+        _ => {
+            script.push_str(SCRIPT_HEAD);
+            let rules = config.get("rules").and_then(Value::as_sequence);
+            for rule in rules.into_iter().flatten().filter_map(Value::as_str) {
+                let mut parts = rule.split(',').map(str::trim);
+                let kind = parts.next().unwrap_or_default();
+                if kind == "MATCH" || kind == "FINAL" {
+                    let target = Target::parse(parts.next().unwrap_or_default());
+                    let _ = writeln!(script, "  return {};", target.js());
+                    let _ = writeln!(script, "}}");
+                    return script;
+                }
+                let (Some(payload), Some(target)) = (parts.next(), parts.next()) else {
+                    break;
+                };
+                let no_resolve = parts.any(|option| option == "no-resolve");
+                // 后续规则的结果取决于这条规则，只能交给内核判断
+                let Some(condition) = condition(kind, payload, no_resolve) else {
+                    break;
+                };
+                let target = Target::parse(target);
+                let _ = writeln!(script, "  if ({condition}) return {};", target.js());
+            }
+        }
+    }
+
+    let _ = writeln!(script, "  return PROXY;");
+    let _ = writeln!(script, "}}");
+    script
+}
+
+/// The javascript condition of a rule, `None` for rules a pac script can't evaluate
+fn condition(kind: &str, payload: &str, no_resolve: bool) -> Option<String> {
+    let quote = |value: &str| serde_json::to_string(value).ok();
+    let payload = payload.to_lowercase();
+    let condition = match kind {
+        "DOMAIN" => format!("host == {}", quote(&payload)?),
+        "DOMAIN-SUFFIX" => format!(
+            "host == {} || dnsDomainIs(host, {})",
+            quote(&payload)?,
+            quote(&format!(".{payload}"))?
+        ),
+        "DOMAIN-KEYWORD" => format!("host.indexOf({}) >= 0", quote(&payload)?),
+        "IP-CIDR" => {
+            let (address, prefix) = payload.split_once('/')?;
+            let address = address.parse::<Ipv4Addr>().ok()?;
+            let prefix = prefix.parse::<u32>().ok().filter(|prefix| *prefix <= 32)?;
+            let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+            let network = Ipv4Addr::from(u32::from(address) & mask);
+            format!(
+                "inNet(ip({}), \"{network}\", \"{}\")",
+                !no_resolve,
+                Ipv4Addr::from(mask)
+            )
+        }
+        _ => return None,
+    };
+    Some(condition.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    fn config(yaml: &str) -> Mapping {
+        serde_yaml_ng::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_generate() {
+        let script = generate(
+            &config(
+                r"
+mode: rule
+rules:
+  - DOMAIN,Example.com,DIRECT
+  - DOMAIN-SUFFIX,google.com,Proxy
+  - DOMAIN-KEYWORD,ads,REJECT
+  - IP-CIDR,192.168.1.7/16,DIRECT,no-resolve
+  - MATCH,DIRECT
+  - DOMAIN,unreachable.com,DIRECT
+",
+            ),
+            "192.168.1.2:7897",
+        );
+        let lines: Vec<&str> = script.lines().collect();
+        assert!(lines.contains(
+            &"  var PROXY = \"PROXY 192.168.1.2:7897; SOCKS5 192.168.1.2:7897; DIRECT\";"
+        ));
+        let branches: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| line.starts_with("  if (") || line.starts_with("  return"))
+            .collect();
+        assert_eq!(
+            branches,
+            vec![
+                "  if (host == \"example.com\") return \"DIRECT\";",
+                "  if (host == \"google.com\" || dnsDomainIs(host, \".google.com\")) return PROXY;",
+                "  if (host.indexOf(\"ads\") >= 0) return REJECT;",
+                "  if (inNet(ip(false), \"192.168.0.0\", \"255.255.0.0\")) return \"DIRECT\";",
+                "  return \"DIRECT\";",
+            ]
+        );
+
+        // GEOIP 无法在 pac 中判断，之后的 MATCH 不能直接生效
+        let script = generate(
+            &config("mode: rule\nrules: ['DOMAIN,a.com,DIRECT', 'GEOIP,US,Proxy', 'MATCH,DIRECT']"),
+            "h:1",
+        );
+        assert!(script.contains("  if (host == \"a.com\") return \"DIRECT\";"));
+        assert!(!script.contains("  return \"DIRECT\";"));
+        assert!(script.trim_end().ends_with("  return PROXY;\n}"));
+
+        let script = generate(&config("mode: global\nrules: [DOMAIN,a.com,DIRECT]"), "h:1");
+        assert!(!script.contains("a.com"));
+        assert!(script.contains("  return PROXY;"));
+        assert!(generate(&config("mode: direct"), "h:1").contains("  return \"DIRECT\";"));
+    }
+}
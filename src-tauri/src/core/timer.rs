@@ -1,8 +1,15 @@
 use crate::{
-    config::Config, core::sysopt::Sysopt, feat, logging, logging_error, singleton,
-    utils::logging::Type,
+    config::Config,
+    core::{
+        sysopt::Sysopt,
+        update_policy::UpdatePolicy,
+        update_schedule::{ScheduleKind, UpdateSchedule},
+    },
+    feat, logging, logging_error, singleton,
+    utils::{dirs, help, logging::Type},
 };
 use anyhow::{Context as _, Result};
+use chrono::{Local, TimeZone as _};
 use delay_timer::prelude::{DelayTimer, DelayTimerBuilder, TaskBuilder};
 use parking_lot::RwLock;
 use smartstring::alias::String;
@@ -19,12 +26,17 @@ use tokio::time::{sleep, timeout};
 
 type TaskID = u64;
 
+/// `next_run` of a schedule that never fires again
+const NEVER: i64 = i64::MAX;
+/// used when the update policy of the profile is invalid
+const DEFAULT_TASK_TIMEOUT: Duration = Duration::from_secs(40);
+
 #[derive(Debug, Clone)]
 pub struct TimerTask {
     pub task_id: TaskID,
-    pub interval_minutes: u64,
-    #[allow(unused)]
-    pub last_run: i64, // Timestamp of last execution
+    pub schedule: UpdateSchedule,
+    /// Timestamp of the next execution, persisted across restarts
+    pub next_run: i64,
 }
 
 pub struct Timer {
@@ -39,6 +51,9 @@ pub struct Timer {
 
     /// Flag to mark if timer is initialized - atomic for better performance
     pub initialized: AtomicBool,
+
+    /// task id and interval in minutes of the media unlock re-check
+    pub unlock_check_task: RwLock<Option<(TaskID, u64)>>,
 }
 
 // Use singleton macro
@@ -51,6 +66,7 @@ impl Timer {
             timer_map: Arc::new(RwLock::new(HashMap::new())),
             timer_count: AtomicU64::new(1),
             initialized: AtomicBool::new(false),
+            unlock_check_task: RwLock::new(None),
         }
     }
 
@@ -88,43 +104,28 @@ impl Timer {
                 logging!(
                     info,
                     Type::Timer,
-                    "注册了定时任务 - uid={}, interval={}min, task_id={}",
+                    "注册了定时任务 - uid={}, schedule={:?}, next_run={}, task_id={}",
                     uid,
-                    task.interval_minutes,
+                    task.schedule.kind,
+                    task.next_run,
                     task.task_id
                 );
             }
         }
 
-        let cur_timestamp = chrono::Local::now().timestamp();
-
-        // Collect profiles that need immediate update
-        let profiles_to_update =
-            if let Some(items) = Config::profiles().await.latest_arc().get_items() {
-                items
-                    .iter()
-                    .filter_map(|item| {
-                        let allow_auto_update =
-                            item.option.as_ref()?.allow_auto_update.unwrap_or_default();
-                        if !allow_auto_update {
-                            return None;
-                        }
-
-                        let interval = item.option.as_ref()?.update_interval? as i64;
-                        let updated = item.updated? as i64;
-                        let uid = item.uid.as_ref()?;
-
-                        if interval > 0 && cur_timestamp - updated >= interval * 60 {
-                            logging!(info, Type::Timer, "需要立即更新的配置: uid={}", uid);
-                            Some(uid.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<String>>()
-            } else {
-                Vec::new()
-            };
+        let cur_timestamp = Local::now().timestamp();
+
+        // 错过的更新在启动时补上
+        let profiles_to_update = self
+            .timer_map
+            .read()
+            .iter()
+            .filter(|(_, task)| task.next_run <= cur_timestamp)
+            .map(|(uid, _)| {
+                logging!(info, Type::Timer, "需要立即更新的配置: uid={}", uid);
+                uid.clone()
+            })
+            .collect::<Vec<String>>();
 
         // Advance tasks outside of locks to minimize lock contention
         if !profiles_to_update.is_empty() {
@@ -147,6 +148,10 @@ impl Timer {
             }
         }
 
+        if let Err(e) = self.refresh_unlock_check().await {
+            logging!(warn, Type::Timer, "Failed to add unlock check task: {}", e);
+        }
+
         logging!(info, Type::Timer, "Timer initialization completed");
         Ok(())
     }
@@ -170,6 +175,68 @@ impl Timer {
         Ok(())
     }
 
+    /// Re-check the nodes of `unlock_check_group` every `unlock_check_interval` minutes.
+    /// 0 or no group disables it, the current route alone can't tell a change of the service
+    /// from a change of the selected node
+    pub async fn refresh_unlock_check(&self) -> Result<()> {
+        let (mut interval, has_group) = {
+            let verge = Config::verge().await.latest_arc();
+            (
+                verge.unlock_check_interval.unwrap_or(0),
+                verge
+                    .unlock_check_group
+                    .as_ref()
+                    .is_some_and(|group| !group.is_empty()),
+            )
+        };
+        if interval > 0 && !has_group {
+            logging!(
+                warn,
+                Type::Timer,
+                "Unlock check interval is set without unlock_check_group, not scheduling it"
+            );
+            interval = 0;
+        }
+
+        let mut current = self.unlock_check_task.write();
+        if current.map_or(0, |(_, minutes)| minutes) == interval {
+            return Ok(());
+        }
+        if let Some((tid, _)) = current.take()
+            && let Err(e) = self.delay_timer.write().remove_task(tid)
+        {
+            logging!(
+                warn,
+                Type::Timer,
+                "Failed to remove unlock check task {}: {}",
+                tid,
+                e
+            );
+        }
+        if interval == 0 {
+            logging!(info, Type::Timer, "Unlock check disabled");
+            return Ok(());
+        }
+
+        let tid = self.timer_count.fetch_add(1, Ordering::SeqCst);
+        let task = TaskBuilder::default()
+            .set_task_id(tid)
+            .set_maximum_parallel_runnable_num(1)
+            .set_frequency_repeated_by_minutes(interval)
+            .spawn_async_routine(|| async move {
+                logging!(info, Type::Timer, "Running unlock check task");
+                crate::cmd::run_scheduled_unlock_check().await;
+            })
+            .context("failed to create unlock check timer task")?;
+        self.delay_timer
+            .write()
+            .add_task(task)
+            .context("failed to add unlock check timer task")?;
+        *current = Some((tid, interval));
+        logging!(info, Type::Timer, "Unlock check every {} minutes", interval);
+        Ok(())
+    }
+
     /// Refresh timer tasks with better error handling
     pub async fn refresh(&self) -> Result<()> {
         // Generate diff outside of lock to minimize lock contention
@@ -188,8 +255,9 @@ impl Timer {
         );
 
         // Apply changes - first collect operations to perform without holding locks
-        let mut operations_to_add: Vec<(String, TaskID, u64)> = Vec::new();
-        let _operations_to_remove: Vec<String> = Vec::new();
+        let mut operations_to_add: Vec<(String, TaskID)> = Vec::new();
+        let persisted = Self::load_next_runs().await;
+        let now = Local::now();
 
         // Perform sync operations while holding locks
         {
@@ -211,17 +279,22 @@ impl Timer {
                             logging!(debug, Type::Timer, "Removed task {} for uid {}", tid, uid);
                         }
                     }
-                    DiffFlag::Add(tid, interval) => {
+                    DiffFlag::Add(tid, schedule, updated) => {
+                        // 优先使用上次保存的下次执行时间
+                        let next_run = persisted
+                            .get(&uid)
+                            .copied()
+                            .unwrap_or_else(|| Self::first_run(&schedule, updated, now));
                         let task = TimerTask {
                             task_id: tid,
-                            interval_minutes: interval,
-                            last_run: chrono::Local::now().timestamp(),
+                            schedule,
+                            next_run,
                         };
 
                         self.timer_map.write().insert(uid.clone(), task);
-                        operations_to_add.push((uid, tid, interval));
+                        operations_to_add.push((uid, tid));
                     }
-                    DiffFlag::Mod(tid, interval) => {
+                    DiffFlag::Mod(tid, schedule, updated) => {
                         // Remove old task first
                         let value = self.delay_timer.write().remove_task(tid);
                         if let Err(e) = value {
@@ -236,24 +309,25 @@ impl Timer {
                         }
 
                         // Then add the new one
+                        let next_run = Self::first_run(&schedule, updated, now);
                         let task = TimerTask {
                             task_id: tid,
-                            interval_minutes: interval,
-                            last_run: chrono::Local::now().timestamp(),
+                            schedule,
+                            next_run,
                         };
 
                         self.timer_map.write().insert(uid.clone(), task);
-                        operations_to_add.push((uid, tid, interval));
+                        operations_to_add.push((uid, tid));
                     }
                 }
             }
         } // Locks are dropped here
 
         // Now perform async operations without holding locks
-        for (uid, tid, interval) in operations_to_add {
+        for (uid, tid) in operations_to_add {
             // Re-acquire locks for individual operations
             let delay_timer = self.delay_timer.write();
-            if let Err(e) = self.add_task(&delay_timer, uid.clone(), tid, interval) {
+            if let Err(e) = self.add_task(&delay_timer, uid.clone(), tid) {
                 logging_error!(Type::Timer, "Failed to add task for uid {}: {}", uid, e);
 
                 // Rollback on failure - remove from timer_map
@@ -263,29 +337,85 @@ impl Timer {
             }
         }
 
+        self.save_next_runs().await;
         Ok(())
     }
 
-    /// Generate map of profile UIDs to update intervals
-    async fn gen_map(&self) -> HashMap<String, u64> {
+    /// The first run of a new or changed schedule, counted from the last update of the profile
+    fn first_run(schedule: &UpdateSchedule, updated: i64, now: chrono::DateTime<Local>) -> i64 {
+        let last_update = Local
+            .timestamp_opt(updated, 0)
+            .single()
+            .filter(|_| updated > 0)
+            .unwrap_or(now);
+        schedule
+            .next_after(last_update)
+            .map_or(NEVER, |next| next.timestamp())
+    }
+
+    /// Next run timestamps saved by the previous session
+    async fn load_next_runs() -> HashMap<String, i64> {
+        let Ok(path) = dirs::update_schedule_path() else {
+            return HashMap::new();
+        };
+        if !path.exists() {
+            return HashMap::new();
+        }
+        help::read_yaml(&path).await.unwrap_or_else(|e| {
+            logging!(warn, Type::Timer, "Failed to read update schedule: {}", e);
+            HashMap::new()
+        })
+    }
+
+    async fn save_next_runs(&self) {
+        let next_runs: HashMap<String, i64> = self
+            .timer_map
+            .read()
+            .iter()
+            .filter(|(_, task)| task.next_run != NEVER)
+            .map(|(uid, task)| (uid.clone(), task.next_run))
+            .collect();
+        let result = match dirs::update_schedule_path() {
+            Ok(path) => {
+                help::save_yaml(
+                    &path,
+                    &next_runs,
+                    Some("# Next automatic update of each profile"),
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            logging!(warn, Type::Timer, "Failed to save update schedule: {}", e);
+        }
+    }
+
+    /// Generate map of profile UIDs to update schedules and last update times
+    async fn gen_map(&self) -> HashMap<String, (UpdateSchedule, i64)> {
         let mut new_map = HashMap::new();
 
         if let Some(items) = Config::profiles().await.latest_arc().get_items() {
             for item in items.iter() {
-                if let Some(option) = item.option.as_ref()
-                    && let Some(allow_auto_update) = option.allow_auto_update
-                    && let (Some(interval), Some(uid)) = (option.update_interval, &item.uid)
-                    && allow_auto_update
-                    && interval > 0
-                {
-                    logging!(
-                        debug,
-                        Type::Timer,
-                        "找到定时更新配置: uid={}, interval={}min",
-                        uid,
-                        interval
-                    );
-                    new_map.insert(uid.clone(), interval);
+                let (Some(option), Some(uid)) = (item.option.as_ref(), &item.uid) else {
+                    continue;
+                };
+                match UpdateSchedule::from_option(option) {
+                    Some(Ok(schedule)) => {
+                        logging!(
+                            debug,
+                            Type::Timer,
+                            "找到定时更新配置: uid={}, schedule={:?}",
+                            uid,
+                            schedule.kind
+                        );
+                        let updated = item.updated.unwrap_or(0) as i64;
+                        new_map.insert(uid.clone(), (schedule, updated));
+                    }
+                    Some(Err(e)) => {
+                        logging!(warn, Type::Timer, "定时更新配置无效: uid={}, {}", uid, e);
+                    }
+                    None => {}
                 }
             }
         }
@@ -316,17 +446,20 @@ impl Timer {
         // Find tasks to modify or delete
         for (uid, task) in timer_map.iter() {
             match new_map.get(uid) {
-                Some(&interval) if interval != task.interval_minutes => {
-                    // Task exists but interval changed
+                Some((schedule, updated)) if *schedule != task.schedule => {
+                    // Task exists but schedule changed
                     logging!(
                         debug,
                         Type::Timer,
-                        "定时任务间隔变更: uid={}, 旧={}, 新={}",
+                        "定时任务计划变更: uid={}, 旧={:?}, 新={:?}",
                         uid,
-                        task.interval_minutes,
-                        interval
+                        task.schedule.kind,
+                        schedule.kind
+                    );
+                    diff_map.insert(
+                        uid.clone(),
+                        DiffFlag::Mod(task.task_id, schedule.clone(), *updated),
                     );
-                    diff_map.insert(uid.clone(), DiffFlag::Mod(task.task_id, interval));
                 }
                 None => {
                     // Task no longer needed
@@ -334,7 +467,7 @@ impl Timer {
                     diff_map.insert(uid.clone(), DiffFlag::Del(task.task_id));
                 }
                 _ => {
-                    // Task exists with same interval, no change needed
+                    // Task exists with same schedule, no change needed
                     logging!(debug, Type::Timer, "定时任务保持不变: uid={}", uid);
                 }
             }
@@ -344,16 +477,16 @@ impl Timer {
         let mut next_id = self.timer_count.load(Ordering::Relaxed);
         let original_id = next_id;
 
-        for (uid, &interval) in new_map.iter() {
-            if !timer_map.contains_key(uid) {
+        for (uid, (schedule, updated)) in new_map.into_iter() {
+            if !timer_map.contains_key(&uid) {
                 logging!(
                     debug,
                     Type::Timer,
-                    "新增定时任务: uid={}, interval={}min",
+                    "新增定时任务: uid={}, schedule={:?}",
                     uid,
-                    interval
+                    schedule.kind
                 );
-                diff_map.insert(uid.clone(), DiffFlag::Add(next_id, interval));
+                diff_map.insert(uid, DiffFlag::Add(next_id, schedule, updated));
                 next_id += 1;
             }
         }
@@ -368,32 +501,18 @@ impl Timer {
     }
 
     /// Add a timer task with better error handling
-    fn add_task(
-        &self,
-        delay_timer: &DelayTimer,
-        uid: String,
-        tid: TaskID,
-        minutes: u64,
-    ) -> Result<()> {
-        logging!(
-            info,
-            Type::Timer,
-            "Adding task: uid={}, id={}, interval={}min",
-            uid,
-            tid,
-            minutes
-        );
+    /// the task checks every minute whether the profile is due, so missed runs are caught up
+    fn add_task(&self, delay_timer: &DelayTimer, uid: String, tid: TaskID) -> Result<()> {
+        logging!(info, Type::Timer, "Adding task: uid={}, id={}", uid, tid);
 
-        // Create a task with reasonable retries and backoff
         let task = TaskBuilder::default()
             .set_task_id(tid)
             .set_maximum_parallel_runnable_num(1)
-            .set_frequency_repeated_by_minutes(minutes)
+            .set_frequency_repeated_by_minutes(1)
             .spawn_async_routine(move || {
                 let uid = uid.clone();
                 Box::pin(async move {
-                    Self::wait_until_sysopt(Duration::from_millis(1000)).await;
-                    Self::async_task(&uid).await;
+                    Self::global().run_if_due(&uid).await;
                 }) as Pin<Box<dyn std::future::Future<Output = ()> + Send>>
             })
             .context("failed to create timer task")?;
@@ -405,65 +524,74 @@ impl Timer {
         Ok(())
     }
 
-    /// Get next update time for a profile
-    pub async fn get_next_update_time(&self, uid: &str) -> Option<i64> {
-        logging!(info, Type::Timer, "获取下次更新时间，uid={}", uid);
-
-        // First extract timer task data without holding the lock across await
-        let task_interval = {
-            let timer_map = self.timer_map.read();
-            match timer_map.get(uid) {
-                Some(t) => t.interval_minutes,
-                None => {
-                    logging!(warn, Type::Timer, "找不到对应的定时任务，uid={}", uid);
-                    return None;
-                }
+    /// Update the profile if its next run has passed, then schedule the following one
+    async fn run_if_due(&self, uid: &String) {
+        let now = Local::now();
+        let should_run = {
+            let mut timer_map = self.timer_map.write();
+            let Some(task) = timer_map.get_mut(uid) else {
+                return;
+            };
+            if now.timestamp() < task.next_run {
+                return;
             }
+            // 处于禁止更新的时间段内，推迟到时间段结束
+            let (should_run, next) = if task.schedule.is_blocked(now) {
+                (false, task.schedule.resume_after(now))
+            } else {
+                (true, task.schedule.next_after(now))
+            };
+            task.next_run = next.map_or(NEVER, |next| next.timestamp());
+            should_run
         };
+        self.save_next_runs().await;
 
-        // Get the profile updated timestamp - now safe to await
-        let items = {
-            let profiles = Config::profiles().await;
-            let profiles_guard = profiles.latest_arc();
-            match profiles_guard.get_items() {
-                Some(i) => i.clone(),
-                None => {
-                    logging!(warn, Type::Timer, "获取配置列表失败");
-                    return None;
-                }
+        if should_run {
+            Self::wait_until_sysopt(Duration::from_millis(1000)).await;
+            Self::async_task(uid).await;
+        }
+    }
+
+    /// Count an interval schedule again from `updated`, the last successful update of the profile,
+    /// so a manual update also pushes back the next automatic one
+    pub async fn reschedule_after_update(&self, uid: &str, updated: i64) {
+        {
+            let mut timer_map = self.timer_map.write();
+            let Some(task) = timer_map.get_mut(uid) else {
+                return;
+            };
+            if !matches!(task.schedule.kind, ScheduleKind::Interval(_)) {
+                return;
             }
-        };
+            task.next_run = Self::first_run(&task.schedule, updated, Local::now());
+        }
+        self.save_next_runs().await;
+    }
+
+    /// Get next update time for a profile
+    pub async fn get_next_update_time(&self, uid: &str) -> Option<i64> {
+        logging!(info, Type::Timer, "获取下次更新时间，uid={}", uid);
 
-        let profile = match items.iter().find(|item| item.uid.as_deref() == Some(uid)) {
-            Some(p) => p,
+        let next_run = match self.timer_map.read().get(uid) {
+            Some(task) => task.next_run,
             None => {
-                logging!(warn, Type::Timer, "找不到对应的配置，uid={}", uid);
+                logging!(warn, Type::Timer, "找不到对应的定时任务，uid={}", uid);
                 return None;
             }
         };
 
-        let updated = profile.updated.unwrap_or(0) as i64;
-
-        // Calculate next update time
-        if updated > 0 && task_interval > 0 {
-            let next_time = updated + (task_interval as i64 * 60);
+        if next_run == NEVER {
+            logging!(warn, Type::Timer, "定时任务没有下次执行时间，uid={}", uid);
+            None
+        } else {
             logging!(
                 info,
                 Type::Timer,
                 "计算得到下次更新时间: {}, uid={}",
-                next_time,
+                next_run,
                 uid
             );
-            Some(next_time)
-        } else {
-            logging!(
-                warn,
-                Type::Timer,
-                "更新时间或间隔无效，updated={}, interval={}",
-                updated,
-                task_interval
-            );
-            None
+            Some(next_run)
         }
     }
 
@@ -483,7 +611,20 @@ impl Timer {
         let task_start = std::time::Instant::now();
         logging!(info, Type::Timer, "Running timer task for profile: {}", uid);
 
-        match tokio::time::timeout(std::time::Duration::from_secs(40), async {
+        // 超时时间覆盖所有传输方式的重试和退避
+        let task_timeout = {
+            let profiles = Config::profiles().await;
+            let profiles = profiles.latest_arc();
+            let option = profiles
+                .get_item(uid)
+                .ok()
+                .and_then(|item| item.option.as_ref());
+            UpdatePolicy::from_option(option)
+                .map(|policy| policy.max_duration())
+                .unwrap_or(DEFAULT_TASK_TIMEOUT)
+        };
+
+        match tokio::time::timeout(task_timeout, async {
             Self::emit_update_event(uid, true);
 
             let is_current = Config::profiles().await.latest_arc().current.as_ref() == Some(uid);
@@ -537,6 +678,7 @@ impl Timer {
 #[derive(Debug)]
 enum DiffFlag {
     Del(TaskID),
-    Add(TaskID, u64),
-    Mod(TaskID, u64),
+    /// task id, schedule and the last update time of the profile
+    Add(TaskID, UpdateSchedule, i64),
+    Mod(TaskID, UpdateSchedule, i64),
 }
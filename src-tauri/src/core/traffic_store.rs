@@ -0,0 +1,456 @@
+use crate::{config::PrfExtra, utils::dirs};
+use anyhow::{Context as _, Result, bail};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use smartstring::alias::String;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write as _,
+    path::PathBuf,
+};
+use tokio::fs;
+
+/// traffic dir under the app home dir, one `{date}.json` per day
+pub const TRAFFIC_DIR: &str = "traffic";
+
+/// per profile usage since its last subscription update
+const QUOTA_FILE: &str = "quota.json";
+
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// the node of connections that bypass the proxies
+const DIRECT: &str = "DIRECT";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Usage {
+    pub up: u64,
+    pub down: u64,
+}
+
+impl Usage {
+    pub const fn add(&mut self, other: Self) {
+        self.up = self.up.saturating_add(other.up);
+        self.down = self.down.saturating_add(other.down);
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.up == 0 && self.down == 0
+    }
+
+    /// `self - other`, stopping at zero
+    const fn saturating_sub(self, other: Self) -> Self {
+        Self {
+            up: self.up.saturating_sub(other.up),
+            down: self.down.saturating_sub(other.down),
+        }
+    }
+
+    /// `self - previous`, or all of `self` when the counter was reset
+    const fn since(self, previous: Self) -> Self {
+        if self.up < previous.up || self.down < previous.down {
+            self
+        } else {
+            Self {
+                up: self.up - previous.up,
+                down: self.down - previous.down,
+            }
+        }
+    }
+}
+
+/// The traffic of one day
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DayTraffic {
+    pub total: Usage,
+    /// by profile uid
+    pub profiles: BTreeMap<String, Usage>,
+    /// by the proxy group the connection matched
+    pub groups: BTreeMap<String, Usage>,
+    /// by the node the connection went out through
+    pub nodes: BTreeMap<String, Usage>,
+    /// by `rule(payload)`
+    pub rules: BTreeMap<String, Usage>,
+}
+
+impl DayTraffic {
+    pub fn merge(&mut self, other: &Self) {
+        self.total.add(other.total);
+        for (target, source) in [
+            (&mut self.profiles, &other.profiles),
+            (&mut self.groups, &other.groups),
+            (&mut self.nodes, &other.nodes),
+            (&mut self.rules, &other.rules),
+        ] {
+            for (key, usage) in source {
+                target.entry(key.clone()).or_default().add(*usage);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total.is_empty() && self.groups.is_empty()
+    }
+}
+
+/// A stored day, as returned by range queries
+#[derive(Debug, Clone, Serialize)]
+pub struct TrafficDay {
+    pub date: String,
+    #[serde(flatten)]
+    pub traffic: DayTraffic,
+}
+
+/// One connection reported by the core, with its traffic so far
+pub struct ConnectionSample<'a> {
+    pub id: &'a str,
+    pub usage: Usage,
+    /// the node first and the matched group last
+    pub chains: &'a [std::string::String],
+    pub rule: &'a str,
+    pub rule_payload: &'a str,
+}
+
+/// Turns the cumulative counters of the core into traffic deltas
+#[derive(Debug, Default)]
+pub struct Sampler {
+    totals: Option<Usage>,
+    connections: HashMap<String, Usage>,
+}
+
+impl Sampler {
+    /// The traffic since the previous sample.
+    /// Only the traffic that did not go out `DIRECT` is counted under `profile`, as that is what
+    /// the subscription meters; connections closed between samples are counted as proxied.
+    /// The first sample only sets the baseline, so traffic from before the app started is not counted
+    pub fn sample<'a>(
+        &mut self,
+        profile: &str,
+        totals: Usage,
+        connections: impl IntoIterator<Item = ConnectionSample<'a>>,
+    ) -> DayTraffic {
+        let mut delta = DayTraffic::default();
+        let baseline = self.totals.is_none();
+        if let Some(previous) = self.totals.replace(totals) {
+            delta.total = totals.since(previous);
+        }
+
+        let mut direct = Usage::default();
+
+        let mut seen = HashSet::new();
+        for connection in connections {
+            let previous = self
+                .connections
+                .insert(connection.id.into(), connection.usage)
+                .unwrap_or_default();
+            seen.insert(String::from(connection.id));
+            let usage = connection.usage.since(previous);
+            if baseline || usage.is_empty() {
+                continue;
+            }
+            if let Some(node) = connection.chains.first() {
+                if node == DIRECT {
+                    direct.add(usage);
+                }
+                delta.nodes.entry(node.into()).or_default().add(usage);
+            }
+            if let Some(group) = connection.chains.last() {
+                delta.groups.entry(group.into()).or_default().add(usage);
+            }
+            let rule = if connection.rule_payload.is_empty() {
+                connection.rule.into()
+            } else {
+                format!("{}({})", connection.rule, connection.rule_payload).into()
+            };
+            delta.rules.entry(rule).or_default().add(usage);
+        }
+        // 已关闭的连接不再跟踪
+        self.connections.retain(|id, _| seen.contains(id));
+
+        let proxied = delta.total.saturating_sub(direct);
+        if !proxied.is_empty() {
+            delta.profiles.insert(profile.into(), proxied);
+        }
+        delta
+    }
+}
+
+/// Usage of a profile since its subscription info was last updated
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+pub struct QuotaState {
+    /// `PrfItem::updated` the usage is counted from
+    pub updated: usize,
+    pub usage: Usage,
+    pub warned: bool,
+}
+
+/// Percentage of the subscription quota used, counting the local usage since the last update
+pub const fn quota_percent(extra: &PrfExtra, local: Usage) -> Option<u64> {
+    if extra.total == 0 {
+        return None;
+    }
+    let used = extra
+        .upload
+        .saturating_add(extra.download)
+        .saturating_add(local.up)
+        .saturating_add(local.down);
+    Some((used as u128 * 100 / extra.total as u128) as u64)
+}
+
+pub fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT)
+        .with_context(|| format!("invalid date \"{date}\", expected YYYY-MM-DD"))
+}
+
+fn traffic_dir() -> Result<PathBuf> {
+    Ok(dirs::app_home_dir()?.join(TRAFFIC_DIR))
+}
+
+async fn read_day(path: &PathBuf) -> Result<DayTraffic> {
+    let data = fs::read(path)
+        .await
+        .with_context(|| format!("failed to read \"{}\"", path.display()))?;
+    serde_json::from_slice(&data).with_context(|| format!("failed to parse \"{}\"", path.display()))
+}
+
+/// Add `delta` to the stored traffic of `date`
+pub async fn merge_day(date: &str, delta: &DayTraffic) -> Result<()> {
+    let dir = traffic_dir()?;
+    fs::create_dir_all(&dir).await?;
+    let path = dir.join(format!("{date}.json"));
+    let mut day = if path.exists() {
+        read_day(&path).await?
+    } else {
+        DayTraffic::default()
+    };
+    day.merge(delta);
+    fs::write(&path, serde_json::to_vec(&day)?)
+        .await
+        .with_context(|| format!("failed to write \"{}\"", path.display()))
+}
+
+/// The stored days from `start` to `end`, both inclusive
+pub async fn query(start: &str, end: &str) -> Result<Vec<TrafficDay>> {
+    let (start, end) = (parse_date(start)?, parse_date(end)?);
+    if start > end {
+        bail!("the start date is after the end date");
+    }
+    let dir = traffic_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut days = Vec::new();
+    let mut entries = fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let Some(date) = name.to_str().and_then(|name| name.strip_suffix(".json")) else {
+            continue;
+        };
+        let Ok(parsed) = parse_date(date) else {
+            continue;
+        };
+        if parsed < start || parsed > end {
+            continue;
+        }
+        days.push(TrafficDay {
+            date: date.into(),
+            traffic: read_day(&entry.path()).await?,
+        });
+    }
+    days.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(days)
+}
+
+/// Delete the days before `cutoff`
+pub async fn prune(cutoff: NaiveDate) -> Result<()> {
+    let dir = traffic_dir()?;
+    if !dir.exists() {
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let Some(date) = name.to_str().and_then(|name| name.strip_suffix(".json")) else {
+            continue;
+        };
+        if parse_date(date).is_ok_and(|date| date < cutoff) {
+            fs::remove_file(entry.path()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// One `date,kind,key,upload,download` row per entry, `kind` being total, profile, group, node or rule
+pub fn to_csv(days: &[TrafficDay]) -> String {
+    fn field(value: &str) -> std::borrow::Cow<'_, str> {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\"")).into()
+        } else {
+            value.into()
+        }
+    }
+
+    let mut csv = std::string::String::from("date,kind,key,upload,download\n");
+    for day in days {
+        let traffic = &day.traffic;
+        let _ = writeln!(
+            csv,
+            "{},total,,{},{}",
+            day.date, traffic.total.up, traffic.total.down
+        );
+        for (kind, map) in [
+            ("profile", &traffic.profiles),
+            ("group", &traffic.groups),
+            ("node", &traffic.nodes),
+            ("rule", &traffic.rules),
+        ] {
+            for (key, usage) in map {
+                let _ = writeln!(
+                    csv,
+                    "{},{kind},{},{},{}",
+                    day.date,
+                    field(key),
+                    usage.up,
+                    usage.down
+                );
+            }
+        }
+    }
+    csv.into()
+}
+
+async fn quota_path() -> Result<PathBuf> {
+    let dir = traffic_dir()?;
+    fs::create_dir_all(&dir).await?;
+    Ok(dir.join(QUOTA_FILE))
+}
+
+pub async fn load_quota() -> Result<BTreeMap<String, QuotaState>> {
+    let path = quota_path().await?;
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let data = fs::read(&path)
+        .await
+        .with_context(|| format!("failed to read \"{}\"", path.display()))?;
+    serde_json::from_slice(&data).context("failed to parse the quota state")
+}
+
+pub async fn save_quota(quota: &BTreeMap<String, QuotaState>) -> Result<()> {
+    let path = quota_path().await?;
+    fs::write(&path, serde_json::to_vec(quota)?)
+        .await
+        .with_context(|| format!("failed to write \"{}\"", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection<'a>(
+        id: &'a str,
+        up: u64,
+        down: u64,
+        chains: &'a [std::string::String],
+    ) -> ConnectionSample<'a> {
+        ConnectionSample {
+            id,
+            usage: Usage { up, down },
+            chains,
+            rule: "DomainSuffix",
+            rule_payload: "example.com",
+        }
+    }
+
+    #[test]
+    fn test_sampler() {
+        let chains = ["HK 01".to_string(), "Proxy".to_string()];
+        let mut sampler = Sampler::default();
+
+        let first = sampler.sample(
+            "p1",
+            Usage {
+                up: 100,
+                down: 1000,
+            },
+            [connection("a", 10, 100, &chains)],
+        );
+        assert!(first.is_empty());
+
+        let delta = sampler.sample(
+            "p1",
+            Usage {
+                up: 150,
+                down: 1500,
+            },
+            [
+                connection("a", 30, 300, &chains),
+                connection("b", 5, 50, &chains),
+            ],
+        );
+        assert_eq!(delta.total, Usage { up: 50, down: 500 });
+        assert_eq!(delta.profiles["p1"], Usage { up: 50, down: 500 });
+        assert_eq!(delta.nodes["HK 01"], Usage { up: 25, down: 250 });
+        assert_eq!(delta.groups["Proxy"], Usage { up: 25, down: 250 });
+        assert_eq!(
+            delta.rules["DomainSuffix(example.com)"],
+            Usage { up: 25, down: 250 }
+        );
+
+        // 直连流量不计入订阅用量
+        let direct = ["DIRECT".to_string(), "Proxy".to_string()];
+        let delta = sampler.sample(
+            "p1",
+            Usage {
+                up: 200,
+                down: 2000,
+            },
+            [
+                connection("a", 40, 400, &chains),
+                connection("c", 30, 300, &direct),
+            ],
+        );
+        assert_eq!(delta.total, Usage { up: 50, down: 500 });
+        assert_eq!(delta.profiles["p1"], Usage { up: 20, down: 200 });
+        assert_eq!(delta.nodes["DIRECT"], Usage { up: 30, down: 300 });
+
+        // 内核重启后计数从零开始
+        let delta = sampler.sample("p2", Usage { up: 20, down: 200 }, []);
+        assert_eq!(delta.total, Usage { up: 20, down: 200 });
+        assert!(delta.nodes.is_empty());
+
+        let mut day = DayTraffic::default();
+        day.merge(&delta);
+        day.merge(&delta);
+        assert_eq!(day.profiles["p2"], Usage { up: 40, down: 400 });
+    }
+
+    #[test]
+    fn test_quota_and_csv() {
+        let extra = PrfExtra {
+            upload: 10,
+            download: 70,
+            total: 100,
+            expire: 0,
+        };
+        assert_eq!(quota_percent(&extra, Usage { up: 5, down: 5 }), Some(90));
+        assert_eq!(quota_percent(&PrfExtra::default(), Usage::default()), None);
+
+        let mut traffic = DayTraffic {
+            total: Usage { up: 1, down: 2 },
+            ..DayTraffic::default()
+        };
+        traffic
+            .rules
+            .insert("Match(a,b)".into(), Usage { up: 1, down: 2 });
+        let csv = to_csv(&[TrafficDay {
+            date: "2024-01-02".into(),
+            traffic,
+        }]);
+        assert_eq!(
+            csv,
+            "date,kind,key,upload,download\n2024-01-02,total,,1,2\n2024-01-02,rule,\"Match(a,b)\",1,2\n"
+        );
+    }
+}
@@ -2,7 +2,6 @@ use once_cell::sync::OnceCell;
 use tauri::tray::TrayIconBuilder;
 use tauri_plugin_mihomo::models::Proxies;
 use tokio::fs;
-#[cfg(target_os = "macos")]
 pub mod speed_rate;
 use crate::config::{IVerge, PrfSelected};
 use crate::core::service;
@@ -230,6 +229,7 @@ impl Tray {
         }
         // TODO: 初始化时，暂时使用此方法更新系统托盘菜单，有效避免代理节点菜单空白
         crate::core::timer::Timer::global().add_update_tray_menu_task()?;
+        speed_rate::SpeedRate::global().refresh().await;
         Ok(())
     }
 
@@ -474,7 +474,7 @@ impl Tray {
             |(main, rest)| format!("{main}+{}", rest.split('.').next().unwrap_or("")),
         );
 
-        let tooltip = format!(
+        let mut tooltip = format!(
             "RV Verge {}\n{}: {}\n{}: {}\n{}: {}",
             reassembled_version,
             sys_proxy_text,
@@ -484,6 +484,10 @@ impl Tray {
             profile_text,
             current_profile_name
         );
+        if let Some(rate) = speed_rate::SpeedRate::global().tooltip_line() {
+            tooltip.push('\n');
+            tooltip.push_str(&rate);
+        }
 
         if let Some(tray) = app_handle.tray_by_id("main") {
             let _ = tray.set_tooltip(Some(&tooltip));
@@ -619,14 +623,26 @@ async fn create_profile_menu_item(
         .map(|(profile_uid, profile_name)| {
             let app_handle = app_handle.clone();
             async move {
-                let is_current_profile = Config::profiles()
-                    .await
-                    .latest_arc()
-                    .is_current_profile_index(profile_uid);
+                let (is_current_profile, is_stale) = {
+                    let profiles = Config::profiles().await;
+                    let profiles = profiles.latest_arc();
+                    (
+                        profiles.is_current_profile_index(profile_uid),
+                        profiles
+                            .get_item(profile_uid)
+                            .is_ok_and(|item| item.stale.unwrap_or(false)),
+                    )
+                };
+                // 连续更新失败的订阅加上标记
+                let label = if is_stale {
+                    format!("{profile_name} ⚠")
+                } else {
+                    profile_name.to_string()
+                };
                 CheckMenuItem::with_id(
                     &app_handle,
                     format!("profiles_{profile_uid}"),
-                    profile_name.as_str(),
+                    label,
                     true,
                     is_current_profile,
                     None::<&str>,
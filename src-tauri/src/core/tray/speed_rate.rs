@@ -1 +1,281 @@
+use super::Tray;
+use crate::{
+    config::Config, core::handle, logging, module::lightweight::is_in_lightweight_mode,
+    process::AsyncHandler, singleton_lazy, utils::logging::Type,
+};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use smartstring::alias::String;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
 
+/// the tray is redrawn at most once per interval, however often the core reports
+const MIN_RENDER_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(target_os = "windows")]
+const ICON_SIZE: u32 = 32;
+
+/// One message of the `/traffic` stream, in bytes per second
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct Traffic {
+    pub up: u64,
+    pub down: u64,
+}
+
+/// State of the traffic stream subscription
+#[derive(Debug, Default)]
+enum Stream {
+    #[default]
+    Stopped,
+    /// `ws_traffic` is being awaited, so the slot is taken
+    Starting,
+    Running(u32),
+}
+
+/// Shows the traffic rate of the core in the tray
+#[derive(Default)]
+pub struct SpeedRate {
+    stream: Mutex<Stream>,
+    latest: Mutex<Option<Traffic>>,
+    last_render: Mutex<Option<Instant>>,
+    rendering: AtomicBool,
+}
+
+singleton_lazy!(SpeedRate, SPEED_RATE, SpeedRate::default);
+
+impl SpeedRate {
+    /// Start or stop the stream according to `enable_tray_speed` and the lightweight mode
+    pub async fn refresh(&self) {
+        let enabled = Config::verge()
+            .await
+            .latest_arc()
+            .enable_tray_speed
+            .unwrap_or(false);
+        if enabled && !is_in_lightweight_mode() {
+            self.start().await;
+        } else {
+            self.stop().await;
+        }
+    }
+
+    async fn start(&self) {
+        {
+            let mut stream = self.stream.lock();
+            if !matches!(*stream, Stream::Stopped) {
+                return;
+            }
+            *stream = Stream::Starting;
+        }
+        let result = handle::Handle::mihomo()
+            .await
+            .ws_traffic(|message| {
+                if let Ok(traffic) = serde_json::from_str::<Traffic>(&message) {
+                    SpeedRate::global().on_traffic(traffic);
+                }
+            })
+            .await;
+        match result {
+            Ok(id) => {
+                let started = {
+                    let mut stream = self.stream.lock();
+                    let started = matches!(*stream, Stream::Starting);
+                    if started {
+                        *stream = Stream::Running(id);
+                    }
+                    started
+                };
+                if started {
+                    logging!(info, Type::Tray, "托盘网速显示已开启");
+                } else {
+                    // 订阅期间已被关闭或重新开启，丢弃这个订阅
+                    let _ = handle::Handle::mihomo().await.disconnect(id, None).await;
+                }
+            }
+            Err(e) => {
+                {
+                    let mut stream = self.stream.lock();
+                    if matches!(*stream, Stream::Starting) {
+                        *stream = Stream::Stopped;
+                    }
+                }
+                logging!(warn, Type::Tray, "订阅内核流量失败: {e:?}");
+            }
+        }
+    }
+
+    async fn stop(&self) {
+        let Stream::Running(id) = std::mem::take(&mut *self.stream.lock()) else {
+            return;
+        };
+        let _ = handle::Handle::mihomo().await.disconnect(id, None).await;
+        *self.latest.lock() = None;
+        logging!(info, Type::Tray, "托盘网速显示已关闭");
+
+        #[cfg(not(target_os = "windows"))]
+        if let Some(tray) = handle::Handle::app_handle().tray_by_id("main") {
+            let _ = tray.set_title(None::<&str>);
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let verge = Config::verge().await.latest_arc();
+            if let Err(e) = Tray::global().update_icon(&verge).await {
+                logging!(warn, Type::Tray, "恢复托盘图标失败: {e}");
+            }
+        }
+        if let Err(e) = Tray::global().update_tooltip().await {
+            logging!(warn, Type::Tray, "更新托盘提示失败: {e}");
+        }
+    }
+
+    fn on_traffic(&self, traffic: Traffic) {
+        // 忽略已关闭或被丢弃的订阅的消息
+        if !matches!(*self.stream.lock(), Stream::Running(_)) {
+            return;
+        }
+        *self.latest.lock() = Some(traffic);
+        {
+            let mut last_render = self.last_render.lock();
+            if last_render.is_some_and(|last| last.elapsed() < MIN_RENDER_INTERVAL) {
+                return;
+            }
+            *last_render = Some(Instant::now());
+        }
+        if self.rendering.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        AsyncHandler::spawn(|| async {
+            let speed_rate = SpeedRate::global();
+            speed_rate.render().await;
+            speed_rate.rendering.store(false, Ordering::Release);
+        });
+    }
+
+    async fn render(&self) {
+        let Some(traffic) = *self.latest.lock() else {
+            return;
+        };
+        if handle::Handle::global().is_exiting() {
+            return;
+        }
+        let Some(tray) = handle::Handle::app_handle().tray_by_id("main") else {
+            return;
+        };
+
+        // Windows 托盘不支持标题，改为绘制图标
+        #[cfg(not(target_os = "windows"))]
+        let _ = tray.set_title(Some(title(traffic).as_str()));
+        #[cfg(target_os = "windows")]
+        let _ = tray.set_icon(Some(tauri::image::Image::new_owned(
+            render_icon(traffic, ICON_SIZE),
+            ICON_SIZE,
+            ICON_SIZE,
+        )));
+
+        if let Err(e) = Tray::global().update_tooltip().await {
+            logging!(warn, Type::Tray, "更新托盘提示失败: {e}");
+        }
+    }
+
+    /// The rate line of the tray tooltip, while the stream is running
+    pub fn tooltip_line(&self) -> Option<String> {
+        (*self.latest.lock()).map(title)
+    }
+}
+
+fn title(traffic: Traffic) -> String {
+    format!(
+        "↑ {} ↓ {}",
+        format_rate(traffic.up),
+        format_rate(traffic.down)
+    )
+    .into()
+}
+
+fn format_rate(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes}B/s").into();
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value >= 100.0 {
+        format!("{value:.0}{}/s", UNITS[unit]).into()
+    } else {
+        format!("{value:.1}{}/s", UNITS[unit]).into()
+    }
+}
+
+/// Height of a rate bar out of `size`, on a log scale from 1 B/s to 100 MB/s
+fn bar_height(bytes: u64, size: u32) -> u32 {
+    const MAX_RATE: f64 = 100.0 * 1024.0 * 1024.0;
+    let level = ((bytes as f64 + 1.0).ln() / (MAX_RATE + 1.0).ln()).clamp(0.0, 1.0);
+    ((level * size as f64).round() as u32).max(1)
+}
+
+/// A square RGBA icon with an upload bar on the left and a download bar on the right
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn render_icon(traffic: Traffic, size: u32) -> Vec<u8> {
+    const UP_COLOR: [u8; 4] = [0x4c, 0xaf, 0x50, 0xff];
+    const DOWN_COLOR: [u8; 4] = [0x21, 0x96, 0xf3, 0xff];
+
+    let mut pixels = vec![0u8; (size * size * 4) as usize];
+    let bar_width = size * 3 / 8;
+    let bars = [
+        (size / 8, bar_height(traffic.up, size), UP_COLOR),
+        (size / 2, bar_height(traffic.down, size), DOWN_COLOR),
+    ];
+    for (left, height, color) in bars {
+        for y in size - height..size {
+            for x in left..left + bar_width {
+                let offset = ((y * size + x) * 4) as usize;
+                pixels[offset..offset + 4].copy_from_slice(&color);
+            }
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_rate() {
+        assert_eq!(format_rate(0), "0B/s");
+        assert_eq!(format_rate(1023), "1023B/s");
+        assert_eq!(format_rate(1536), "1.5KB/s");
+        assert_eq!(format_rate(200 * 1024 * 1024), "200MB/s");
+        assert_eq!(format_rate(3 * 1024 * 1024 * 1024), "3.0GB/s");
+    }
+
+    #[test]
+    fn test_render_icon() {
+        let size = 32;
+        let pixel = |pixels: &[u8], x: u32, y: u32| {
+            let offset = ((y * size + x) * 4) as usize;
+            pixels[offset + 3]
+        };
+
+        let idle = render_icon(Traffic::default(), size);
+        assert_eq!(idle.len(), (size * size * 4) as usize);
+        assert_eq!(pixel(&idle, 4, size - 1), 0xff);
+        assert_eq!(pixel(&idle, 4, size - 2), 0);
+
+        let busy = render_icon(
+            Traffic {
+                up: 1024,
+                down: 1024 * 1024 * 1024,
+            },
+            size,
+        );
+        assert_eq!(pixel(&busy, 4, size / 2), 0);
+        assert_eq!(pixel(&busy, size / 2, 0), 0xff);
+        assert!(bar_height(1024, size) < bar_height(1024 * 1024, size));
+    }
+}
@@ -0,0 +1,217 @@
+use crate::config::PrfOption;
+use anyhow::{Result, anyhow, bail};
+use backoff::ExponentialBackoff;
+use smartstring::alias::String;
+use std::{fmt, str::FromStr, time::Duration};
+
+const DEFAULT_RETRIES: u32 = 2;
+const DEFAULT_STALE_AFTER: u32 = 3;
+/// same default as `PrfItem::from_url`
+const DEFAULT_TIMEOUT_SECS: u64 = 20;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+const RANDOMIZATION_FACTOR: f64 = 0.5;
+
+/// How a subscription is downloaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdateTransport {
+    Direct,
+    /// through the mixed port of the core
+    SelfProxy,
+    SystemProxy,
+    /// through the node route of the core, out of `node`, or out of whatever `group` selects.
+    /// The groups of the user are not switched and the rules are not involved
+    Core {
+        group: String,
+        node: Option<String>,
+    },
+}
+
+impl UpdateTransport {
+    /// Set the proxy flags `PrfItem::from_url` reads.
+    /// `Core` goes through `PrfOption::proxy_port`, set once the node route is open
+    pub const fn apply(&self, option: &mut PrfOption) {
+        let (self_proxy, with_proxy) = match self {
+            Self::Direct | Self::Core { .. } => (false, false),
+            Self::SelfProxy => (true, false),
+            Self::SystemProxy => (false, true),
+        };
+        option.self_proxy = Some(self_proxy);
+        option.with_proxy = Some(with_proxy);
+    }
+
+    fn from_flags(option: &PrfOption) -> Self {
+        if option.self_proxy.unwrap_or(false) {
+            Self::SelfProxy
+        } else if option.with_proxy.unwrap_or(false) {
+            Self::SystemProxy
+        } else {
+            Self::Direct
+        }
+    }
+}
+
+impl FromStr for UpdateTransport {
+    type Err = anyhow::Error;
+
+    /// `direct`, `self_proxy`, `system_proxy`, `core:<group>` or `core:<group>/<node>`
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim() {
+            "direct" => Ok(Self::Direct),
+            "self_proxy" => Ok(Self::SelfProxy),
+            "system_proxy" => Ok(Self::SystemProxy),
+            other => {
+                let target = other
+                    .strip_prefix("core:")
+                    .ok_or_else(|| anyhow!("unknown update transport \"{other}\""))?;
+                let (group, node) = match target.split_once('/') {
+                    Some((group, node)) => (group, Some(node.into())),
+                    None => (target, None),
+                };
+                if group.is_empty() {
+                    bail!("missing proxy group in update transport \"{other}\"");
+                }
+                Ok(Self::Core {
+                    group: group.into(),
+                    node,
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Display for UpdateTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Direct => write!(f, "direct"),
+            Self::SelfProxy => write!(f, "self_proxy"),
+            Self::SystemProxy => write!(f, "system_proxy"),
+            Self::Core {
+                group,
+                node: Some(node),
+            } => write!(f, "core:{group}/{node}"),
+            Self::Core { group, node: None } => write!(f, "core:{group}"),
+        }
+    }
+}
+
+/// Retries and transports used to update a subscription
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdatePolicy {
+    /// tried in order until one succeeds
+    pub transports: Vec<UpdateTransport>,
+    /// extra attempts per transport
+    pub retries: u32,
+    /// failed updates in a row before the subscription is marked stale
+    pub stale_after: u32,
+    pub timeout_secs: u64,
+}
+
+impl UpdatePolicy {
+    /// Without `update_transports` the configured proxy flags are tried first, then the core and the system proxy
+    pub fn from_option(option: Option<&PrfOption>) -> Result<Self> {
+        let default = PrfOption::default();
+        let option = option.unwrap_or(&default);
+
+        let mut transports = match option.update_transports.as_ref() {
+            Some(list) if !list.is_empty() => list
+                .iter()
+                .map(|value| value.parse())
+                .collect::<Result<Vec<UpdateTransport>>>()?,
+            _ => vec![
+                UpdateTransport::from_flags(option),
+                UpdateTransport::SelfProxy,
+                UpdateTransport::SystemProxy,
+            ],
+        };
+        let mut seen = vec![];
+        transports.retain(|transport| {
+            let duplicate = seen.contains(transport);
+            seen.push(transport.clone());
+            !duplicate
+        });
+
+        Ok(Self {
+            transports,
+            retries: option.update_retries.unwrap_or(DEFAULT_RETRIES),
+            stale_after: option
+                .stale_after_failures
+                .unwrap_or(DEFAULT_STALE_AFTER)
+                .max(1),
+            timeout_secs: option.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECS),
+        })
+    }
+
+    /// Exponential backoff with jitter between the attempts of one transport
+    pub fn backoff(&self) -> ExponentialBackoff {
+        ExponentialBackoff {
+            initial_interval: INITIAL_BACKOFF,
+            randomization_factor: RANDOMIZATION_FACTOR,
+            multiplier: 2.0,
+            max_interval: MAX_BACKOFF,
+            max_elapsed_time: None,
+            ..Default::default()
+        }
+    }
+
+    /// Upper bound of a whole update, every attempt timing out and every backoff at its maximum
+    pub fn max_duration(&self) -> Duration {
+        let transports = u32::try_from(self.transports.len()).unwrap_or(u32::MAX);
+        let attempts = transports.saturating_mul(self.retries.saturating_add(1));
+        let waits = transports.saturating_mul(self.retries);
+        let longest_wait = MAX_BACKOFF.mul_f64(1.0 + RANDOMIZATION_FACTOR);
+        Duration::from_secs(self.timeout_secs).saturating_mul(attempts)
+            + longest_wait.saturating_mul(waits)
+            // 写入配置和 diff 的时间
+            + Duration::from_secs(20)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_policy() {
+        let policy = UpdatePolicy::from_option(None).unwrap();
+        assert_eq!(
+            policy.transports,
+            vec![
+                UpdateTransport::Direct,
+                UpdateTransport::SelfProxy,
+                UpdateTransport::SystemProxy
+            ]
+        );
+
+        let option = PrfOption {
+            update_transports: Some(vec![
+                "core:Proxy/HK 01".into(),
+                "system_proxy".into(),
+                "system_proxy".into(),
+            ]),
+            update_retries: Some(0),
+            timeout_seconds: Some(10),
+            ..PrfOption::default()
+        };
+        let policy = UpdatePolicy::from_option(Some(&option)).unwrap();
+        assert_eq!(
+            policy.transports,
+            vec![
+                UpdateTransport::Core {
+                    group: "Proxy".into(),
+                    node: Some("HK 01".into()),
+                },
+                UpdateTransport::SystemProxy,
+            ]
+        );
+        assert_eq!(policy.transports[0].to_string(), "core:Proxy/HK 01");
+        assert_eq!(policy.max_duration(), Duration::from_secs(40));
+
+        let option = PrfOption {
+            update_transports: Some(vec!["carrier pigeon".into()]),
+            ..PrfOption::default()
+        };
+        assert!(UpdatePolicy::from_option(Some(&option)).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use crate::config::PrfOption;
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Duration, Local, NaiveTime, TimeZone as _};
+use cron::Schedule;
+use std::str::FromStr as _;
+
+/// Upper bound of candidates skipped while looking for a run outside the blackout window
+const MAX_SKIPPED_RUNS: usize = 1000;
+
+/// When a profile is updated automatically
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpdateSchedule {
+    pub kind: ScheduleKind,
+    /// daily window in which no scheduled update runs
+    pub blackout: Option<Blackout>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleKind {
+    /// every N minutes after the last run
+    Interval(u64),
+    Cron(Box<Schedule>),
+}
+
+/// A daily `start-end` time window, wrapping midnight when `end` is before `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Blackout {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl UpdateSchedule {
+    pub const fn interval(minutes: u64) -> Self {
+        Self {
+            kind: ScheduleKind::Interval(minutes),
+            blackout: None,
+        }
+    }
+
+    /// The schedule configured in `option`, `update_cron` takes precedence over `update_interval`.
+    /// `None` when the profile is not updated automatically
+    pub fn from_option(option: &PrfOption) -> Option<Result<Self>> {
+        if !option.allow_auto_update.unwrap_or_default() {
+            return None;
+        }
+        let kind = match (option.update_cron.as_deref(), option.update_interval) {
+            (Some(expr), _) if !expr.trim().is_empty() => {
+                parse_cron(expr).map(Box::new).map(ScheduleKind::Cron)
+            }
+            (_, Some(minutes)) if minutes > 0 => Ok(ScheduleKind::Interval(minutes)),
+            _ => return None,
+        };
+        let blackout = option
+            .update_blackout
+            .as_deref()
+            .filter(|window| !window.trim().is_empty())
+            .map(Blackout::from_str)
+            .transpose();
+        Some(kind.and_then(|kind| {
+            Ok(Self {
+                kind,
+                blackout: blackout?,
+            })
+        }))
+    }
+
+    /// The first run after `after`, moved out of the blackout window
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut next = self.raw_next(after)?;
+        for _ in 0..MAX_SKIPPED_RUNS {
+            let Some(blackout) = self.blackout.filter(|blackout| blackout.contains(next)) else {
+                return Some(next);
+            };
+            let end = blackout.end_after(next)?;
+            next = match &self.kind {
+                // 间隔任务顺延到窗口结束
+                ScheduleKind::Interval(_) => end,
+                ScheduleKind::Cron(_) => self.raw_next(end - Duration::seconds(1))?,
+            };
+        }
+        None
+    }
+
+    /// Whether a due run has to wait because `now` is inside the blackout window
+    pub fn is_blocked(&self, now: DateTime<Local>) -> bool {
+        self.blackout.is_some_and(|blackout| blackout.contains(now))
+    }
+
+    /// Where a run that became due inside the blackout window is moved to
+    pub fn resume_after(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self.blackout {
+            Some(blackout) if blackout.contains(now) => blackout.end_after(now),
+            _ => Some(now),
+        }
+    }
+
+    fn raw_next(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        match &self.kind {
+            ScheduleKind::Interval(minutes) => {
+                Some(after + Duration::minutes(i64::try_from(*minutes).ok()?))
+            }
+            ScheduleKind::Cron(schedule) => schedule.after(&after).next(),
+        }
+    }
+}
+
+/// Accepts standard 5 field cron, cron with seconds, `@daily` style shorthands and `HH:MM daily`.
+/// Expressions with seconds are passed to the cron crate as they are, weekdays included
+fn parse_cron(expr: &str) -> Result<Schedule> {
+    let expr = expr.trim();
+    let normalized = if expr.starts_with('@') {
+        expr.to_owned()
+    } else if let Some(time) = expr.strip_suffix("daily").map(str::trim) {
+        let time = NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| anyhow!("invalid daily time \"{time}\""))?;
+        format!("0 {} {} * * *", time.format("%-M"), time.format("%-H"))
+    } else {
+        let mut fields: Vec<std::string::String> =
+            expr.split_whitespace().map(str::to_owned).collect();
+        if fields.len() == 5 {
+            fields[4] = translate_weekdays(&fields[4])
+                .map_err(|e| anyhow!("invalid cron expression \"{expr}\": {e}"))?;
+            fields.insert(0, "0".into());
+        }
+        fields.join(" ")
+    };
+    Schedule::from_str(&normalized).map_err(|e| anyhow!("invalid cron expression \"{expr}\": {e}"))
+}
+
+/// Convert the numeric weekdays of standard cron, `0-7` with `0` and `7` for Sunday,
+/// to the `1-7` from Sunday used by the cron crate. Names like `MON` are kept
+fn translate_weekdays(field: &str) -> Result<std::string::String> {
+    let number = |day: &str| -> Result<Option<u8>> {
+        if !day.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(None);
+        }
+        match day.parse::<u8>() {
+            Ok(day @ 0..=7) => Ok(Some(day)),
+            _ => bail!("invalid weekday \"{day}\", expected 0-7"),
+        }
+    };
+
+    let mut parts = Vec::new();
+    for part in field.split(',') {
+        let (days, step) = match part.split_once('/') {
+            Some((days, step)) => (days, Some(step)),
+            None => (part, None),
+        };
+        let days = match days.split_once('-') {
+            Some((start, end)) => match (number(start)?, number(end)?) {
+                (Some(start), Some(end)) if start > end => {
+                    bail!("invalid weekday range \"{days}\"")
+                }
+                (Some(0), Some(7)) => "1-7".to_owned(),
+                (Some(7), Some(7)) if step.is_none() => "1".to_owned(),
+                // `5-7` ends on Sunday, which comes first in the cron crate
+                (Some(start), Some(7)) if step.is_none() => format!("{}-7,1", start + 1),
+                (Some(_), Some(7)) => bail!("unsupported weekday range \"{part}\""),
+                (Some(start), Some(end)) => format!("{}-{}", start + 1, end + 1),
+                _ => days.to_owned(),
+            },
+            None => match number(days)? {
+                Some(day) => (day % 7 + 1).to_string(),
+                None => days.to_owned(),
+            },
+        };
+        parts.push(match step {
+            Some(step) => format!("{days}/{step}"),
+            None => days,
+        });
+    }
+    Ok(parts.join(","))
+}
+
+impl Blackout {
+    fn contains(self, time: DateTime<Local>) -> bool {
+        let time = time.time();
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// The first end of the window at or after `time`
+    fn end_after(self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut date = time.date_naive();
+        if time.time() >= self.end {
+            date = date.succ_opt()?;
+        }
+        Local
+            .from_local_datetime(&date.and_time(self.end))
+            .earliest()
+    }
+}
+
+impl std::str::FromStr for Blackout {
+    type Err = anyhow::Error;
+
+    /// `HH:MM-HH:MM`
+    fn from_str(window: &str) -> Result<Self> {
+        let Some((start, end)) = window.split_once('-') else {
+            bail!("invalid time window \"{window}\", expected HH:MM-HH:MM");
+        };
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .map_err(|_| anyhow!("invalid time window \"{window}\", expected HH:MM-HH:MM"))
+        };
+        let (start, end) = (parse(start)?, parse(end)?);
+        if start == end {
+            bail!("empty time window \"{window}\"");
+        }
+        Ok(Self { start, end })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(clippy::unwrap_used)]
+    fn at(time: &str) -> DateTime<Local> {
+        let naive = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        Local.from_local_datetime(&naive).earliest().unwrap()
+    }
+
+    #[allow(clippy::unwrap_used)]
+    fn schedule(
+        cron: Option<&str>,
+        interval: Option<u64>,
+        blackout: Option<&str>,
+    ) -> UpdateSchedule {
+        let option = PrfOption {
+            allow_auto_update: Some(true),
+            update_cron: cron.map(Into::into),
+            update_interval: interval,
+            update_blackout: blackout.map(Into::into),
+            ..PrfOption::default()
+        };
+        UpdateSchedule::from_option(&option).unwrap().unwrap()
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_next_after() {
+        let daily = schedule(Some("03:00 daily"), Some(60), None);
+        assert_eq!(daily, schedule(Some("0 3 * * *"), None, None));
+        let hourly = schedule(Some("@hourly"), None, None);
+        assert_eq!(
+            hourly.next_after(at("2025-03-10 04:10")),
+            Some(at("2025-03-10 05:00"))
+        );
+        assert_eq!(
+            daily.next_after(at("2025-03-10 04:00")),
+            Some(at("2025-03-11 03:00"))
+        );
+        let cron = schedule(Some("30 */6 * * *"), None, None);
+        assert_eq!(
+            cron.next_after(at("2025-03-10 06:30")),
+            Some(at("2025-03-10 12:30"))
+        );
+
+        // 每 6 小时，09:00-18:00 之间不更新
+        let every_6h = schedule(None, Some(360), Some("09:00-18:00"));
+        assert_eq!(
+            every_6h.next_after(at("2025-03-10 02:00")),
+            Some(at("2025-03-10 08:00"))
+        );
+        assert_eq!(
+            every_6h.next_after(at("2025-03-10 08:00")),
+            Some(at("2025-03-10 18:00"))
+        );
+        assert!(every_6h.is_blocked(at("2025-03-10 12:00")));
+        assert_eq!(
+            every_6h.resume_after(at("2025-03-10 12:00")),
+            Some(at("2025-03-10 18:00"))
+        );
+
+        let nightly = schedule(Some("0 * * * *"), None, Some("22:00-07:00"));
+        assert_eq!(
+            nightly.next_after(at("2025-03-10 21:30")),
+            Some(at("2025-03-11 07:00"))
+        );
+
+        // 标准 cron 的星期从 0 (周日) 开始，2025-03-10 是周一
+        let weekdays = schedule(Some("0 9 * * 1-5"), None, None);
+        assert_eq!(
+            weekdays.next_after(at("2025-03-08 10:00")),
+            Some(at("2025-03-10 09:00"))
+        );
+        let sunday = schedule(Some("0 9 * * 0"), None, None);
+        assert_eq!(sunday, schedule(Some("0 9 * * 7"), None, None));
+        assert_eq!(sunday, schedule(Some("0 9 * * 7-7"), None, None));
+        // 带秒的表达式使用 cron crate 的星期，1 为周日
+        assert_eq!(sunday, schedule(Some("0 0 9 * * 1"), None, None));
+        for sunday in [sunday, schedule(Some("0 9 * * SUN"), None, None)] {
+            assert_eq!(
+                sunday.next_after(at("2025-03-10 10:00")),
+                Some(at("2025-03-16 09:00"))
+            );
+        }
+        let weekend = schedule(Some("0 9 * * 6-7"), None, None);
+        assert_eq!(
+            weekend.next_after(at("2025-03-15 10:00")),
+            Some(at("2025-03-16 09:00"))
+        );
+
+        let option = PrfOption {
+            allow_auto_update: Some(true),
+            update_cron: Some("not a cron".into()),
+            ..PrfOption::default()
+        };
+        assert!(UpdateSchedule::from_option(&option).unwrap().is_err());
+        assert!(UpdateSchedule::from_option(&PrfOption::default()).is_none());
+    }
+}
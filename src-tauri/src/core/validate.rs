@@ -6,7 +6,11 @@ use tauri_plugin_shell::ShellExt as _;
 use tokio::fs;
 
 use crate::config::{Config, ConfigType};
-use crate::core::handle;
+use crate::constants;
+use crate::core::{
+    config_check, handle,
+    validation_report::{ValidationIssue, ValidationReport, ValidationSource},
+};
 use crate::singleton_lazy;
 use crate::utils::dirs;
 use crate::{logging, utils::logging::Type};
@@ -112,16 +116,20 @@ impl CoreConfigValidator {
     }
 
     /// 只进行文件语法检查，不进行完整验证
-    async fn validate_file_syntax(config_path: &str) -> Result<(bool, String)> {
+    async fn validate_file_syntax(
+        config_path: &str,
+        source: ValidationSource,
+    ) -> Result<ValidationReport> {
         logging!(info, Type::Validate, "开始检查文件: {}", config_path);
+        let report = ValidationReport::new(source, Some(config_path.into()));
 
         // 读取文件内容
         let content = match fs::read_to_string(config_path).await {
             Ok(content) => content,
             Err(err) => {
-                let error_msg = format!("Failed to read file: {err}").into();
+                let error_msg = format!("Failed to read file: {err}");
                 logging!(error, Type::Validate, "无法读取文件: {}", error_msg);
-                return Ok((false, error_msg));
+                return Ok(report.with_issue(ValidationIssue::error("yaml_read_error", error_msg)));
             }
         };
         // 对YAML文件尝试解析，只检查语法正确性
@@ -129,27 +137,31 @@ impl CoreConfigValidator {
         match serde_yaml_ng::from_str::<serde_yaml_ng::Value>(&content) {
             Ok(_) => {
                 logging!(info, Type::Validate, "YAML语法检查通过");
-                Ok((true, String::new()))
+                Ok(report)
             }
             Err(err) => {
-                // 使用标准化的前缀，以便错误处理函数能正确识别
-                let error_msg = format!("YAML syntax error: {err}").into();
-                logging!(error, Type::Validate, "YAML语法错误: {}", error_msg);
-                Ok((false, error_msg))
+                logging!(error, Type::Validate, "YAML语法错误: {}", err);
+                Ok(ValidationReport::from_yaml_error(
+                    source,
+                    Some(config_path.into()),
+                    &err,
+                ))
             }
         }
     }
 
     /// 验证脚本文件语法
-    async fn validate_script_file(path: &str) -> Result<(bool, String)> {
+    async fn validate_script_file(path: &str) -> Result<ValidationReport> {
+        let report = ValidationReport::new(ValidationSource::Script, Some(path.into()));
+
         // 读取脚本内容
         let content = match fs::read_to_string(path).await {
             Ok(content) => content,
             Err(err) => {
-                let error_msg = format!("Failed to read script file: {err}").into();
+                let error_msg = format!("Failed to read script file: {err}");
                 logging!(warn, Type::Validate, "脚本语法错误: {}", err);
                 //handle::Handle::notice_message("config_validate::script_syntax_error", &error_msg);
-                return Ok((false, error_msg));
+                return Ok(report.with_issue(ValidationIssue::error("script_error", error_msg)));
             }
         };
 
@@ -173,16 +185,20 @@ impl CoreConfigValidator {
                     let error_msg = "Script must contain a main function";
                     logging!(warn, Type::Validate, "脚本缺少main函数: {}", path);
                     //handle::Handle::notice_message("config_validate::script_missing_main", error_msg);
-                    return Ok((false, error_msg.into()));
+                    return Ok(
+                        report.with_issue(ValidationIssue::error("script_missing_main", error_msg))
+                    );
                 }
 
-                Ok((true, String::new()))
+                Ok(report)
             }
             Err(err) => {
-                let error_msg = format!("Script syntax error: {err}").into();
                 logging!(warn, Type::Validate, "脚本语法错误: {}", err);
                 //handle::Handle::notice_message("config_validate::script_syntax_error", &error_msg);
-                Ok((false, error_msg))
+                Ok(ValidationReport::from_script_error(
+                    Some(path.into()),
+                    &err.to_string(),
+                ))
             }
         }
     }
@@ -191,18 +207,30 @@ impl CoreConfigValidator {
     pub async fn validate_config_file(
         config_path: &str,
         is_merge_file: Option<bool>,
-    ) -> Result<(bool, String)> {
+    ) -> Result<ValidationReport> {
+        let is_dns_config = std::path::Path::new(config_path)
+            .file_name()
+            .is_some_and(|name| name == constants::files::DNS_CONFIG);
+        let source = if is_merge_file.unwrap_or(false) {
+            ValidationSource::Merge
+        } else if is_dns_config {
+            ValidationSource::DnsConfig
+        } else {
+            ValidationSource::Profile
+        };
+
         // 检查程序是否正在退出，如果是则跳过验证
         if handle::Handle::global().is_exiting() {
             logging!(info, Type::Core, "应用正在退出，跳过验证");
-            return Ok((true, String::new()));
+            return Ok(ValidationReport::new(source, Some(config_path.into())));
         }
 
         // 检查文件是否存在
         if !std::path::Path::new(config_path).exists() {
-            let error_msg = format!("File not found: {config_path}").into();
+            let error_msg = format!("File not found: {config_path}");
             //handle::Handle::notice_message("config_validate::file_not_found", &error_msg);
-            return Ok((false, error_msg));
+            return Ok(ValidationReport::new(source, Some(config_path.into()))
+                .with_issue(ValidationIssue::error("file_not_found", error_msg)));
         }
 
         // 如果是合并文件且不是强制验证，执行语法检查但不进行完整验证
@@ -213,7 +241,7 @@ impl CoreConfigValidator {
                 "检测到Merge文件，仅进行语法检查: {}",
                 config_path
             );
-            return Self::validate_file_syntax(config_path).await;
+            return Self::validate_file_syntax(config_path, source).await;
         }
 
         // 检查是否为脚本文件
@@ -231,7 +259,7 @@ impl CoreConfigValidator {
                         config_path,
                         err
                     );
-                    return Self::validate_config_internal(config_path).await;
+                    return Self::validate_config_internal(config_path, source).await;
                 }
             }
         };
@@ -253,19 +281,38 @@ impl CoreConfigValidator {
             "使用Clash内核验证配置文件: {}",
             config_path
         );
-        Self::validate_config_internal(config_path).await
+        Self::validate_config_internal(config_path, source).await
     }
 
     /// 内部验证配置文件的实现
-    async fn validate_config_internal(config_path: &str) -> Result<(bool, String)> {
+    async fn validate_config_internal(
+        config_path: &str,
+        source: ValidationSource,
+    ) -> Result<ValidationReport> {
+        let report = ValidationReport::new(source, Some(config_path.into()));
+
         // 检查程序是否正在退出，如果是则跳过验证
         if handle::Handle::global().is_exiting() {
             logging!(info, Type::Validate, "应用正在退出，跳过验证");
-            return Ok((true, String::new()));
+            return Ok(report);
         }
 
         logging!(info, Type::Validate, "开始验证配置文件: {}", config_path);
 
+        // 先进行内置的语义检查，不依赖内核
+        let issues = match Self::check_config_semantics(config_path, source).await {
+            Ok(issues) => issues,
+            Err(report) => {
+                logging!(
+                    info,
+                    Type::Validate,
+                    "YAML 语法检查失败:\n{}",
+                    report.error_message()
+                );
+                return Ok(report);
+            }
+        };
+
         let clash_core = Config::verge().await.latest_arc().get_valid_clash_core();
         logging!(info, Type::Validate, "使用内核: {}", clash_core);
 
@@ -274,15 +321,32 @@ impl CoreConfigValidator {
         let app_dir_str = dirs::path_to_str(&app_dir)?;
         logging!(info, Type::Validate, "验证目录: {}", app_dir_str);
 
-        // 使用子进程运行clash验证配置
-        let command = app_handle.shell().sidecar(clash_core.as_str())?.args([
-            "-t",
-            "-d",
-            app_dir_str,
-            "-f",
-            config_path,
-        ]);
-        let output = command.output().await?;
+        // 使用子进程运行clash验证配置，内核不可用时仅使用内置检查的结果
+        let output = match app_handle.shell().sidecar(clash_core.as_str()) {
+            Ok(command) => {
+                command
+                    .args(["-t", "-d", app_dir_str, "-f", config_path])
+                    .output()
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+        let output = match output {
+            Ok(output) => output,
+            Err(err) => {
+                logging!(
+                    warn,
+                    Type::Validate,
+                    "Warning: 无法运行内核验证，仅使用内置检查结果: {}",
+                    err
+                );
+                return Ok(ValidationReport::from_config_issues(
+                    source,
+                    Some(config_path.into()),
+                    issues,
+                ));
+            }
+        };
 
         let status = &output.status;
         let stderr = &output.stderr;
@@ -311,19 +375,39 @@ impl CoreConfigValidator {
             };
 
             logging!(info, Type::Validate, "-------- 验证结束 --------");
-            Ok((false, error_msg)) // 返回错误消息给调用者处理
+            // 返回错误给调用者处理
+            Ok(
+                ValidationReport::from_core_output(source, Some(config_path.into()), &error_msg)
+                    .with_warnings(issues),
+            )
         } else {
             logging!(info, Type::Validate, "验证成功");
             logging!(info, Type::Validate, "-------- 验证结束 --------");
-            Ok((true, String::new()))
+            Ok(report.with_warnings(issues))
         }
     }
 
+    /// 内置的语法和语义检查，YAML 语法错误时返回失败的报告
+    async fn check_config_semantics(
+        config_path: &str,
+        source: ValidationSource,
+    ) -> Result<Vec<config_check::ConfigIssue>, ValidationReport> {
+        let Ok(content) = fs::read_to_string(config_path).await else {
+            return Ok(vec![]);
+        };
+        // 内核可能无法运行，YAML 语法错误需要在这里报告
+        let config =
+            serde_yaml_ng::from_str::<serde_yaml_ng::Mapping>(&content).map_err(|err| {
+                ValidationReport::from_yaml_error(source, Some(config_path.into()), &err)
+            })?;
+        Ok(config_check::check_config(&config))
+    }
+
     /// 验证运行时配置
-    pub async fn validate_config(&self) -> Result<(bool, String)> {
+    pub async fn validate_config(&self) -> Result<ValidationReport> {
         if !self.try_start() {
             logging!(info, Type::Validate, "验证已在进行中，跳过新的验证请求");
-            return Ok((true, String::new()));
+            return Ok(ValidationReport::new(ValidationSource::Runtime, None));
         }
         defer! {
             self.finish();
@@ -332,7 +416,7 @@ impl CoreConfigValidator {
 
         let config_path = Config::generate_file(ConfigType::Check).await?;
         let config_path = dirs::path_to_str(&config_path)?;
-        Self::validate_config_internal(config_path).await
+        Self::validate_config_internal(config_path, ValidationSource::Runtime).await
     }
 }
 
@@ -0,0 +1,303 @@
+use crate::core::config_check::ConfigIssue;
+use regex::Regex;
+use serde::Serialize;
+use smartstring::alias::String;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// which kind of file was validated
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationSource {
+    Profile,
+    Merge,
+    Script,
+    DnsConfig,
+    /// the generated runtime config
+    Runtime,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// machine readable kind, e.g. `yaml_syntax_error`, `core_fatal`
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// key path like `proxy-groups[1].proxies[0]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+impl ValidationIssue {
+    pub fn error(code: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: code.into(),
+            message: message.into(),
+            line: None,
+            column: None,
+            path: None,
+        }
+    }
+
+    pub const fn at(mut self, line: Option<usize>, column: Option<usize>) -> Self {
+        self.line = line;
+        self.column = column;
+        self
+    }
+
+    /// `message (line 3, column 5)`, without the position when the message already has it
+    pub fn describe(&self) -> String {
+        let mut text = std::string::String::new();
+        if let Some(path) = &self.path {
+            text.push_str(&format!("{path}: "));
+        }
+        text.push_str(&self.message);
+        if self
+            .line
+            .is_some_and(|line| mentions_line(&self.message, line))
+        {
+            return text.into();
+        }
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                text.push_str(&format!(" (line {line}, column {column})"))
+            }
+            (Some(line), None) => text.push_str(&format!(" (line {line})")),
+            _ => {}
+        }
+        text.into()
+    }
+}
+
+/// whether `message` names `line`, e.g. `yaml: line 12:` or `at line 3, col 5`
+fn mentions_line(message: &str, line: usize) -> bool {
+    let message = message.to_ascii_lowercase();
+    let needle = format!("line {line}");
+    message.match_indices(&needle).any(|(index, _)| {
+        !message[index + needle.len()..].starts_with(|c: char| c.is_ascii_digit())
+    })
+}
+
+impl From<ConfigIssue> for ValidationIssue {
+    fn from(issue: ConfigIssue) -> Self {
+        Self {
+            path: Some(issue.path),
+            ..Self::error(issue.code, issue.message)
+        }
+    }
+}
+
+/// Result of validating a config file
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub source: ValidationSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub const fn new(source: ValidationSource, file: Option<String>) -> Self {
+        Self {
+            source,
+            file,
+            issues: vec![],
+        }
+    }
+
+    pub fn with_issue(mut self, issue: ValidationIssue) -> Self {
+        self.issues.push(issue);
+        self
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+
+    pub fn first_error(&self) -> Option<&ValidationIssue> {
+        self.issues.iter().find(|i| i.severity == Severity::Error)
+    }
+
+    /// all errors, one per line
+    pub fn error_message(&self) -> String {
+        self.issues
+            .iter()
+            .filter(|i| i.severity == Severity::Error)
+            .map(|i| i.describe())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into()
+    }
+
+    /// the `(is_valid, error_message)` pair of the older validator API
+    pub fn to_legacy(&self) -> (bool, String) {
+        (self.is_valid(), self.error_message())
+    }
+
+    pub fn from_yaml_error(
+        source: ValidationSource,
+        file: Option<String>,
+        err: &serde_yaml_ng::Error,
+    ) -> Self {
+        let message = err.to_string();
+        let code = if message.contains("mapping values are not allowed") {
+            "yaml_mapping_error"
+        } else if message.contains("did not find expected key") {
+            "yaml_key_error"
+        } else {
+            "yaml_syntax_error"
+        };
+        let location = err.location();
+        let issue = ValidationIssue::error(code, format!("YAML syntax error: {message}")).at(
+            location.as_ref().map(|l| l.line()),
+            location.as_ref().map(|l| l.column()),
+        );
+        Self::new(source, file).with_issue(issue)
+    }
+
+    /// script errors from boa, which put the position as `line 3, col 5`
+    pub fn from_script_error(file: Option<String>, message: &str) -> Self {
+        let (line, column) = parse_position(message);
+        let issue = ValidationIssue::error(
+            "script_syntax_error",
+            format!("Script syntax error: {message}"),
+        )
+        .at(line, column);
+        Self::new(ValidationSource::Script, file).with_issue(issue)
+    }
+
+    pub fn from_config_issues(
+        source: ValidationSource,
+        file: Option<String>,
+        issues: Vec<ConfigIssue>,
+    ) -> Self {
+        Self {
+            issues: issues.into_iter().map(Into::into).collect(),
+            ..Self::new(source, file)
+        }
+    }
+
+    /// Add findings of the built-in checks that the core has the final say on
+    pub fn with_warnings(mut self, issues: Vec<ConfigIssue>) -> Self {
+        self.issues
+            .extend(issues.into_iter().map(|issue| ValidationIssue {
+                severity: Severity::Warning,
+                ..issue.into()
+            }));
+        self
+    }
+
+    /// Parse the output of `mihomo -t`, keeping the `level=fatal` / `level=error` messages.
+    /// Falls back to the whole output when nothing can be recognized.
+    pub fn from_core_output(source: ValidationSource, file: Option<String>, output: &str) -> Self {
+        let mut report = Self::new(source, file);
+        let msg_re = Regex::new(r#"msg="((?:[^"\\]|\\.)*)""#).ok();
+        for line in output.lines() {
+            let fatal = line.contains("level=fatal") || line.contains("FATA");
+            if !fatal && !line.contains("level=error") {
+                continue;
+            }
+            let message = msg_re
+                .as_ref()
+                .and_then(|re| re.captures(line))
+                .map(|cap| cap[1].replace("\\\"", "\""))
+                .unwrap_or_else(|| line.trim().to_owned());
+            let (line_no, column) = parse_position(&message);
+            let code = if fatal { "core_fatal" } else { "core_error" };
+            report
+                .issues
+                .push(ValidationIssue::error(code, message).at(line_no, column));
+        }
+        if report.issues.is_empty() {
+            let message = output.trim();
+            let message = if message.is_empty() {
+                "Core validation failed"
+            } else {
+                message
+            };
+            report
+                .issues
+                .push(ValidationIssue::error("core_fatal", message));
+        }
+        report
+    }
+}
+
+/// find `line N` and `col N` / `column N` in an error message
+fn parse_position(message: &str) -> (Option<usize>, Option<usize>) {
+    let Ok(re) = Regex::new(r"line (\d+)(?:[:,]?\s*col(?:umn)? (\d+))?") else {
+        return (None, None);
+    };
+    re.captures(message).map_or((None, None), |cap| {
+        (
+            cap.get(1).and_then(|m| m.as_str().parse().ok()),
+            cap.get(2).and_then(|m| m.as_str().parse().ok()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_reports() {
+        let err = serde_yaml_ng::from_str::<serde_yaml_ng::Value>("a: 1\nb: [1, 2\n")
+            .expect_err("invalid yaml should fail");
+        let report = ValidationReport::from_yaml_error(ValidationSource::Merge, None, &err);
+        assert!(!report.is_valid());
+        let issue = report.first_error().expect("should have an error");
+        assert!(issue.line.is_some());
+
+        let output = r#"time="2025-01-01T00:00:00Z" level=info msg="Start initial configuration in progress"
+time="2025-01-01T00:00:00Z" level=fatal msg="Parse config error: yaml: line 12: did not find expected key""#;
+        let report = ValidationReport::from_core_output(ValidationSource::Runtime, None, output);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].code, "core_fatal");
+        assert_eq!(report.issues[0].line, Some(12));
+        assert_eq!(
+            report.issues[0].message,
+            "Parse config error: yaml: line 12: did not find expected key"
+        );
+        assert_eq!(
+            ValidationIssue::error("core_error", "yaml: line 12: bad")
+                .at(Some(1), None)
+                .describe(),
+            "yaml: line 12: bad (line 1)"
+        );
+
+        let issue = ConfigIssue {
+            code: "unknown_policy",
+            path: "rules[0]".into(),
+            message: "unknown policy \"x\"".into(),
+        };
+        let report =
+            ValidationReport::new(ValidationSource::Runtime, None).with_warnings(vec![issue]);
+        assert!(report.is_valid());
+        assert_eq!(report.issues[0].severity, Severity::Warning);
+
+        let report = ValidationReport::from_script_error(None, "unexpected token at line 3, col 5");
+        assert_eq!(
+            (report.issues[0].line, report.issues[0].column),
+            (Some(3), Some(5))
+        );
+        assert_eq!(
+            report.to_legacy(),
+            (
+                false,
+                "Script syntax error: unexpected token at line 3, col 5".into()
+            )
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use super::SeqMap;
+use super::{ProviderMap, SeqMap};
 use crate::{
     config::PrfItem,
     utils::{dirs, help},
@@ -20,6 +20,8 @@ pub enum ChainType {
     Rules(SeqMap),
     Proxies(SeqMap),
     Groups(SeqMap),
+    RuleProviders(ProviderMap),
+    ProxyProviders(ProviderMap),
 }
 
 #[derive(Debug, Clone)]
@@ -110,6 +112,20 @@ impl AsyncChainItemFrom for Option<ChainItem> {
                     data: ChainType::Groups(seq_map),
                 })
             }
+            "rule-providers" => {
+                let provider_map = help::read_yaml::<ProviderMap>(&path).await.ok()?;
+                Some(ChainItem {
+                    uid,
+                    data: ChainType::RuleProviders(provider_map),
+                })
+            }
+            "proxy-providers" => {
+                let provider_map = help::read_yaml::<ProviderMap>(&path).await.ok()?;
+                Some(ChainItem {
+                    uid,
+                    data: ChainType::ProxyProviders(provider_map),
+                })
+            }
             _ => None,
         }
     }
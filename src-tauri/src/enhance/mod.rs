@@ -1,28 +1,81 @@
 mod chain;
 pub mod field;
 mod merge;
+mod node_route;
+mod provider;
 mod script;
 pub mod seq;
 mod tun;
 
+pub use self::script::{SCRIPT_WORKER_ARG, run_script_worker};
+
 use self::{
     chain::{AsyncChainItemFrom as _, ChainItem, ChainType},
     field::{use_keys, use_lowercase, use_sort},
     merge::use_merge,
-    script::use_script,
+    node_route::use_node_route,
+    provider::{ProviderMap, dangling_rule_sets, use_providers},
+    script::{ScriptEnv, ScriptLimits, use_builtin_script, use_script},
     seq::{SeqMap, use_seq},
     tun::use_tun,
 };
 use crate::constants;
+use crate::core::node_route::injected_port;
 use crate::utils::dirs;
 use crate::{config::Config, utils::tmpl};
 use crate::{logging, utils::logging::Type};
+use serde::Serialize;
 use serde_yaml_ng::Mapping;
 use smartstring::alias::String;
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 use tokio::fs;
 
 type ResultLog = Vec<(String, String)>;
+
+/// config snapshot after one stage of the enhance pipeline
+#[derive(Debug, Clone, Serialize)]
+pub struct EnhanceStage {
+    pub stage: String,
+    /// top-level keys added, removed or modified by this stage
+    pub changed_keys: Vec<String>,
+    pub config: Mapping,
+}
+
+/// Records every stage when running a dry-run
+#[derive(Debug, Default)]
+struct EnhanceTrace {
+    enabled: bool,
+    stages: Vec<EnhanceStage>,
+}
+
+impl EnhanceTrace {
+    fn record(&mut self, stage: &str, config: &Mapping) {
+        if !self.enabled {
+            return;
+        }
+        let previous = self.stages.last().map(|s| &s.config);
+        let mut changed_keys: Vec<String> = config
+            .iter()
+            .filter(|(key, value)| previous.and_then(|p| p.get(*key)) != Some(*value))
+            .chain(
+                previous
+                    .into_iter()
+                    .flat_map(Mapping::iter)
+                    .filter(|(key, _)| !config.contains_key(*key)),
+            )
+            .filter_map(|(key, _)| key.as_str().map(Into::into))
+            .collect();
+        changed_keys.sort();
+        self.stages.push(EnhanceStage {
+            stage: stage.into(),
+            changed_keys,
+            config: config.clone(),
+        });
+    }
+}
 #[derive(Debug)]
 struct ConfigValues {
     clash_config: Mapping,
@@ -32,6 +85,8 @@ struct ConfigValues {
     socks_enabled: bool,
     http_enabled: bool,
     enable_dns_settings: bool,
+    script_limits: ScriptLimits,
+    script_env: Arc<ScriptEnv>,
     #[cfg(not(target_os = "windows"))]
     redir_enabled: bool,
     #[cfg(target_os = "linux")]
@@ -46,6 +101,8 @@ struct ProfileItems {
     rules_item: ChainItem,
     proxies_item: ChainItem,
     groups_item: ChainItem,
+    rule_providers_item: ChainItem,
+    proxy_providers_item: ChainItem,
     global_merge: ChainItem,
     global_script: ChainItem,
     profile_name: String,
@@ -76,6 +133,14 @@ impl Default for ProfileItems {
                 uid: "".into(),
                 data: ChainType::Groups(SeqMap::default()),
             },
+            rule_providers_item: ChainItem {
+                uid: "".into(),
+                data: ChainType::RuleProviders(ProviderMap::default()),
+            },
+            proxy_providers_item: ChainItem {
+                uid: "".into(),
+                data: ChainType::ProxyProviders(ProviderMap::default()),
+            },
             global_merge: ChainItem {
                 uid: "Merge".into(),
                 data: ChainType::Merge(Mapping::new()),
@@ -91,7 +156,18 @@ impl Default for ProfileItems {
 async fn get_config_values() -> ConfigValues {
     let clash_config = { Config::clash().await.latest_arc().0.clone() };
 
-    let (clash_core, enable_tun, enable_builtin, socks_enabled, http_enabled, enable_dns_settings) = {
+    let (
+        clash_core,
+        enable_tun,
+        enable_builtin,
+        socks_enabled,
+        http_enabled,
+        enable_dns_settings,
+        script_limits,
+        script_env,
+    ) = {
+        let profiles = Config::profiles().await;
+        let profiles = profiles.latest_arc();
         let verge = Config::verge().await;
         let verge = verge.latest_arc();
         (
@@ -101,6 +177,8 @@ async fn get_config_values() -> ConfigValues {
             verge.verge_socks_enabled.unwrap_or(false),
             verge.verge_http_enabled.unwrap_or(false),
             verge.enable_dns_settings.unwrap_or(false),
+            ScriptLimits::from_verge(&verge),
+            Arc::new(ScriptEnv::new(&verge, &profiles)),
         )
     };
 
@@ -126,6 +204,8 @@ async fn get_config_values() -> ConfigValues {
         socks_enabled,
         http_enabled,
         enable_dns_settings,
+        script_limits,
+        script_env,
         #[cfg(not(target_os = "windows"))]
         redir_enabled,
         #[cfg(target_os = "linux")]
@@ -136,7 +216,17 @@ async fn get_config_values() -> ConfigValues {
 #[allow(clippy::cognitive_complexity)]
 async fn collect_profile_items() -> ProfileItems {
     // 从profiles里拿东西 - 先收集需要的数据，然后释放锁
-    let (current, merge_uid, script_uid, rules_uid, proxies_uid, groups_uid, name) = {
+    let (
+        current,
+        merge_uid,
+        script_uid,
+        rules_uid,
+        proxies_uid,
+        groups_uid,
+        rule_providers_uid,
+        proxy_providers_uid,
+        name,
+    ) = {
         let current = {
             let profiles = Config::profiles().await;
             let profiles_clone = profiles.latest_arc();
@@ -170,6 +260,12 @@ async fn collect_profile_items() -> ProfileItems {
         let groups_uid = current_item
             .current_groups()
             .unwrap_or_else(|| "Groups".into());
+        let rule_providers_uid = current_item
+            .current_rule_providers()
+            .unwrap_or_else(|| "RuleProviders".into());
+        let proxy_providers_uid = current_item
+            .current_proxy_providers()
+            .unwrap_or_else(|| "ProxyProviders".into());
 
         let name = profiles_ref
             .get_item(&current_profile_uid)
@@ -184,6 +280,8 @@ async fn collect_profile_items() -> ProfileItems {
             rules_uid,
             proxies_uid,
             groups_uid,
+            rule_providers_uid,
+            proxy_providers_uid,
             name,
         )
     };
@@ -274,6 +372,40 @@ async fn collect_profile_items() -> ProfileItems {
         data: ChainType::Groups(SeqMap::default()),
     });
 
+    let rule_providers_item = {
+        let item = {
+            let profiles = Config::profiles().await;
+            let profiles = profiles.latest_arc();
+            profiles.get_item(&rule_providers_uid).ok().cloned()
+        };
+        if let Some(item) = item {
+            <Option<ChainItem>>::from_async(&item).await
+        } else {
+            None
+        }
+    }
+    .unwrap_or_else(|| ChainItem {
+        uid: "".into(),
+        data: ChainType::RuleProviders(ProviderMap::default()),
+    });
+
+    let proxy_providers_item = {
+        let item = {
+            let profiles = Config::profiles().await;
+            let profiles = profiles.latest_arc();
+            profiles.get_item(&proxy_providers_uid).ok().cloned()
+        };
+        if let Some(item) = item {
+            <Option<ChainItem>>::from_async(&item).await
+        } else {
+            None
+        }
+    }
+    .unwrap_or_else(|| ChainItem {
+        uid: "".into(),
+        data: ChainType::ProxyProviders(ProviderMap::default()),
+    });
+
     let global_merge = {
         let item = {
             let profiles = Config::profiles().await;
@@ -315,29 +447,44 @@ async fn collect_profile_items() -> ProfileItems {
         rules_item,
         proxies_item,
         groups_item,
+        rule_providers_item,
+        proxy_providers_item,
         global_merge,
         global_script,
         profile_name: name,
     }
 }
 
-fn process_global_items(
+async fn process_global_items(
     mut config: Mapping,
     global_merge: ChainItem,
     global_script: ChainItem,
     profile_name: String,
+    script_limits: ScriptLimits,
+    script_env: &Arc<ScriptEnv>,
+    trace: &mut EnhanceTrace,
 ) -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
     let mut result_map = HashMap::new();
     let mut exists_keys = use_keys(&config);
+    trace.record("profile", &config);
 
     if let ChainType::Merge(merge) = global_merge.data {
         exists_keys.extend(use_keys(&merge));
         config = use_merge(merge, config.to_owned());
     }
+    trace.record("global_merge", &config);
 
     if let ChainType::Script(script) = global_script.data {
         let mut logs = vec![];
-        match use_script(script, config.to_owned(), profile_name) {
+        match use_script(
+            script,
+            config.to_owned(),
+            profile_name,
+            script_limits,
+            Arc::clone(script_env),
+        )
+        .await
+        {
             Ok((res_config, res_logs)) => {
                 exists_keys.extend(use_keys(&res_config));
                 config = res_config;
@@ -347,42 +494,70 @@ fn process_global_items(
         }
         result_map.insert(global_script.uid, logs);
     }
+    trace.record("global_script", &config);
 
     (config, exists_keys, result_map)
 }
 
 #[allow(clippy::too_many_arguments)]
-fn process_profile_items(
+async fn process_profile_items(
     mut config: Mapping,
     mut exists_keys: Vec<String>,
     mut result_map: HashMap<String, ResultLog>,
     rules_item: ChainItem,
     proxies_item: ChainItem,
     groups_item: ChainItem,
+    rule_providers_item: ChainItem,
+    proxy_providers_item: ChainItem,
     merge_item: ChainItem,
     script_item: ChainItem,
     profile_name: String,
+    script_limits: ScriptLimits,
+    script_env: &Arc<ScriptEnv>,
+    trace: &mut EnhanceTrace,
 ) -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
     if let ChainType::Rules(rules) = rules_item.data {
         config = use_seq(rules, config.to_owned(), "rules");
     }
+    trace.record("rules", &config);
 
     if let ChainType::Proxies(proxies) = proxies_item.data {
         config = use_seq(proxies, config.to_owned(), "proxies");
     }
+    trace.record("proxies", &config);
 
     if let ChainType::Groups(groups) = groups_item.data {
         config = use_seq(groups, config.to_owned(), "proxy-groups");
     }
+    trace.record("groups", &config);
+
+    if let ChainType::RuleProviders(providers) = rule_providers_item.data {
+        config = use_providers(providers, config.to_owned(), "rule-providers");
+    }
+    trace.record("rule_providers", &config);
+
+    if let ChainType::ProxyProviders(providers) = proxy_providers_item.data {
+        config = use_providers(providers, config.to_owned(), "proxy-providers");
+    }
+    trace.record("proxy_providers", &config);
 
     if let ChainType::Merge(merge) = merge_item.data {
         exists_keys.extend(use_keys(&merge));
         config = use_merge(merge, config.to_owned());
     }
+    trace.record("profile_merge", &config);
 
     if let ChainType::Script(script) = script_item.data {
         let mut logs = vec![];
-        match use_script(script, config.to_owned(), profile_name) {
+        match use_script(
+            script,
+            config.to_owned(),
+            profile_name,
+            script_limits,
+            Arc::clone(script_env),
+        )
+        .await
+        {
             Ok((res_config, res_logs)) => {
                 exists_keys.extend(use_keys(&res_config));
                 config = res_config;
@@ -392,6 +567,24 @@ fn process_profile_items(
         }
         result_map.insert(script_item.uid, logs);
     }
+    trace.record("profile_script", &config);
+
+    // 检查 RULE-SET 规则引用的 rule-providers 是否存在
+    let dangling = dangling_rule_sets(&config);
+    if !dangling.is_empty() {
+        let uid = if rule_providers_item.uid.is_empty() {
+            "RuleProviders".into()
+        } else {
+            rule_providers_item.uid
+        };
+        let logs = result_map.entry(uid).or_default();
+        for name in dangling {
+            logs.push((
+                "warn".into(),
+                format!("RULE-SET `{name}` refers to a missing rule-provider").into(),
+            ));
+        }
+    }
 
     (config, exists_keys, result_map)
 }
@@ -470,6 +663,8 @@ fn apply_builtin_scripts(
     mut config: Mapping,
     clash_core: Option<String>,
     enable_builtin: bool,
+    script_limits: ScriptLimits,
+    script_env: &Arc<ScriptEnv>,
 ) -> Mapping {
     if enable_builtin {
         ChainItem::builtin()
@@ -479,7 +674,13 @@ fn apply_builtin_scripts(
             .for_each(|item| {
                 logging!(debug, Type::Core, "run builtin script {}", item.uid);
                 if let ChainType::Script(script) = item.data {
-                    match use_script(script, config.to_owned(), "".into()) {
+                    match use_builtin_script(
+                        script,
+                        config.to_owned(),
+                        "".into(),
+                        script_limits,
+                        script_env,
+                    ) {
                         Ok((res_config, _)) => {
                             config = res_config;
                         }
@@ -527,6 +728,22 @@ async fn apply_dns_settings(mut config: Mapping, enable_dns_settings: bool) -> M
 /// Enhance mode
 /// 返回最终订阅、该订阅包含的键、和script执行的结果
 pub async fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
+    enhance_with_trace(&mut EnhanceTrace::default()).await
+}
+
+/// 执行增强流程但不写入运行时配置，返回每个阶段后的配置快照
+pub async fn enhance_dry_run() -> Vec<EnhanceStage> {
+    let mut trace = EnhanceTrace {
+        enabled: true,
+        ..Default::default()
+    };
+    enhance_with_trace(&mut trace).await;
+    trace.stages
+}
+
+async fn enhance_with_trace(
+    trace: &mut EnhanceTrace,
+) -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
     // gather config values
     let cfg_vals = get_config_values().await;
     let ConfigValues {
@@ -537,6 +754,8 @@ pub async fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
         socks_enabled,
         http_enabled,
         enable_dns_settings,
+        script_limits,
+        script_env,
         #[cfg(not(target_os = "windows"))]
         redir_enabled,
         #[cfg(target_os = "linux")]
@@ -551,13 +770,23 @@ pub async fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
     let rules_item = profile.rules_item;
     let proxies_item = profile.proxies_item;
     let groups_item = profile.groups_item;
+    let rule_providers_item = profile.rule_providers_item;
+    let proxy_providers_item = profile.proxy_providers_item;
     let global_merge = profile.global_merge;
     let global_script = profile.global_script;
     let profile_name = profile.profile_name;
 
     // process globals
-    let (config, exists_keys, result_map) =
-        process_global_items(config, global_merge, global_script, profile_name.clone());
+    let (config, exists_keys, result_map) = process_global_items(
+        config,
+        global_merge,
+        global_script,
+        profile_name.clone(),
+        script_limits,
+        &script_env,
+        trace,
+    )
+    .await;
 
     // process profile-specific items
     let (config, exists_keys, result_map) = process_profile_items(
@@ -567,10 +796,16 @@ pub async fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
         rules_item,
         proxies_item,
         groups_item,
+        rule_providers_item,
+        proxy_providers_item,
         merge_item,
         script_item,
         profile_name,
-    );
+        script_limits,
+        &script_env,
+        trace,
+    )
+    .await;
 
     // merge default clash config
     let config = merge_default_config(
@@ -584,15 +819,31 @@ pub async fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
         tproxy_enabled,
     )
     .await;
+    trace.record("merge_default_config", &config);
 
     // builtin scripts
-    let mut config = apply_builtin_scripts(config, clash_core, enable_builtin);
+    let mut config = apply_builtin_scripts(
+        config,
+        clash_core,
+        enable_builtin,
+        script_limits,
+        &script_env,
+    );
+    trace.record("builtin_scripts", &config);
+
+    if let Some(port) = injected_port().await {
+        config = use_node_route(config, port);
+    }
+    trace.record("use_node_route", &config);
 
     config = use_tun(config, enable_tun);
+    trace.record("use_tun", &config);
     config = use_sort(config);
+    trace.record("use_sort", &config);
 
     // dns settings
     config = apply_dns_settings(config, enable_dns_settings).await;
+    trace.record("apply_dns_settings", &config);
 
     let mut exists_set = HashSet::new();
     exists_set.extend(exists_keys);
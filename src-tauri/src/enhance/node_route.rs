@@ -0,0 +1,94 @@
+use crate::core::node_route::NODE_ROUTE_GROUP;
+use serde_yaml_ng::{Mapping, Value};
+
+/// Add [`NODE_ROUTE_GROUP`] with every proxy, group and proxy provider of the config,
+/// and a loopback listener on `port` routed to it without going through the rules
+pub fn use_node_route(mut config: Mapping, port: u16) -> Mapping {
+    let names = |field: &str| -> Vec<Value> {
+        config
+            .get(field)
+            .and_then(Value::as_sequence)
+            .into_iter()
+            .flatten()
+            .filter_map(|item| item.get("name").and_then(Value::as_str))
+            .map(Value::from)
+            .collect()
+    };
+    let mut proxies = vec![Value::from("DIRECT")];
+    proxies.extend(names("proxies"));
+    proxies.extend(names("proxy-groups"));
+    let providers: Vec<Value> = config
+        .get("proxy-providers")
+        .and_then(Value::as_mapping)
+        .into_iter()
+        .flat_map(Mapping::keys)
+        .cloned()
+        .collect();
+
+    let mut group = Mapping::new();
+    group.insert("name".into(), NODE_ROUTE_GROUP.into());
+    group.insert("type".into(), "select".into());
+    group.insert("hidden".into(), true.into());
+    group.insert("proxies".into(), proxies.into());
+    if !providers.is_empty() {
+        group.insert("use".into(), providers.into());
+    }
+    push(&mut config, "proxy-groups", group);
+
+    let mut listener = Mapping::new();
+    listener.insert("name".into(), NODE_ROUTE_GROUP.into());
+    listener.insert("type".into(), "mixed".into());
+    listener.insert("listen".into(), "127.0.0.1".into());
+    listener.insert("port".into(), port.into());
+    listener.insert("proxy".into(), NODE_ROUTE_GROUP.into());
+    push(&mut config, "listeners", listener);
+
+    config
+}
+
+fn push(config: &mut Mapping, field: &str, item: Mapping) {
+    let mut items = config
+        .get(field)
+        .and_then(Value::as_sequence)
+        .cloned()
+        .unwrap_or_default();
+    items.push(item.into());
+    config.insert(field.into(), items.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_use_node_route() {
+        let config: Mapping = serde_yaml_ng::from_str(
+            r"
+proxies:
+  - { name: a, type: ss }
+proxy-groups:
+  - { name: Proxy, type: select, proxies: [a] }
+proxy-providers:
+  sub: { type: http }
+listeners:
+  - { name: in, type: socks, port: 1080 }
+",
+        )
+        .unwrap_or_default();
+        let config = use_node_route(config, 40000);
+
+        let groups = &config["proxy-groups"];
+        assert_eq!(groups.as_sequence().map(Vec::len), Some(2));
+        assert_eq!(groups[1]["name"], NODE_ROUTE_GROUP);
+        assert_eq!(groups[1]["hidden"], true);
+        let expected: Vec<Value> = ["DIRECT", "a", "Proxy"].map(Value::from).into();
+        assert_eq!(groups[1]["proxies"], Value::from(expected));
+        assert_eq!(groups[1]["use"], Value::from(vec![Value::from("sub")]));
+
+        let listeners = &config["listeners"];
+        assert_eq!(listeners.as_sequence().map(Vec::len), Some(2));
+        assert_eq!(listeners[1]["port"], 40000);
+        assert_eq!(listeners[1]["listen"], "127.0.0.1");
+        assert_eq!(listeners[1]["proxy"], NODE_ROUTE_GROUP);
+    }
+}
@@ -0,0 +1,156 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml_ng::{Mapping, Value};
+use std::collections::HashSet;
+
+/// Prepend, append or delete entries of `rule-providers` / `proxy-providers` by key
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderMap {
+    /// entries placed before the original ones, kept on key conflicts
+    #[serde(default)]
+    pub prepend: Mapping,
+
+    /// entries placed after the original ones, override on key conflicts
+    #[serde(default)]
+    pub append: Mapping,
+
+    /// keys of the original entries to delete
+    #[serde(default)]
+    pub delete: Vec<String>,
+}
+
+pub fn use_providers(providers: ProviderMap, mut config: Mapping, field: &str) -> Mapping {
+    let ProviderMap {
+        prepend,
+        append,
+        delete,
+    } = providers;
+
+    if prepend.is_empty() && append.is_empty() && delete.is_empty() {
+        return config;
+    }
+
+    let mut new_map = prepend;
+    if let Some(Value::Mapping(origin)) = config.get(field) {
+        for (key, value) in origin {
+            let deleted = key.as_str().is_some_and(|k| delete.iter().any(|d| d == k));
+            if !deleted && !new_map.contains_key(key) {
+                new_map.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    for (key, value) in append {
+        new_map.insert(key, value);
+    }
+    config.insert(Value::String(field.into()), Value::Mapping(new_map));
+
+    // proxy-groups refer to proxy-providers by `use`
+    if field == "proxy-providers"
+        && !delete.is_empty()
+        && let Some(Value::Sequence(groups)) = config.get_mut("proxy-groups")
+    {
+        for group in groups.iter_mut() {
+            if let Some(Value::Sequence(uses)) = group.get_mut("use") {
+                uses.retain(|u| u.as_str().is_none_or(|u| !delete.iter().any(|d| d == u)));
+            }
+        }
+    }
+
+    config
+}
+
+/// `RULE-SET` names in `rules` and `sub-rules` without a matching `rule-providers` entry
+pub fn dangling_rule_sets(config: &Mapping) -> Vec<String> {
+    let Ok(re) = Regex::new(r"RULE-SET\s*,\s*([^,()\s]+)") else {
+        return vec![];
+    };
+    let providers: HashSet<&str> = config
+        .get("rule-providers")
+        .and_then(Value::as_mapping)
+        .map(|m| m.keys().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let rules = config
+        .get("rules")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten();
+    let sub_rules = config
+        .get("sub-rules")
+        .and_then(Value::as_mapping)
+        .into_iter()
+        .flat_map(Mapping::values)
+        .filter_map(Value::as_sequence)
+        .flatten();
+
+    let mut dangling = vec![];
+    for rule in rules.chain(sub_rules).filter_map(Value::as_str) {
+        for cap in re.captures_iter(rule) {
+            let name = &cap[1];
+            if !providers.contains(name) && !dangling.iter().any(|d| d == name) {
+                dangling.push(name.to_owned());
+            }
+        }
+    }
+    dangling
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_providers_and_dangling_rule_sets() {
+        let config: Mapping = serde_yaml_ng::from_str(
+            r"
+rule-providers:
+  reject: { type: http, behavior: domain, url: https://example.com/reject.yaml }
+  old: { type: http, behavior: domain, url: https://example.com/old.yaml }
+proxy-providers:
+  sub: { type: http, url: https://example.com/sub.yaml }
+proxy-groups:
+  - { name: PROXY, type: select, use: [sub] }
+rules:
+  - RULE-SET,reject,REJECT
+  - RULE-SET,old,DIRECT
+  - AND,((RULE-SET,extra),(NETWORK,UDP)),REJECT
+  - MATCH,PROXY
+",
+        )
+        .expect("Failed to parse test config YAML");
+
+        let rule_providers: ProviderMap = serde_yaml_ng::from_str(
+            r"
+append:
+  extra: { type: http, behavior: ipcidr, url: https://example.com/extra.yaml }
+delete: [old]
+",
+        )
+        .expect("Failed to parse provider map");
+        let proxy_providers = ProviderMap {
+            delete: vec!["sub".into()],
+            ..Default::default()
+        };
+
+        let config = use_providers(rule_providers, config, "rule-providers");
+        let config = use_providers(proxy_providers, config, "proxy-providers");
+
+        let keys: Vec<&str> = config
+            .get("rule-providers")
+            .and_then(Value::as_mapping)
+            .expect("rule-providers should be a mapping")
+            .keys()
+            .filter_map(Value::as_str)
+            .collect();
+        assert_eq!(keys, vec!["reject", "extra"]);
+        assert_eq!(
+            config
+                .get("proxy-groups")
+                .and_then(|g| g.get(0))
+                .and_then(|g| g.get("use")),
+            Some(&Value::Sequence(vec![]))
+        );
+        assert_eq!(dangling_rule_sets(&config), vec!["old".to_owned()]);
+    }
+}
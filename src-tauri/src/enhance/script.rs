@@ -1,56 +1,544 @@
 use super::use_lowercase;
-use anyhow::{Error, Result};
+use crate::{
+    config::{IProfiles, IVerge},
+    process::AsyncHandler,
+    utils::dirs,
+};
+use anyhow::{Context as _, Error, Result};
+use serde::{Deserialize, Serialize};
 use serde_yaml_ng::Mapping;
 use smartstring::alias::String;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    io::{Read as _, Write as _},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{Arc, mpsc},
+    thread,
+    time::{Duration, Instant},
+};
 
-pub fn use_script(
+/// The argument that makes the app binary run one script from stdin and exit
+pub const SCRIPT_WORKER_ARG: &str = "--verge-script-worker";
+
+/// 脚本执行限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptLimits {
+    /// max iterations of a single loop
+    pub loop_iterations: u64,
+    /// max depth of recursive calls
+    pub recursion: usize,
+    /// wall-clock limit of a script run
+    pub timeout: Duration,
+    /// max memory of the process running the script, `0` to disable
+    pub memory_bytes: u64,
+}
+
+impl ScriptLimits {
+    pub const DEFAULT_LOOP_ITERATIONS: u64 = 10_000_000;
+    pub const DEFAULT_RECURSION: usize = 512;
+    pub const DEFAULT_TIMEOUT_SECS: u64 = 10;
+    pub const DEFAULT_MEMORY_MB: u64 = 256;
+
+    pub fn from_verge(verge: &IVerge) -> Self {
+        Self {
+            loop_iterations: verge
+                .script_max_loop_iterations
+                .unwrap_or(Self::DEFAULT_LOOP_ITERATIONS)
+                .max(1),
+            timeout: Duration::from_secs(
+                verge
+                    .script_timeout_seconds
+                    .unwrap_or(Self::DEFAULT_TIMEOUT_SECS)
+                    .max(1),
+            ),
+            memory_bytes: verge
+                .script_max_memory_mb
+                .unwrap_or(Self::DEFAULT_MEMORY_MB)
+                .saturating_mul(1024 * 1024),
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            loop_iterations: Self::DEFAULT_LOOP_ITERATIONS,
+            recursion: Self::DEFAULT_RECURSION,
+            timeout: Duration::from_secs(Self::DEFAULT_TIMEOUT_SECS),
+            memory_bytes: Self::DEFAULT_MEMORY_MB * 1024 * 1024,
+        }
+    }
+}
+
+/// 脚本中 `verge` 全局对象可读取的数据
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScriptEnv {
+    pub core: String,
+    pub platform: String,
+    /// enabled ports by kind, e.g. `mixed`, `socks`, `http`
+    pub ports: BTreeMap<String, u16>,
+    /// the current profile and its chain items, which `verge.getProfile(uid)` can read
+    pub profiles: HashMap<String, ScriptProfile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptProfile {
+    pub itype: String,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl ScriptEnv {
+    pub fn new(verge: &IVerge, profiles: &IProfiles) -> Self {
+        let mut ports = BTreeMap::new();
+        if let Some(port) = verge.verge_mixed_port {
+            ports.insert("mixed".into(), port);
+        }
+        if verge.verge_socks_enabled.unwrap_or(false)
+            && let Some(port) = verge.verge_socks_port
+        {
+            ports.insert("socks".into(), port);
+        }
+        if verge.verge_http_enabled.unwrap_or(false)
+            && let Some(port) = verge.verge_port
+        {
+            ports.insert("http".into(), port);
+        }
+        #[cfg(not(target_os = "windows"))]
+        if verge.verge_redir_enabled.unwrap_or(false)
+            && let Some(port) = verge.verge_redir_port
+        {
+            ports.insert("redir".into(), port);
+        }
+        #[cfg(target_os = "linux")]
+        if verge.verge_tproxy_enabled.unwrap_or(false)
+            && let Some(port) = verge.verge_tproxy_port
+        {
+            ports.insert("tproxy".into(), port);
+        }
+
+        // 脚本只能读取当前订阅及其增强链，其他订阅可能含有无关的凭据
+        let readable = Self::readable_uids(profiles);
+        let mut items = HashMap::new();
+        if let Ok(profiles_dir) = dirs::app_profiles_dir() {
+            for item in profiles.items.iter().flatten() {
+                let (Some(uid), Some(itype), Some(file)) = (&item.uid, &item.itype, &item.file)
+                else {
+                    continue;
+                };
+                if !readable.contains(uid) {
+                    continue;
+                }
+                items.insert(
+                    uid.clone(),
+                    ScriptProfile {
+                        itype: itype.clone(),
+                        name: item.name.clone().unwrap_or_default(),
+                        path: profiles_dir.join(file.as_str()),
+                    },
+                );
+            }
+        }
+
+        Self {
+            core: verge.get_valid_clash_core(),
+            platform: std::env::consts::OS.into(),
+            ports,
+            profiles: items,
+        }
+    }
+
+    fn readable_uids(profiles: &IProfiles) -> HashSet<String> {
+        let mut uids = HashSet::from(["Merge".into(), "Script".into()]);
+        if let Some(current) = profiles
+            .get_current()
+            .and_then(|uid| profiles.get_item(uid).ok())
+        {
+            uids.extend(current.uid.clone());
+            uids.extend(
+                [
+                    current.current_merge(),
+                    current.current_script(),
+                    current.current_rules(),
+                    current.current_proxies(),
+                    current.current_groups(),
+                    current.current_rule_providers(),
+                    current.current_proxy_providers(),
+                ]
+                .into_iter()
+                .flatten(),
+            );
+        }
+        uids
+    }
+
+    /// the JSON object exposed as `verge.getProfile(uid)`
+    fn profile_json(&self, uid: &str) -> Result<Option<std::string::String>> {
+        let Some(profile) = self.profiles.get(uid) else {
+            return Ok(None);
+        };
+        let content = std::fs::read_to_string(&profile.path)?;
+        let data = if profile.itype == "script" {
+            serde_json::Value::String(content)
+        } else {
+            let value: serde_yaml_ng::Value = serde_yaml_ng::from_str(&content)?;
+            serde_json::to_value(value)?
+        };
+        Ok(Some(serde_json::to_string(&serde_json::json!({
+            "uid": uid,
+            "type": profile.itype.as_str(),
+            "name": profile.name.as_str(),
+            "data": data,
+        }))?))
+    }
+
+    fn globals_json(&self) -> Result<std::string::String> {
+        Ok(serde_json::to_string(&serde_json::json!({
+            "core": self.core.as_str(),
+            "platform": self.platform.as_str(),
+            "ports": self.ports,
+        }))?)
+    }
+}
+
+/// 脚本触发的执行限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ScriptLimitExceeded {
+    LoopIterations(u64),
+    Recursion(usize),
+    Timeout(Duration),
+    Memory(u64),
+}
+
+impl fmt::Display for ScriptLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LoopIterations(limit) => {
+                write!(f, "script exceeded the loop iteration limit ({limit})")
+            }
+            Self::Recursion(limit) => write!(f, "script exceeded the recursion limit ({limit})"),
+            Self::Timeout(limit) => {
+                write!(
+                    f,
+                    "script exceeded the time limit ({}ms)",
+                    limit.as_millis()
+                )
+            }
+            Self::Memory(limit) => write!(
+                f,
+                "script exceeded the memory limit ({}MB)",
+                limit / 1024 / 1024
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ScriptLimitExceeded {}
+
+type ScriptOutput = Result<(Mapping, Vec<(String, String)>)>;
+
+/// What the parent sends to the script process
+#[derive(Serialize, Deserialize)]
+struct WorkerRequest {
     script: String,
     config: Mapping,
     name: String,
-) -> Result<(Mapping, Vec<(String, String)>)> {
+    loop_iterations: u64,
+    recursion: usize,
+    env: ScriptEnv,
+}
+
+/// What the script process answers
+#[derive(Debug, Serialize, Deserialize)]
+enum WorkerResponse {
+    Done {
+        config: Mapping,
+        logs: Vec<(String, String)>,
+    },
+    Limit(ScriptLimitExceeded),
+    Failed(String),
+}
+
+impl WorkerResponse {
+    fn from_output(output: ScriptOutput) -> Self {
+        match output {
+            Ok((config, logs)) => Self::Done { config, logs },
+            Err(err) => match err.downcast_ref::<ScriptLimitExceeded>() {
+                Some(exceeded) => Self::Limit(*exceeded),
+                None => Self::Failed(err.to_string().into()),
+            },
+        }
+    }
+
+    fn into_output(self) -> ScriptOutput {
+        match self {
+            Self::Done { config, logs } => Ok((config, logs)),
+            Self::Limit(exceeded) => Err(exceeded.into()),
+            Self::Failed(err) => Err(anyhow::anyhow!(err)),
+        }
+    }
+}
+
+/// 在子进程中执行用户脚本，超出时间或内存限制时结束子进程
+pub async fn use_script(
+    script: String,
+    config: Mapping,
+    name: String,
+    limits: ScriptLimits,
+    env: Arc<ScriptEnv>,
+) -> ScriptOutput {
+    // 等待子进程会阻塞线程，不能占用异步运行时的工作线程
+    AsyncHandler::spawn_blocking(move || run_in_worker(script, config, name, limits, &env)).await?
+}
+
+/// 在当前进程中执行内置脚本，内置脚本可信，无需启动子进程
+pub fn use_builtin_script(
+    script: String,
+    config: Mapping,
+    name: String,
+    limits: ScriptLimits,
+    env: &Arc<ScriptEnv>,
+) -> ScriptOutput {
+    run_script(script, config, name, limits, env)
+}
+
+fn run_in_worker(
+    script: String,
+    config: Mapping,
+    name: String,
+    limits: ScriptLimits,
+    env: &ScriptEnv,
+) -> ScriptOutput {
+    let request = serde_json::to_vec(&WorkerRequest {
+        script,
+        config,
+        name,
+        loop_iterations: limits.loop_iterations,
+        recursion: limits.recursion,
+        env: env.clone(),
+    })?;
+
+    let mut child = Command::new(std::env::current_exe()?)
+        .arg(SCRIPT_WORKER_ARG)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("failed to start the script process")?;
+    let result = wait_worker(&mut child, &request, limits);
+    if result.is_err() {
+        let _ = child.kill();
+    }
+    let _ = child.wait();
+    result?.into_output()
+}
+
+fn wait_worker(child: &mut Child, request: &[u8], limits: ScriptLimits) -> Result<WorkerResponse> {
+    // 输出可能超过管道缓冲区，需要边执行边读取
+    let mut stdout = child
+        .stdout
+        .take()
+        .context("script process has no stdout")?;
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("verge-script".into())
+        .spawn(move || {
+            let mut output = Vec::new();
+            let _ = tx.send(stdout.read_to_end(&mut output).map(|_| output));
+        })?;
+
+    let mut stdin = child.stdin.take().context("script process has no stdin")?;
+    stdin
+        .write_all(request)
+        .context("failed to send the script to the script process")?;
+    drop(stdin);
+
+    let started = Instant::now();
+    let mut memory = MemoryProbe::new(child.id(), limits.memory_bytes);
+    loop {
+        match rx.recv_timeout(Duration::from_millis(20)) {
+            Ok(output) => {
+                let output = output.context("failed to read the script result")?;
+                return serde_json::from_slice(&output)
+                    .context("script process exited unexpectedly");
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                anyhow::bail!("script process exited unexpectedly")
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+        if started.elapsed() >= limits.timeout {
+            return Err(ScriptLimitExceeded::Timeout(limits.timeout).into());
+        }
+        if memory.exceeded() {
+            return Err(ScriptLimitExceeded::Memory(limits.memory_bytes).into());
+        }
+    }
+}
+
+/// Entry of the script process: read a request from stdin, run it and write the response to stdout
+pub fn run_script_worker() -> i32 {
+    let mut input = Vec::new();
+    let response = match std::io::stdin().read_to_end(&mut input) {
+        Ok(_) => match serde_json::from_slice::<WorkerRequest>(&input) {
+            Ok(request) => run_request(request),
+            Err(err) => WorkerResponse::Failed(format!("invalid script request: {err}").into()),
+        },
+        Err(err) => WorkerResponse::Failed(format!("failed to read script request: {err}").into()),
+    };
+    let mut stdout = std::io::stdout().lock();
+    match serde_json::to_writer(&mut stdout, &response) {
+        Ok(()) if stdout.flush().is_ok() => 0,
+        _ => 1,
+    }
+}
+
+fn run_request(request: WorkerRequest) -> WorkerResponse {
+    let limits = ScriptLimits {
+        loop_iterations: request.loop_iterations,
+        recursion: request.recursion,
+        ..ScriptLimits::default()
+    };
+    WorkerResponse::from_output(run_script(
+        request.script,
+        request.config,
+        request.name,
+        limits,
+        &Arc::new(request.env),
+    ))
+}
+
+/// The memory of the script process, which holds nothing but the script runtime
+struct MemoryProbe {
+    limit: u64,
+    pid: sysinfo::Pid,
+    system: sysinfo::System,
+}
+
+impl MemoryProbe {
+    fn new(pid: u32, limit: u64) -> Self {
+        Self {
+            limit,
+            pid: sysinfo::Pid::from_u32(pid),
+            system: sysinfo::System::new(),
+        }
+    }
+
+    fn exceeded(&mut self) -> bool {
+        if self.limit == 0 {
+            return false;
+        }
+        self.system.refresh_processes_specifics(
+            sysinfo::ProcessesToUpdate::Some(&[self.pid]),
+            true,
+            sysinfo::ProcessRefreshKind::nothing().with_memory(),
+        );
+        self.system
+            .process(self.pid)
+            .is_some_and(|p| p.memory() > self.limit)
+    }
+}
+
+fn limit_error(err: &str, limits: ScriptLimits) -> Option<ScriptLimitExceeded> {
+    if err.contains("loop iteration limit") {
+        Some(ScriptLimitExceeded::LoopIterations(limits.loop_iterations))
+    } else if err.contains("recursion limit") || err.contains("stack size") {
+        Some(ScriptLimitExceeded::Recursion(limits.recursion))
+    } else {
+        None
+    }
+}
+
+fn js_error(msg: impl fmt::Display) -> boa_engine::JsError {
+    boa_engine::JsError::from_opaque(boa_engine::JsString::from(msg.to_string().as_str()).into())
+}
+
+fn string_arg(
+    args: &[boa_engine::JsValue],
+    index: usize,
+    name: &str,
+    context: &mut boa_engine::Context,
+) -> boa_engine::JsResult<std::string::String> {
+    let value = args
+        .get(index)
+        .ok_or_else(|| js_error(format!("Missing {name} argument")))?;
+    value
+        .to_string(context)?
+        .to_std_string()
+        .map_err(|_| js_error(format!("Failed to convert {name} to string")))
+}
+
+fn run_script(
+    script: String,
+    config: Mapping,
+    name: String,
+    limits: ScriptLimits,
+    env: &Arc<ScriptEnv>,
+) -> ScriptOutput {
     use boa_engine::{Context, JsString, JsValue, Source, native_function::NativeFunction};
     use std::{cell::RefCell, rc::Rc};
     let mut context = Context::default();
-
+    context
+        .runtime_limits_mut()
+        .set_loop_iteration_limit(limits.loop_iterations);
+    context
+        .runtime_limits_mut()
+        .set_recursion_limit(limits.recursion);
     let outputs = Rc::new(RefCell::new(vec![]));
 
     let copy_outputs = Rc::clone(&outputs);
+    let profile_env = Arc::clone(env);
     unsafe {
         let _ = context.register_global_builtin_callable(
             "__verge_log__".into(),
             2,
             NativeFunction::from_closure(
                 move |_: &JsValue, args: &[JsValue], context: &mut Context| {
-                    let level = args.first().ok_or_else(|| {
-                        boa_engine::JsError::from_opaque(
-                            JsString::from("Missing level argument").into(),
-                        )
-                    })?;
-                    let level = level.to_string(context)?;
-                    let level = level.to_std_string().map_err(|_| {
-                        boa_engine::JsError::from_opaque(
-                            JsString::from("Failed to convert level to string").into(),
-                        )
-                    })?;
-
-                    let data = args.get(1).ok_or_else(|| {
-                        boa_engine::JsError::from_opaque(
-                            JsString::from("Missing data argument").into(),
-                        )
-                    })?;
-                    let data = data.to_string(context)?;
-                    let data = data.to_std_string().map_err(|_| {
-                        boa_engine::JsError::from_opaque(
-                            JsString::from("Failed to convert data to string").into(),
-                        )
-                    })?;
+                    let level = string_arg(args, 0, "level", context)?;
+                    let data = string_arg(args, 1, "data", context)?;
                     let mut out = copy_outputs.borrow_mut();
                     out.push((level.into(), data.into()));
                     Ok(JsValue::undefined())
                 },
             ),
         );
+        let _ = context.register_global_builtin_callable(
+            "__verge_profile__".into(),
+            1,
+            NativeFunction::from_closure(
+                move |_: &JsValue, args: &[JsValue], context: &mut Context| {
+                    let uid = string_arg(args, 0, "uid", context)?;
+                    match profile_env.profile_json(&uid).map_err(js_error)? {
+                        Some(json) => Ok(JsString::from(json.as_str()).into()),
+                        None => Ok(JsValue::null()),
+                    }
+                },
+            ),
+        );
     }
+    let _ = context.register_global_builtin_callable(
+        "__verge_yaml_parse__".into(),
+        1,
+        NativeFunction::from_fn_ptr(|_, args, context| {
+            let data = string_arg(args, 0, "yaml", context)?;
+            let value: serde_yaml_ng::Value = serde_yaml_ng::from_str(&data).map_err(js_error)?;
+            let json = serde_json::to_string(&value).map_err(js_error)?;
+            Ok(JsString::from(json.as_str()).into())
+        }),
+    );
+    let _ = context.register_global_builtin_callable(
+        "__verge_yaml_stringify__".into(),
+        1,
+        NativeFunction::from_fn_ptr(|_, args, context| {
+            let data = string_arg(args, 0, "json", context)?;
+            let value: serde_json::Value = serde_json::from_str(&data).map_err(js_error)?;
+            let yaml = serde_yaml_ng::to_string(&value).map_err(js_error)?;
+            Ok(JsString::from(yaml.as_str()).into())
+        }),
+    );
     let _ = context.eval(Source::from_bytes(
         r#"var console = Object.freeze({
         log(data){__verge_log__("log",JSON.stringify(data, null, 2))},
@@ -62,46 +550,85 @@ pub fn use_script(
       });"#,
     ));
 
+    let globals = env.globals_json()?;
+    let _ = context.eval(Source::from_bytes(
+        format!(
+            r"var verge = (function(g){{
+        function freeze(o){{Object.values(o).forEach(v=>{{if(v&&typeof v==='object')freeze(v)}});return Object.freeze(o)}}
+        return freeze({{
+          core: g.core,
+          platform: g.platform,
+          ports: g.ports,
+          yaml: {{
+            parse(data){{return JSON.parse(__verge_yaml_parse__(String(data)))}},
+            stringify(data){{return __verge_yaml_stringify__(JSON.stringify(data))}},
+          }},
+          getProfile(uid){{const r=__verge_profile__(String(uid));return r===null?null:JSON.parse(r)}},
+        }});
+      }})({globals});"
+        )
+        .as_bytes(),
+    ));
+
     let config = use_lowercase(config);
     let config_str = serde_json::to_string(&config)?;
 
     // 仅处理 name 参数中的特殊字符
     let safe_name = escape_js_string_for_single_quote(&name);
 
+    // main 可以是 async 函数，结果在任务队列执行完后写入 __verge_result__
     let code = format!(
-        r"try{{
-        {script};
-        JSON.stringify(main({config_str},'{safe_name}')||'')
-      }} catch(err) {{
-        `__error_flag__ ${{err.toString()}}`
-      }}"
+        r"var __verge_result__;
+      (async () => {{
+        try{{
+          {script};
+          __verge_result__ = JSON.stringify((await main({config_str},'{safe_name}'))||'')
+        }} catch(err) {{
+          __verge_result__ = `__error_flag__ ${{err.toString()}}`
+        }}
+      }})();"
     );
 
-    if let Ok(result) = context.eval(Source::from_bytes(code.as_str())) {
-        if !result.is_string() {
-            anyhow::bail!("main function should return object");
-        }
-        let result = result
-            .to_string(&mut context)
-            .map_err(|e| anyhow::anyhow!("Failed to convert JS result to string: {}", e))?;
-        let result = result
-            .to_std_string()
-            .map_err(|_| anyhow::anyhow!("Failed to convert JS string to std string"))?;
-
-        // 直接解析JSON结果,不做其他解析
-        let res: Result<Mapping, Error> = parse_json_safely(&result);
-
-        let mut out = outputs.borrow_mut();
-        match res {
-            Ok(config) => Ok((use_lowercase(config), out.to_vec())),
-            Err(err) => {
-                out.push(("exception".into(), err.to_string().into()));
-                Ok((config, out.to_vec()))
-            }
+    // 执行限制导致的错误无法在脚本中被 catch
+    let check_limit = |err: boa_engine::JsError| -> Error {
+        let err = err.to_string();
+        match limit_error(&err, limits) {
+            Some(exceeded) => exceeded.into(),
+            None => anyhow::anyhow!(err),
         }
-    } else {
+    };
+    context
+        .eval(Source::from_bytes(code.as_str()))
+        .map_err(check_limit)?;
+    context.run_jobs().map_err(check_limit)?;
+
+    let result = context
+        .eval(Source::from_bytes("__verge_result__"))
+        .map_err(check_limit)?;
+    if result.is_undefined() {
+        anyhow::bail!("main function did not resolve");
+    }
+    if !result.is_string() {
         anyhow::bail!("main function should return object");
     }
+    let result = result
+        .to_string(&mut context)
+        .map_err(|e| anyhow::anyhow!("Failed to convert JS result to string: {}", e))?;
+    let result = result
+        .to_std_string()
+        .map_err(|_| anyhow::anyhow!("Failed to convert JS string to std string"))?;
+
+    // 直接解析JSON结果,不做其他解析
+    let res: Result<Mapping, Error> = parse_json_safely(&result);
+
+    let mut out = outputs.borrow_mut();
+    match res {
+        Ok(config) => Ok((use_lowercase(config), out.to_vec())),
+        Err(err) => {
+            out.push(("exception".into(), err.to_string().into()));
+            Ok((config, out.to_vec()))
+        }
+    }
 }
 
 fn parse_json_safely(json_str: &str) -> Result<Mapping, Error> {
@@ -151,8 +678,14 @@ fn test_script() {
   ";
 
     let config = serde_yaml_ng::from_str(config).expect("Failed to parse test config YAML");
-    let (config, results) = use_script(script.into(), config, "".into())
-        .expect("Script execution should succeed in test");
+    let (config, results) = run_script(
+        script.into(),
+        config,
+        "".into(),
+        ScriptLimits::default(),
+        &Arc::default(),
+    )
+    .expect("Script execution should succeed in test");
 
     let _ = serde_yaml_ng::to_string(&config).expect("Failed to serialize config to YAML");
     let yaml_config_size = std::mem::size_of_val(&config);
@@ -182,3 +715,94 @@ fn test_escape_unescape() {
     assert!(parsed_quoted.contains_key("key"));
     assert!(parsed_quoted.contains_key("nested"));
 }
+
+#[test]
+#[allow(clippy::expect_used)]
+fn test_script_limits() {
+    let config: Mapping =
+        serde_yaml_ng::from_str("rules: []").expect("Failed to parse test config YAML");
+
+    let endless = "function main(config) { while (true) {} return config; }";
+    let limits = ScriptLimits {
+        loop_iterations: 10_000,
+        ..ScriptLimits::default()
+    };
+    let err = run_script(
+        endless.into(),
+        config.clone(),
+        "".into(),
+        limits,
+        &Arc::default(),
+    )
+    .expect_err("endless loop should hit the loop limit");
+    assert_eq!(
+        err.downcast_ref::<ScriptLimitExceeded>(),
+        Some(&ScriptLimitExceeded::LoopIterations(10_000))
+    );
+
+    let recursive =
+        "function f(n) { return f(n + 1); } function main(config) { f(0); return config; }";
+    let err = run_script(
+        recursive.into(),
+        config,
+        "".into(),
+        ScriptLimits::default(),
+        &Arc::default(),
+    )
+    .expect_err("endless recursion should hit the recursion limit");
+    assert!(err.downcast_ref::<ScriptLimitExceeded>().is_some());
+
+    // 执行限制需要经过子进程的响应传回
+    let response = WorkerResponse::from_output(Err(err));
+    let response: WorkerResponse = serde_json::from_slice(
+        &serde_json::to_vec(&response).expect("Failed to serialize worker response"),
+    )
+    .expect("Failed to parse worker response");
+    let err = response
+        .into_output()
+        .expect_err("limit should survive the worker response");
+    assert!(err.downcast_ref::<ScriptLimitExceeded>().is_some());
+}
+
+#[test]
+#[allow(clippy::expect_used)]
+fn test_async_script_env() {
+    let config: Mapping =
+        serde_yaml_ng::from_str("rules: []").expect("Failed to parse test config YAML");
+    let env = ScriptEnv {
+        core: "verge-mihomo".into(),
+        platform: "linux".into(),
+        ports: BTreeMap::from([("mixed".into(), 7897)]),
+        ..ScriptEnv::default()
+    };
+    let script = r#"
+    async function main(config) {
+      const extra = await Promise.resolve(verge.yaml.parse("rules:\n  - MATCH,DIRECT"));
+      config.rules = extra.rules;
+      config.core = verge.core;
+      config.port = verge.ports.mixed;
+      config.missing = verge.getProfile("missing");
+      console.log(verge.yaml.stringify({ a: 1 }));
+      return config;
+    }"#;
+    let (config, logs) = run_script(
+        script.into(),
+        config,
+        "".into(),
+        ScriptLimits::default(),
+        &Arc::new(env),
+    )
+    .expect("Async script execution should succeed in test");
+
+    assert_eq!(
+        config.get("rules"),
+        Some(&serde_yaml_ng::from_str("[MATCH,DIRECT]").expect("Failed to parse rules"))
+    );
+    assert_eq!(
+        config.get("core").and_then(|v| v.as_str()),
+        Some("verge-mihomo")
+    );
+    assert_eq!(config.get("port").and_then(|v| v.as_u64()), Some(7897));
+    assert!(config.get("missing").is_some_and(|v| v.is_null()));
+    assert_eq!(logs, vec![("log".into(), "\"a: 1\\n\"".into())]);
+}
@@ -1,11 +1,165 @@
+use crate::{logging, utils::logging::Type};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_yaml_ng::{Mapping, Sequence, Value};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct SeqMap {
     pub prepend: Sequence,
     pub append: Sequence,
     pub delete: Vec<String>,
+
+    /// delete the original items that match any of the matchers
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delete_match: Vec<SeqMatcher>,
+
+    /// patch the matched items in place
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modify: Vec<SeqModify>,
+}
+
+/// Selects items of a sequence, all the given predicates must match.
+/// String items (e.g. rules) can only be matched by `name`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SeqMatcher {
+    /// regex on `name`, or on the item itself for string items
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// `type` of the item, case insensitive
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub itype: Option<String>,
+
+    /// regex on `server`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+
+    /// a port like `443` or a range like `8000-9000`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<SeqPort>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SeqPort {
+    Port(u16),
+    Range(String),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SeqModify {
+    #[serde(rename = "match")]
+    pub matcher: SeqMatcher,
+
+    /// keys to set on every matched item, e.g. `udp: true`
+    #[serde(default)]
+    pub set: Mapping,
+
+    /// keys to remove from every matched item
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove: Vec<String>,
+}
+
+/// a matcher with its regexes compiled
+struct CompiledMatcher {
+    name: Option<Regex>,
+    itype: Option<String>,
+    server: Option<Regex>,
+    port: Option<(u16, u16)>,
+}
+
+impl CompiledMatcher {
+    fn new(matcher: &SeqMatcher) -> Option<Self> {
+        let compile = |pattern: Option<&str>| match pattern {
+            Some(pattern) => match Regex::new(pattern) {
+                Ok(re) => Ok(Some(re)),
+                Err(err) => {
+                    logging!(
+                        warn,
+                        Type::Core,
+                        "Warning: 无效的正则表达式 `{pattern}`: {err}"
+                    );
+                    Err(())
+                }
+            },
+            None => Ok(None),
+        };
+        let port = match &matcher.port {
+            Some(SeqPort::Port(port)) => Some((*port, *port)),
+            Some(SeqPort::Range(range)) => {
+                let parsed = match range.split_once('-') {
+                    Some((start, end)) => start.trim().parse().ok().zip(end.trim().parse().ok()),
+                    None => range.trim().parse().ok().map(|port| (port, port)),
+                };
+                if parsed.is_none() {
+                    logging!(warn, Type::Core, "Warning: 无效的端口范围 `{range}`");
+                    return None;
+                }
+                parsed
+            }
+            None => None,
+        };
+
+        let compiled = Self {
+            name: compile(matcher.name.as_deref()).ok()?,
+            itype: matcher.itype.as_ref().map(|t| t.to_lowercase()),
+            server: compile(matcher.server.as_deref()).ok()?,
+            port,
+        };
+        // an empty matcher would select everything
+        if compiled.name.is_none()
+            && compiled.itype.is_none()
+            && compiled.server.is_none()
+            && compiled.port.is_none()
+        {
+            return None;
+        }
+        Some(compiled)
+    }
+
+    fn is_match(&self, item: &Value) -> bool {
+        match item {
+            Value::String(s) => {
+                self.itype.is_none()
+                    && self.server.is_none()
+                    && self.port.is_none()
+                    && self.name.as_ref().is_some_and(|re| re.is_match(s))
+            }
+            Value::Mapping(m) => {
+                let field = |key: &str| m.get(key).and_then(Value::as_str);
+                self.name
+                    .as_ref()
+                    .is_none_or(|re| field("name").is_some_and(|name| re.is_match(name)))
+                    && self.itype.as_ref().is_none_or(|itype| {
+                        field("type").is_some_and(|t| t.eq_ignore_ascii_case(itype))
+                    })
+                    && self
+                        .server
+                        .as_ref()
+                        .is_none_or(|re| field("server").is_some_and(|server| re.is_match(server)))
+                    && self.port.is_none_or(|(start, end)| {
+                        m.get("port")
+                            .and_then(|port| match port {
+                                Value::Number(n) => n.as_u64(),
+                                Value::String(s) => s.parse().ok(),
+                                _ => None,
+                            })
+                            .is_some_and(|port| (u64::from(start)..=u64::from(end)).contains(&port))
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+fn item_name(item: &Value) -> Option<&str> {
+    match item {
+        Value::String(s) => Some(s),
+        Value::Mapping(m) => m.get("name").and_then(Value::as_str),
+        _ => None,
+    }
 }
 
 pub fn use_seq(seq: SeqMap, mut config: Mapping, field: &str) -> Mapping {
@@ -13,73 +167,125 @@ pub fn use_seq(seq: SeqMap, mut config: Mapping, field: &str) -> Mapping {
         prepend,
         append,
         delete,
+        delete_match,
+        modify,
     } = seq;
 
+    let delete_match: Vec<CompiledMatcher> = delete_match
+        .iter()
+        .filter_map(CompiledMatcher::new)
+        .collect();
+
     let mut new_seq = Sequence::new();
     new_seq.extend(prepend);
 
+    // names of the removed items, used to clean up the references to them
+    let mut removed: HashSet<String> = delete.iter().cloned().collect();
+
     if let Some(Value::Sequence(origin)) = config.get(field) {
         // Filter out deleted items
-        let filtered: Sequence = origin
-            .iter()
-            .filter(|item| {
-                if let Value::String(s) = item {
-                    !delete.contains(s)
-                } else if let Value::Mapping(m) = item {
-                    if let Some(Value::String(name)) = m.get("name") {
-                        !delete.contains(name)
-                    } else {
-                        true
-                    }
-                } else {
-                    true
+        for item in origin {
+            let exact = item_name(item).is_some_and(|name| delete.iter().any(|d| d == name));
+            if exact {
+                continue;
+            }
+            if delete_match.iter().any(|m| m.is_match(item)) {
+                if let Some(name) = item_name(item) {
+                    removed.insert(name.into());
                 }
-            })
-            .cloned()
-            .collect();
-        new_seq.extend(filtered);
+                continue;
+            }
+            new_seq.push(item.clone());
+        }
     }
 
     new_seq.extend(append);
+
+    // old name -> new name, when a patch renames an item
+    let mut renamed = HashMap::new();
+    for patch in modify.iter() {
+        let Some(matcher) = CompiledMatcher::new(&patch.matcher) else {
+            continue;
+        };
+        // renaming several items to one name would leave duplicate names
+        if patch.set.contains_key("name")
+            && new_seq.iter().filter(|item| matcher.is_match(item)).count() > 1
+        {
+            logging!(
+                warn,
+                Type::Core,
+                "Warning: modify 会把多个 {field} 重命名为同一名称, 已跳过"
+            );
+            continue;
+        }
+        for item in new_seq.iter_mut() {
+            if !matcher.is_match(item) {
+                continue;
+            }
+            let Value::Mapping(map) = item else {
+                continue;
+            };
+            let old_name = map.get("name").and_then(Value::as_str).map(str::to_owned);
+            for key in patch.remove.iter() {
+                map.remove(key.as_str());
+            }
+            for (key, value) in patch.set.iter() {
+                map.insert(key.clone(), value.clone());
+            }
+            if let (Some(old_name), Some(new_name)) =
+                (old_name, map.get("name").and_then(Value::as_str))
+                && old_name != new_name
+            {
+                renamed.insert(old_name, new_name.to_owned());
+            }
+        }
+    }
+
     config.insert(Value::String(field.into()), Value::Sequence(new_seq));
 
-    // If this is proxies field, we also need to filter proxy-groups
-    if field == "proxies"
-        && let Some(Value::Sequence(groups)) = config.get_mut("proxy-groups")
+    // proxies and groups are referenced by name from groups and `dialer-proxy`
+    if (field == "proxies" || field == "proxy-groups")
+        && (!removed.is_empty() || !renamed.is_empty())
     {
-        let mut new_groups = Sequence::new();
-        for group in groups {
-            if let Value::Mapping(group_map) = group {
-                let mut new_group = group_map.clone();
-                if let Some(Value::Sequence(proxies)) = group_map.get("proxies") {
-                    let filtered_proxies: Sequence = proxies
-                        .iter()
-                        .filter(|p| {
-                            if let Value::String(name) = p {
-                                !delete.contains(name)
-                            } else {
-                                true
-                            }
-                        })
-                        .cloned()
-                        .collect();
-                    new_group.insert(
-                        Value::String("proxies".into()),
-                        Value::Sequence(filtered_proxies),
-                    );
+        update_references(&mut config, &removed, &renamed);
+    }
+
+    config
+}
+
+/// Drop the `removed` names from the `proxies` of every group and rename the `renamed` ones,
+/// also in the `dialer-proxy` of proxies
+fn update_references(
+    config: &mut Mapping,
+    removed: &HashSet<String>,
+    renamed: &HashMap<String, String>,
+) {
+    if let Some(Value::Sequence(groups)) = config.get_mut("proxy-groups") {
+        for group in groups.iter_mut() {
+            let Some(Value::Sequence(proxies)) = group.get_mut("proxies") else {
+                continue;
+            };
+            proxies.retain(|p| p.as_str().is_none_or(|name| !removed.contains(name)));
+            for proxy in proxies.iter_mut() {
+                if let Some(new) = proxy.as_str().and_then(|name| renamed.get(name)) {
+                    *proxy = Value::String(new.clone());
                 }
-                new_groups.push(Value::Mapping(new_group));
-            } else {
-                new_groups.push(group.clone());
             }
         }
-        config.insert(
-            Value::String("proxy-groups".into()),
-            Value::Sequence(new_groups),
-        );
     }
 
-    config
+    if !renamed.is_empty()
+        && let Some(Value::Sequence(proxies)) = config.get_mut("proxies")
+    {
+        for proxy in proxies.iter_mut() {
+            let Some(dialer) = proxy.get_mut("dialer-proxy") else {
+                continue;
+            };
+            if let Some(new) = dialer.as_str().and_then(|name| renamed.get(name)) {
+                *dialer = Value::String(new.clone());
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -116,6 +322,7 @@ proxy-groups:
             prepend: Sequence::new(),
             append: Sequence::new(),
             delete: vec!["proxy1".to_string()],
+            ..Default::default()
         };
 
         config = use_seq(seq, config, "proxies");
@@ -168,4 +375,129 @@ proxy-groups:
         );
         assert_eq!(group2_proxies.len(), 0);
     }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_match_delete_and_modify() {
+        let config_str = r#"
+proxies:
+- { name: "HK 01", type: ss, server: hk.example.com, port: 443 }
+- { name: "HK 02 expire", type: vmess, server: hk.example.com, port: 8443 }
+- { name: "US 01", type: trojan, server: us.example.com, port: 443 }
+proxy-groups:
+- { name: auto, type: url-test, proxies: ["HK 01", "HK 02 expire", "US 01"] }
+"#;
+        let config: Mapping =
+            serde_yaml_ng::from_str(config_str).expect("Failed to parse test config YAML");
+        let seq: SeqMap = serde_yaml_ng::from_str(
+            r#"
+prepend: []
+append: []
+delete: []
+delete-match:
+  - name: "expire"
+  - { type: TROJAN, port: "400-500" }
+modify:
+  - match: { server: "^hk\\." }
+    set: { udp: true, name: "HK 01 udp" }
+"#,
+        )
+        .expect("Failed to parse seq map");
+
+        let config = use_seq(seq, config, "proxies");
+        let proxies = config
+            .get("proxies")
+            .and_then(Value::as_sequence)
+            .expect("proxies should be a sequence");
+        assert_eq!(proxies.len(), 1);
+        assert_eq!(item_name(&proxies[0]), Some("HK 01 udp"));
+        assert_eq!(proxies[0].get("udp"), Some(&Value::Bool(true)));
+
+        let group_proxies = config
+            .get("proxy-groups")
+            .and_then(Value::as_sequence)
+            .and_then(|groups| groups[0].get("proxies"))
+            .expect("group should have proxies");
+        assert_eq!(
+            group_proxies,
+            &Value::Sequence(vec![Value::String("HK 01 udp".into())])
+        );
+    }
+
+    #[test]
+    #[allow(clippy::expect_used)]
+    fn test_rename_references_and_duplicates() {
+        let config_str = r#"
+proxies:
+- { name: "HK 01", type: ss, server: hk.example.com, port: 443 }
+- { name: "HK 02", type: ss, server: hk.example.com, port: 443 }
+- { name: "relay", type: ss, server: relay.example.com, port: 443, dialer-proxy: "HK 01" }
+- { name: "chain", type: ss, server: chain.example.com, port: 443, dialer-proxy: "auto" }
+proxy-groups:
+- { name: auto, type: url-test, proxies: ["HK 01", "HK 02"] }
+- { name: old, type: select, proxies: ["HK 01"] }
+- { name: select, type: select, proxies: ["auto", "old", "relay"] }
+"#;
+        let config: Mapping =
+            serde_yaml_ng::from_str(config_str).expect("Failed to parse test config YAML");
+
+        let seq: SeqMap = serde_yaml_ng::from_str(
+            r#"
+prepend: []
+append: []
+delete: []
+modify:
+  - match: { server: "^hk\\." }
+    set: { name: "HK" }
+  - match: { name: "^HK 01$" }
+    set: { name: "Hong Kong 01" }
+"#,
+        )
+        .expect("Failed to parse seq map");
+        let config = use_seq(seq, config, "proxies");
+        let proxies = config
+            .get("proxies")
+            .and_then(Value::as_sequence)
+            .expect("proxies should be a sequence");
+        let names: Vec<_> = proxies.iter().filter_map(item_name).collect();
+        assert_eq!(names, ["Hong Kong 01", "HK 02", "relay", "chain"]);
+        assert_eq!(
+            proxies[2].get("dialer-proxy").and_then(Value::as_str),
+            Some("Hong Kong 01")
+        );
+
+        let seq: SeqMap = serde_yaml_ng::from_str(
+            r#"
+prepend: []
+append: []
+delete: []
+delete-match:
+  - name: "^old$"
+modify:
+  - match: { name: "^auto$" }
+    set: { name: "fastest" }
+"#,
+        )
+        .expect("Failed to parse seq map");
+        let config = use_seq(seq, config, "proxy-groups");
+        let group_proxies = |index: usize| {
+            config
+                .get("proxy-groups")
+                .and_then(Value::as_sequence)
+                .and_then(|groups| groups[index].get("proxies"))
+                .and_then(Value::as_sequence)
+                .map(|proxies| proxies.iter().filter_map(Value::as_str).collect::<Vec<_>>())
+                .expect("group should have proxies")
+        };
+        assert_eq!(group_proxies(0), ["Hong Kong 01", "HK 02"]);
+        assert_eq!(group_proxies(1), ["fastest", "relay"]);
+        let proxies = config
+            .get("proxies")
+            .and_then(Value::as_sequence)
+            .expect("proxies should be a sequence");
+        assert_eq!(
+            proxies[3].get("dialer-proxy").and_then(Value::as_str),
+            Some("fastest")
+        );
+    }
 }
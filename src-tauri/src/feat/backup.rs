@@ -1,10 +1,14 @@
+use super::restore::{BackupArchive, RestoreOptions, RestorePlan, apply_restore, plan_restore};
 use crate::{
-    config::{Config, IVerge},
-    core::backup,
+    config::{Config, IBackupTarget, IVerge},
+    core::{
+        backup,
+        backup_retention::{self, RetentionPolicy},
+        backup_target::{self, BackupFileInfo, BackupTarget, LocalDirTarget},
+    },
     logging, logging_error,
-    process::AsyncHandler,
     utils::{
-        dirs::{PathBufExec as _, app_home_dir, local_backup_dir},
+        dirs::{PathBufExec as _, local_backup_dir},
         logging::Type,
     },
 };
@@ -16,6 +20,13 @@ use smartstring::alias::String;
 use std::path::PathBuf;
 use tokio::fs;
 
+/// Backups removed, or about to be removed, by the retention policy
+#[derive(Debug, Default, Serialize)]
+pub struct RetentionPreview {
+    pub local: Vec<BackupFileInfo>,
+    pub webdav: Vec<BackupFileInfo>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct LocalBackupFile {
     pub filename: String,
@@ -24,27 +35,45 @@ pub struct LocalBackupFile {
     pub content_length: u64,
 }
 
-/// Create a backup and upload to WebDAV
-pub async fn create_backup_and_upload_webdav() -> Result<()> {
+/// Create a backup in the temp dir, hand it to `upload` and remove it afterwards
+async fn with_temp_backup<F, Fut>(upload: F) -> Result<()>
+where
+    F: FnOnce(PathBuf, String) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
     let (file_name, temp_file_path) = backup::create_backup().await.map_err(|err| {
         logging!(error, Type::Backup, "Failed to create backup: {err:#?}");
         err
     })?;
 
-    if let Err(err) = backup::WebDavClient::global()
-        .upload(temp_file_path.clone(), file_name)
-        .await
-    {
-        logging!(error, Type::Backup, "Failed to upload to WebDAV: {err:#?}");
-        // 上传失败时重置客户端缓存
-        backup::WebDavClient::global().reset();
-        return Err(err);
-    }
+    let result = upload(temp_file_path.clone(), file_name).await;
 
     if let Err(err) = temp_file_path.remove_if_exists().await {
         logging!(warn, Type::Backup, "Failed to remove temp file: {err:#?}");
     }
+    result
+}
+
+/// Create a backup and upload to WebDAV
+pub async fn create_backup_and_upload_webdav() -> Result<()> {
+    with_temp_backup(|path, file_name| async move {
+        let client = backup::WebDavClient::global();
+        client.upload(path, file_name).await.map_err(|err| {
+            logging!(error, Type::Backup, "Failed to upload to WebDAV: {err:#?}");
+            // 上传失败时重置客户端缓存
+            client.reset();
+            err
+        })
+    })
+    .await?;
 
+    if let Err(err) = apply_webdav_retention().await {
+        logging!(
+            warn,
+            Type::Backup,
+            "Failed to apply retention to WebDAV backups: {err:#?}"
+        );
+    }
     Ok(())
 }
 
@@ -75,50 +104,216 @@ pub async fn delete_webdav_backup(filename: String) -> Result<()> {
         })
 }
 
-/// Restore WebDAV backup
-pub async fn restore_webdav_backup(filename: String) -> Result<()> {
-    let verge = Config::verge().await;
-    let verge_data = verge.latest_arc();
-    let webdav_url = verge_data.webdav_url.clone();
-    let webdav_username = verge_data.webdav_username.clone();
-    let webdav_password = verge_data.webdav_password.clone();
-
-    let backup_storage_path = app_home_dir()
-        .map_err(|e| anyhow::anyhow!("Failed to get app home dir: {e}"))?
-        .join(filename.as_str());
-    backup::WebDavClient::global()
+/// Download a backup from `target` to a temp file and open it, the file is always removed
+async fn open_remote_backup(
+    target: &dyn BackupTarget,
+    filename: String,
+    passphrase: Option<String>,
+) -> Result<BackupArchive> {
+    backup_target::checked_file_name(&filename)?;
+    // 不放在应用目录下，以免与配置文件重名时覆盖它们
+    let backup_storage_path =
+        std::env::temp_dir().join(format!("verge-restore-{}", nanoid::nanoid!()));
+    target
         .download(filename, backup_storage_path.clone())
         .await
         .map_err(|err| {
             logging!(
                 error,
                 Type::Backup,
-                "Failed to download WebDAV backup file: {err:#?}"
+                "Failed to download backup file: {err:#?}"
             );
             err
         })?;
 
-    // extract zip file
-    let value = backup_storage_path.clone();
-    let file = AsyncHandler::spawn_blocking(move || std::fs::File::open(&value)).await??;
-    let mut zip = zip::ZipArchive::new(file)?;
-    zip.extract(app_home_dir()?)?;
-    logging_error!(
-        Type::Backup,
-        super::patch_verge(
-            &IVerge {
-                webdav_url,
-                webdav_username,
-                webdav_password,
-                ..IVerge::default()
-            },
-            false
+    // 口令错误时也要删除临时文件
+    let archive = open_backup(backup_storage_path.clone(), passphrase).await;
+    backup_storage_path.remove_if_exists().await?;
+    archive
+}
+
+/// Decrypt the backup if needed and read its files
+async fn open_backup(path: PathBuf, passphrase: Option<String>) -> Result<BackupArchive> {
+    let data = backup::read_backup(path, passphrase).await.map_err(|err| {
+        logging!(error, Type::Backup, "Failed to open backup file: {err:#?}");
+        err
+    })?;
+    BackupArchive::from_zip(data).await
+}
+
+/// Restore an opened backup, keeping the current WebDAV config, backup passphrase, targets
+/// and REST API token
+async fn restore_archive(archive: BackupArchive, options: &RestoreOptions) -> Result<RestorePlan> {
+    let (
+        webdav_url,
+        webdav_username,
+        webdav_password,
+        backup_passphrase,
+        backup_targets,
+        rest_api_token,
+    ) = {
+        let verge = Config::verge().await;
+        let verge = verge.latest_arc();
+        (
+            verge.webdav_url.clone(),
+            verge.webdav_username.clone(),
+            verge.webdav_password.clone(),
+            verge.backup_passphrase.clone(),
+            verge.backup_targets.clone(),
+            verge.rest_api_token.clone(),
         )
+    };
+
+    let plan = apply_restore(archive, options).await?;
+    if options.verge {
+        logging_error!(
+            Type::Backup,
+            super::patch_verge(
+                &IVerge {
+                    webdav_url,
+                    webdav_username,
+                    webdav_password,
+                    backup_passphrase,
+                    backup_targets,
+                    rest_api_token,
+                    ..IVerge::default()
+                },
+                false
+            )
+            .await
+        );
+    }
+    Ok(plan)
+}
+
+/// Preview what restoring a WebDAV backup would change
+pub async fn preview_webdav_backup_restore(
+    filename: String,
+    passphrase: Option<String>,
+    options: RestoreOptions,
+) -> Result<RestorePlan> {
+    let archive = open_remote_backup(backup::WebDavClient::global(), filename, passphrase).await?;
+    plan_restore(&archive, &options).await
+}
+
+/// Restore WebDAV backup
+pub async fn restore_webdav_backup(
+    filename: String,
+    passphrase: Option<String>,
+    options: RestoreOptions,
+) -> Result<RestorePlan> {
+    let archive = open_remote_backup(backup::WebDavClient::global(), filename, passphrase).await?;
+    restore_archive(archive, &options).await
+}
+
+async fn find_backup_target(uid: &str) -> Result<IBackupTarget> {
+    Config::verge()
         .await
-    );
-    // 最后删除临时文件
-    backup_storage_path.remove_if_exists().await?;
-    Ok(())
+        .latest_arc()
+        .backup_targets
+        .iter()
+        .flatten()
+        .find(|target| target.uid.as_deref() == Some(uid))
+        .cloned()
+        .ok_or_else(|| anyhow!("Backup target not found: {}", uid))
+}
+
+/// Create a backup and push it to a configured backup target
+pub async fn create_target_backup(uid: String) -> Result<()> {
+    let config = find_backup_target(&uid).await?;
+    with_temp_backup(|path, file_name| async move {
+        backup_target::push_backup(&config, path, file_name)
+            .await
+            .map_err(|err| {
+                logging!(
+                    error,
+                    Type::Backup,
+                    "Failed to upload to backup target {}: {err:#?}",
+                    uid
+                );
+                err
+            })
+    })
+    .await
+}
+
+/// List backups on a configured backup target
+pub async fn list_target_backup(uid: String) -> Result<Vec<BackupFileInfo>> {
+    let target = backup_target::build_target(&find_backup_target(&uid).await?)?;
+    target.list().await
+}
+
+/// Delete a backup from a configured backup target
+pub async fn delete_target_backup(uid: String, filename: String) -> Result<()> {
+    let target = backup_target::build_target(&find_backup_target(&uid).await?)?;
+    target.delete(filename).await
+}
+
+/// Preview what restoring a backup from a configured backup target would change
+pub async fn preview_target_backup_restore(
+    uid: String,
+    filename: String,
+    passphrase: Option<String>,
+    options: RestoreOptions,
+) -> Result<RestorePlan> {
+    let target = backup_target::build_target(&find_backup_target(&uid).await?)?;
+    let archive = open_remote_backup(target.as_ref(), filename, passphrase).await?;
+    plan_restore(&archive, &options).await
+}
+
+/// Restore a backup from a configured backup target
+pub async fn restore_target_backup(
+    uid: String,
+    filename: String,
+    passphrase: Option<String>,
+    options: RestoreOptions,
+) -> Result<RestorePlan> {
+    let target = backup_target::build_target(&find_backup_target(&uid).await?)?;
+    let archive = open_remote_backup(target.as_ref(), filename, passphrase).await?;
+    restore_archive(archive, &options).await
+}
+
+/// The configured retention policy and whether WebDAV backups are set up
+async fn retention_settings() -> (RetentionPolicy, bool) {
+    let verge = Config::verge().await.latest_arc();
+    let webdav = verge.webdav_url.as_ref().is_some_and(|url| !url.is_empty());
+    (RetentionPolicy::from_verge(&verge), webdav)
+}
+
+/// Apply the retention policy to the local backup dir, a no-op when no policy is configured
+pub async fn apply_local_retention() -> Result<Vec<BackupFileInfo>> {
+    let (policy, _) = retention_settings().await;
+    backup_retention::apply(&LocalDirTarget::new(local_backup_dir()?), &policy).await
+}
+
+/// Apply the retention policy to the WebDAV backup dir, a no-op when no policy is configured
+pub async fn apply_webdav_retention() -> Result<Vec<BackupFileInfo>> {
+    let (policy, webdav) = retention_settings().await;
+    if !webdav {
+        return Ok(vec![]);
+    }
+    backup_retention::apply(backup::WebDavClient::global(), &policy).await
+}
+
+/// List the local and WebDAV backups the retention policy would delete, without deleting them
+pub async fn preview_backup_retention() -> Result<RetentionPreview> {
+    let (policy, webdav) = retention_settings().await;
+    let local =
+        backup_retention::preview(&LocalDirTarget::new(local_backup_dir()?), &policy).await?;
+    let webdav = if webdav {
+        backup_retention::preview(backup::WebDavClient::global(), &policy).await?
+    } else {
+        vec![]
+    };
+    Ok(RetentionPreview { local, webdav })
+}
+
+/// Delete the local and WebDAV backups that fall outside the retention policy
+pub async fn apply_backup_retention() -> Result<RetentionPreview> {
+    Ok(RetentionPreview {
+        local: apply_local_retention().await?,
+        webdav: apply_webdav_retention().await?,
+    })
 }
 
 /// Create a backup and save to local storage
@@ -243,41 +438,32 @@ pub async fn delete_local_backup(filename: String) -> Result<()> {
     Ok(())
 }
 
-/// Restore local backup
-pub async fn restore_local_backup(filename: String) -> Result<()> {
-    let backup_dir = local_backup_dir()?;
-    let target_path = backup_dir.join(filename.as_str());
+fn local_backup_path(filename: &str) -> Result<PathBuf> {
+    let target_path = local_backup_dir()?.join(filename);
     if !target_path.exists() {
         return Err(anyhow!("Backup file not found: {}", filename));
     }
+    Ok(target_path)
+}
 
-    let (webdav_url, webdav_username, webdav_password) = {
-        let verge = Config::verge().await;
-        let verge = verge.latest_arc();
-        (
-            verge.webdav_url.clone(),
-            verge.webdav_username.clone(),
-            verge.webdav_password.clone(),
-        )
-    };
+/// Preview what restoring a local backup would change
+pub async fn preview_local_backup_restore(
+    filename: String,
+    passphrase: Option<String>,
+    options: RestoreOptions,
+) -> Result<RestorePlan> {
+    let archive = open_backup(local_backup_path(&filename)?, passphrase).await?;
+    plan_restore(&archive, &options).await
+}
 
-    let file = AsyncHandler::spawn_blocking(move || std::fs::File::open(&target_path)).await??;
-    let mut zip = zip::ZipArchive::new(file)?;
-    zip.extract(app_home_dir()?)?;
-    logging_error!(
-        Type::Backup,
-        super::patch_verge(
-            &IVerge {
-                webdav_url,
-                webdav_username,
-                webdav_password,
-                ..IVerge::default()
-            },
-            false
-        )
-        .await
-    );
-    Ok(())
+/// Restore local backup
+pub async fn restore_local_backup(
+    filename: String,
+    passphrase: Option<String>,
+    options: RestoreOptions,
+) -> Result<RestorePlan> {
+    let archive = open_backup(local_backup_path(&filename)?, passphrase).await?;
+    restore_archive(archive, &options).await
 }
 
 /// Export local backup file to user selected destination
@@ -1,9 +1,9 @@
 use crate::{
     config::{Config, IVerge},
-    core::{CoreManager, handle, hotkey, sysopt, tray},
+    core::{CoreManager, Timer, handle, hotkey, sysopt, tray},
     logging_error,
-    module::{auto_backup::AutoBackupManager, lightweight},
-    utils::{draft::SharedBox, logging::Type},
+    module::{auto_backup::AutoBackupManager, lightweight, traffic_stats::TrafficStats},
+    utils::{draft::SharedBox, logging::Type, server},
 };
 use anyhow::Result;
 use serde_yaml_ng::Mapping;
@@ -67,6 +67,10 @@ enum UpdateFlags {
     SystrayTooltip = 1 << 8,
     SystrayClickBehavior = 1 << 9,
     LighteWeight = 1 << 10,
+    PacServer = 1 << 11,
+    UnlockCheck = 1 << 12,
+    TraySpeed = 1 << 13,
+    TrafficStats = 1 << 14,
 }
 
 fn determine_update_flags(patch: &IVerge) -> i32 {
@@ -155,11 +159,16 @@ fn determine_update_flags(patch: &IVerge) -> i32 {
         || sysproxy_tray_icon.is_some()
         || tun_tray_icon.is_some()
         || tray_icon.is_some()
-        || enable_tray_speed.is_some()
     // || enable_tray_icon.is_some()
     {
         update_flags |= UpdateFlags::SystrayIcon as i32;
     }
+    if enable_tray_speed.is_some() {
+        update_flags |= UpdateFlags::TraySpeed as i32;
+    }
+    if patch.enable_traffic_stats.is_some() {
+        update_flags |= UpdateFlags::TrafficStats as i32;
+    }
 
     if patch.hotkeys.is_some() {
         update_flags |= UpdateFlags::Hotkey as i32;
@@ -182,6 +191,14 @@ fn determine_update_flags(patch: &IVerge) -> i32 {
         update_flags |= UpdateFlags::SystrayMenu as i32;
     }
 
+    if patch.pac_lan_enabled.is_some() || patch.pac_lan_port.is_some() {
+        update_flags |= UpdateFlags::PacServer as i32;
+    }
+
+    if patch.unlock_check_interval.is_some() || patch.unlock_check_group.is_some() {
+        update_flags |= UpdateFlags::UnlockCheck as i32;
+    }
+
     update_flags
 }
 
@@ -234,6 +251,18 @@ async fn process_terminated_flags(update_flags: i32, patch: &IVerge) -> Result<(
             lightweight::disable_auto_light_weight_mode();
         }
     }
+    if (update_flags & (UpdateFlags::PacServer as i32)) != 0 {
+        server::restart_pac_lan_server().await?;
+    }
+    if (update_flags & (UpdateFlags::UnlockCheck as i32)) != 0 {
+        Timer::global().refresh_unlock_check().await?;
+    }
+    if (update_flags & (UpdateFlags::TraySpeed as i32)) != 0 {
+        tray::speed_rate::SpeedRate::global().refresh().await;
+    }
+    if (update_flags & (UpdateFlags::TrafficStats as i32)) != 0 {
+        TrafficStats::global().refresh_settings().await?;
+    }
     Ok(())
 }
 
@@ -3,6 +3,7 @@ mod clash;
 mod config;
 mod profile;
 mod proxy;
+mod restore;
 mod window;
 
 // Re-export all functions from modules
@@ -11,4 +12,5 @@ pub use clash::*;
 pub use config::*;
 pub use profile::*;
 pub use proxy::*;
+pub use restore::*;
 pub use window::*;
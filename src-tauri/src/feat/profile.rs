@@ -1,13 +1,21 @@
 use crate::{
     cmd,
-    config::{Config, PrfItem, PrfOption, profiles::profiles_draft_update_item_safe},
-    core::{CoreManager, handle, tray},
+    config::{Config, PrfDiff, PrfItem, PrfOption, profiles::profiles_draft_update_item_safe},
+    core::{
+        CoreManager, Timer, handle,
+        node_route::NodeRoute,
+        tray,
+        update_policy::{UpdatePolicy, UpdateTransport},
+    },
     logging, logging_error,
     utils::logging::Type,
 };
-use anyhow::{Result, bail};
+use anyhow::{Result, anyhow, bail};
+use backoff::backoff::Backoff as _;
+use serde_yaml_ng::Mapping;
 use smartstring::alias::String;
 use tauri::Emitter as _;
+use tokio::time::sleep;
 
 /// Toggle proxy profile
 pub async fn toggle_proxy_profile(profile_index: String) {
@@ -131,6 +139,57 @@ async fn should_update_profile(
     }
 }
 
+/// 对比新旧订阅内容
+async fn compute_profile_diff(uid: &String, item: &PrfItem) -> Option<PrfDiff> {
+    let new_config = serde_yaml_ng::from_str::<Mapping>(item.file_data.as_ref()?).ok()?;
+    let old_item = {
+        let profiles = Config::profiles().await;
+        let profiles = profiles.latest_arc();
+        profiles.get_item(uid).ok()?.clone()
+    };
+    let old_data = old_item.read_file().await.ok()?;
+    let old_config = serde_yaml_ng::from_str::<Mapping>(&old_data).ok()?;
+    Some(PrfDiff::compute(&old_config, &new_config))
+}
+
+/// 写入新的订阅内容，并记录和通知订阅变化
+async fn apply_profile_update(
+    uid: &String,
+    item: &mut PrfItem,
+    profile_name: &String,
+) -> Result<()> {
+    let diff = compute_profile_diff(uid, item)
+        .await
+        .filter(|diff| !diff.is_empty());
+    if let Some(diff) = diff.as_ref() {
+        item.diffs = Some(vec![diff.clone()]);
+    }
+
+    profiles_draft_update_item_safe(uid, item).await?;
+    // 间隔更新从本次更新时间重新计时，手动更新也会推迟下一次自动更新
+    if let Some(updated) = item.updated {
+        Timer::global()
+            .reschedule_after_update(uid, updated as i64)
+            .await;
+    }
+
+    if let Some(diff) = diff {
+        let summary = diff.summary();
+        logging!(
+            info,
+            Type::Config,
+            "[订阅更新] {} 订阅内容变化: {}",
+            uid,
+            summary
+        );
+        handle::Handle::notice_message(
+            "update_profile::diff",
+            format!("{profile_name}: {summary}"),
+        );
+    }
+    Ok(())
+}
+
 async fn perform_profile_update(
     uid: &String,
     url: &String,
@@ -138,91 +197,160 @@ async fn perform_profile_update(
     option: Option<&PrfOption>,
 ) -> Result<bool> {
     logging!(info, Type::Config, "[订阅更新] 开始下载新的订阅内容");
-    let mut merged_opt = PrfOption::merge(opt, option);
+    let merged_opt = PrfOption::merge(opt, option).unwrap_or_default();
+    let policy = UpdatePolicy::from_option(Some(&merged_opt))?;
     let is_current = {
         let profiles = Config::profiles().await;
         profiles.latest_arc().is_current_profile_index(uid)
     };
-    let profiles = Config::profiles().await;
-    let profiles_arc = profiles.latest_arc();
-    let profile_name = profiles_arc
-        .get_name_by_uid(uid)
-        .cloned()
-        .unwrap_or_else(|| String::from("UnKown Profile"));
-
-    let mut last_err;
+    let (profile_name, was_stale, cached) = {
+        let profiles = Config::profiles().await;
+        let profiles_arc = profiles.latest_arc();
+        let profile_name = profiles_arc
+            .get_name_by_uid(uid)
+            .cloned()
+            .unwrap_or_else(|| String::from("UnKown Profile"));
+        let cached = profiles_arc.get_item(uid).ok().cloned();
+        let was_stale = cached
+            .as_ref()
+            .is_some_and(|item| item.stale.unwrap_or(false));
+        (profile_name, was_stale, cached)
+    };
 
-    match PrfItem::from_url(url, None, None, merged_opt.as_ref()).await {
-        Ok(mut item) => {
-            logging!(info, Type::Config, "[订阅更新] 更新订阅配置成功");
-            profiles_draft_update_item_safe(uid, &mut item).await?;
-            return Ok(is_current);
-        }
-        Err(err) => {
-            logging!(
-                warn,
-                Type::Config,
-                "Warning: [订阅更新] 正常更新失败: {err}，尝试使用Clash代理更新"
-            );
-            last_err = err;
+    let mut last_err = anyhow!("no update transport configured");
+    for (index, transport) in policy.transports.iter().enumerate() {
+        match fetch_with_retry(url, &merged_opt, transport, &policy, cached.as_ref()).await {
+            Ok(mut item) => {
+                logging!(
+                    info,
+                    Type::Config,
+                    "[订阅更新] 使用 {} 更新订阅配置成功",
+                    transport
+                );
+                let unchanged = item.file_data.is_none();
+                apply_profile_update(uid, &mut item, &profile_name).await?;
+                if index > 0 {
+                    handle::Handle::notice_message("update_with_clash_proxy", profile_name);
+                }
+                if was_stale {
+                    let _ = tray::Tray::global().update_menu().await;
+                }
+                if unchanged {
+                    logging!(
+                        info,
+                        Type::Config,
+                        "[订阅更新] 订阅内容未变化，跳过重新加载"
+                    );
+                }
+                return Ok(is_current && !unchanged);
+            }
+            Err(err) => {
+                logging!(
+                    warn,
+                    Type::Config,
+                    "Warning: [订阅更新] 使用 {} 更新失败: {err}",
+                    transport
+                );
+                last_err = err;
+            }
         }
     }
 
-    merged_opt.get_or_insert_with(PrfOption::default).self_proxy = Some(true);
-    merged_opt.get_or_insert_with(PrfOption::default).with_proxy = Some(false);
+    record_update_failure(uid, &profile_name, policy.stale_after).await;
+    handle::Handle::notice_message(
+        "update_failed_even_with_clash",
+        format!("{profile_name} - {last_err}"),
+    );
+    Ok(is_current)
+}
 
-    match PrfItem::from_url(url, None, None, merged_opt.as_ref()).await {
-        Ok(mut item) => {
-            logging!(
-                info,
-                Type::Config,
-                "[订阅更新] 使用 Clash代理 更新订阅配置成功"
-            );
-            profiles_draft_update_item_safe(uid, &mut item).await?;
-            handle::Handle::notice_message("update_with_clash_proxy", profile_name);
-            drop(last_err);
-            return Ok(is_current);
-        }
-        Err(err) => {
-            logging!(
-                warn,
-                Type::Config,
-                "Warning: [订阅更新] 正常更新失败: {err}，尝试使用Clash代理更新"
-            );
-            last_err = err;
+/// Download the subscription through `transport`, retrying with exponential backoff
+async fn fetch_with_retry(
+    url: &String,
+    option: &PrfOption,
+    transport: &UpdateTransport,
+    policy: &UpdatePolicy,
+    cached: Option<&PrfItem>,
+) -> Result<PrfItem> {
+    let mut option = option.clone();
+    transport.apply(&mut option);
+
+    let mut backoff = policy.backoff();
+    let mut attempt = 0;
+    loop {
+        let result = match transport {
+            UpdateTransport::Core { group, node } => {
+                fetch_through_route(url, &option, node.as_deref().unwrap_or(group), cached).await
+            }
+            _ => PrfItem::from_url_cached(url, Some(&option), cached).await,
+        };
+        match result {
+            Ok(item) => return Ok(item),
+            Err(err) if attempt < policy.retries => {
+                attempt += 1;
+                let delay = backoff.next_backoff().unwrap_or(backoff.max_interval);
+                logging!(
+                    info,
+                    Type::Config,
+                    "[订阅更新] 使用 {} 第 {} 次重试，等待 {:?}: {err}",
+                    transport,
+                    attempt,
+                    delay
+                );
+                sleep(delay).await;
+            }
+            Err(err) => return Err(err),
         }
     }
+}
 
-    merged_opt.get_or_insert_with(PrfOption::default).self_proxy = Some(false);
-    merged_opt.get_or_insert_with(PrfOption::default).with_proxy = Some(true);
+/// Download through the node route with `target` selected in it.
+/// The route is only held for the attempt, not across the backoff
+async fn fetch_through_route(
+    url: &String,
+    option: &PrfOption,
+    target: &str,
+    cached: Option<&PrfItem>,
+) -> Result<PrfItem> {
+    let route = NodeRoute::open().await?;
+    route.select(target).await?;
+    let option = PrfOption {
+        proxy_port: Some(route.port()),
+        ..option.clone()
+    };
+    PrfItem::from_url_cached(url, Some(&option), cached).await
+}
 
-    match PrfItem::from_url(url, None, None, merged_opt.as_ref()).await {
-        Ok(mut item) => {
+/// Count the failed update, notifying when the subscription becomes stale
+async fn record_update_failure(uid: &String, profile_name: &String, stale_after: u32) {
+    let result = Config::profiles()
+        .await
+        .with_data_modify(|mut profiles| async move {
+            let became_stale = profiles.record_update_failure(uid, stale_after).await?;
+            Ok((profiles, became_stale))
+        })
+        .await;
+    match result {
+        Ok(true) => {
             logging!(
-                info,
+                warn,
                 Type::Config,
-                "[订阅更新] 使用 系统代理 更新订阅配置成功"
+                "[订阅更新] {} 连续更新失败 {} 次，标记为过期",
+                uid,
+                stale_after
             );
-            profiles_draft_update_item_safe(uid, &mut item).await?;
-            handle::Handle::notice_message("update_with_clash_proxy", profile_name);
-            drop(last_err);
-            return Ok(is_current);
+            handle::Handle::notice_message("update_profile::stale", profile_name.clone());
+            let _ = tray::Tray::global().update_menu().await;
         }
+        Ok(false) => {}
         Err(err) => {
             logging!(
                 warn,
                 Type::Config,
-                "Warning: [订阅更新] 正常更新失败: {err}，尝试使用系统代理更新"
+                "Warning: [订阅更新] 记录更新失败次数失败: {err}"
             );
-            last_err = err;
         }
     }
-
-    handle::Handle::notice_message(
-        "update_failed_even_with_clash",
-        format!("{profile_name} - {last_err}"),
-    );
-    Ok(is_current)
 }
 
 pub async fn update_profile(
@@ -0,0 +1,409 @@
+use crate::{
+    config::{Config, IProfiles, PrfItem, ProfileHistory, RevisionReason},
+    constants::files::DNS_CONFIG,
+    core::backup_manifest::{self, BackupManifest},
+    logging,
+    process::AsyncHandler,
+    utils::{
+        dirs::{self, app_home_dir},
+        logging::Type,
+    },
+};
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use smartstring::alias::String;
+use std::{
+    collections::{BTreeMap, HashSet},
+    env::consts::OS,
+    io::{Cursor, Read as _},
+};
+use tokio::fs;
+
+const PROFILES_PREFIX: &str = "profiles/";
+
+/// Parts of a backup to restore
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RestoreOptions {
+    /// profile files, merged into `profiles.yaml`
+    pub profiles: bool,
+    /// only restore these profiles and their chain items, all profiles when empty
+    pub profile_uids: Vec<String>,
+    /// `verge.yaml`
+    pub verge: bool,
+    /// the clash `config.yaml`
+    pub clash: bool,
+    /// `dns_config.yaml`
+    pub dns: bool,
+}
+
+impl RestoreOptions {
+    pub fn all() -> Self {
+        Self {
+            profiles: true,
+            profile_uids: vec![],
+            verge: true,
+            clash: true,
+            dns: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreAction {
+    /// the file does not exist yet
+    Add,
+    /// the file exists with different content
+    Overwrite,
+    /// the file exists with the same content
+    Unchanged,
+    /// merged into the current file instead of replacing it
+    Merge,
+    /// not selected, left alone
+    Skip,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreEntry {
+    pub path: String,
+    pub action: RestoreAction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreProfile {
+    pub uid: String,
+    pub name: Option<String>,
+    pub itype: Option<String>,
+    pub selected: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RestorePlan {
+    pub entries: Vec<RestoreEntry>,
+    /// profiles found in the backup
+    pub profiles: Vec<RestoreProfile>,
+    /// file name of the local backup made before restoring
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub safety_backup: Option<String>,
+    /// `None` for backups made before manifests were added
+    pub manifest: Option<BackupManifest>,
+}
+
+/// Files of an opened and verified backup archive, keyed by their path inside the zip
+pub struct BackupArchive {
+    files: BTreeMap<String, Vec<u8>>,
+    manifest: Option<BackupManifest>,
+}
+
+impl BackupArchive {
+    pub async fn from_zip(data: Vec<u8>) -> Result<Self> {
+        let mut files = AsyncHandler::spawn_blocking(move || {
+            let mut zip = zip::ZipArchive::new(Cursor::new(data))?;
+            let mut files: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+            for i in 0..zip.len() {
+                let mut file = zip.by_index(i)?;
+                if file.is_dir() {
+                    continue;
+                }
+                // 拒绝越出应用目录的路径
+                let Some(name) = file
+                    .enclosed_name()
+                    .and_then(|p| p.to_str().map(|s| s.replace('\\', "/")))
+                else {
+                    bail!("Invalid file path in backup: {}", file.name());
+                };
+                let mut content = Vec::new();
+                file.read_to_end(&mut content)?;
+                files.insert(name.into(), content);
+            }
+            Ok::<_, anyhow::Error>(files)
+        })
+        .await??;
+
+        let manifest = match files.remove(backup_manifest::MANIFEST_FILE) {
+            Some(content) => {
+                let manifest: BackupManifest = serde_json::from_slice(&content)?;
+                manifest.verify(&files)?;
+                Some(manifest)
+            }
+            None => {
+                logging!(
+                    warn,
+                    Type::Backup,
+                    "Backup has no manifest, skipping integrity check"
+                );
+                None
+            }
+        };
+
+        let mut archive = Self { files, manifest };
+        archive.migrate()?;
+        Ok(archive)
+    }
+
+    /// Adapt `verge.yaml` from an older schema or another OS to this platform
+    fn migrate(&mut self) -> Result<()> {
+        if !self
+            .manifest
+            .as_ref()
+            .is_none_or(BackupManifest::needs_migration)
+        {
+            return Ok(());
+        }
+        let Some(content) = self.files.get_mut(dirs::VERGE_CONFIG) else {
+            return Ok(());
+        };
+        let mut verge: serde_yaml_ng::Mapping = serde_yaml_ng::from_slice(content)?;
+        backup_manifest::migrate_verge(&mut verge, OS);
+        *content = serde_yaml_ng::to_string(&verge)?.into_bytes();
+        logging!(info, Type::Backup, "Migrated verge.yaml from the backup");
+        Ok(())
+    }
+
+    fn profiles(&self) -> Result<IProfiles> {
+        match self.files.get(dirs::PROFILE_YAML) {
+            Some(content) => Ok(serde_yaml_ng::from_slice(content)?),
+            None => Ok(IProfiles::default()),
+        }
+    }
+}
+
+/// Items to restore: the chosen uids plus the chain items they refer to, `None` for all
+fn selected_profiles(profiles: &IProfiles, uids: &[String]) -> Option<Vec<PrfItem>> {
+    if uids.is_empty() {
+        return None;
+    }
+    let items = profiles.items.as_deref().unwrap_or_default();
+    let mut wanted: HashSet<&str> = uids.iter().map(String::as_str).collect();
+    for item in items {
+        if let (Some(uid), Some(option)) = (&item.uid, &item.option)
+            && wanted.contains(uid.as_str())
+        {
+            let chain = [
+                &option.merge,
+                &option.script,
+                &option.rules,
+                &option.proxies,
+                &option.groups,
+                &option.rule_providers,
+                &option.proxy_providers,
+            ];
+            wanted.extend(chain.into_iter().flatten().map(String::as_str));
+        }
+    }
+    Some(
+        items
+            .iter()
+            .filter(|item| {
+                item.uid
+                    .as_ref()
+                    .is_some_and(|uid| wanted.contains(uid.as_str()))
+            })
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Work out what restoring `archive` with `options` would change, without touching any file
+pub async fn plan_restore(
+    archive: &BackupArchive,
+    options: &RestoreOptions,
+) -> Result<RestorePlan> {
+    let profiles = archive.profiles()?;
+    let selected = selected_profiles(&profiles, &options.profile_uids);
+    let selected_files: Option<HashSet<&str>> = selected.as_ref().map(|items| {
+        items
+            .iter()
+            .filter_map(|item| item.file.as_deref())
+            .collect()
+    });
+
+    let home_dir = app_home_dir()?;
+    let mut plan = RestorePlan {
+        manifest: archive.manifest.clone(),
+        ..Default::default()
+    };
+    for (path, content) in &archive.files {
+        if path == dirs::PROFILE_YAML {
+            // profiles.yaml 合并到当前配置，不直接覆盖
+            let action = if options.profiles {
+                RestoreAction::Merge
+            } else {
+                RestoreAction::Skip
+            };
+            plan.entries.push(RestoreEntry {
+                path: path.clone(),
+                action,
+            });
+            continue;
+        }
+
+        let wanted = if let Some(file) = path.strip_prefix(PROFILES_PREFIX) {
+            options.profiles
+                && selected_files
+                    .as_ref()
+                    .is_none_or(|files| files.contains(file))
+        } else if path == dirs::VERGE_CONFIG {
+            options.verge
+        } else if path == dirs::CLASH_CONFIG {
+            options.clash
+        } else if path == DNS_CONFIG {
+            options.dns
+        } else {
+            false
+        };
+
+        let action = if !wanted {
+            RestoreAction::Skip
+        } else {
+            match fs::read(home_dir.join(path.as_str())).await {
+                Ok(current) if current == *content => RestoreAction::Unchanged,
+                Ok(_) => RestoreAction::Overwrite,
+                Err(_) => RestoreAction::Add,
+            }
+        };
+        plan.entries.push(RestoreEntry {
+            path: path.clone(),
+            action,
+        });
+    }
+
+    plan.profiles = profiles
+        .items
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|item| {
+            let uid = item.uid?;
+            let is_selected = options.profiles
+                && selected
+                    .as_ref()
+                    .is_none_or(|items| items.iter().any(|s| s.uid.as_ref() == Some(&uid)));
+            Some(RestoreProfile {
+                uid,
+                name: item.name,
+                itype: item.itype,
+                selected: is_selected,
+            })
+        })
+        .collect();
+
+    Ok(plan)
+}
+
+/// Restore the selected parts of `archive` after making a safety backup of the current state
+pub async fn apply_restore(
+    archive: BackupArchive,
+    options: &RestoreOptions,
+) -> Result<RestorePlan> {
+    let mut plan = plan_restore(&archive, options).await?;
+
+    let safety_backup = super::create_local_backup_with_namer(|name| {
+        match name.rsplit_once('.') {
+            Some((stem, ext)) => format!("{stem}-pre-restore.{ext}"),
+            None => format!("{name}-pre-restore"),
+        }
+        .into()
+    })
+    .await?;
+    logging!(
+        info,
+        Type::Backup,
+        "Safety backup created before restore: {}",
+        safety_backup
+    );
+    plan.safety_backup = Some(safety_backup);
+
+    let home_dir = app_home_dir()?;
+    for entry in &plan.entries {
+        if !matches!(entry.action, RestoreAction::Add | RestoreAction::Overwrite) {
+            continue;
+        }
+        let Some(content) = archive.files.get(&entry.path) else {
+            continue;
+        };
+        let target = home_dir.join(entry.path.as_str());
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        if let Some(file) = entry.path.strip_prefix(PROFILES_PREFIX)
+            && let Ok(data) = std::str::from_utf8(content)
+        {
+            let previous = fs::read_to_string(&target).await.ok();
+            if let Err(e) =
+                ProfileHistory::record(file, previous.as_deref(), data, RevisionReason::Restore)
+                    .await
+            {
+                logging!(warn, Type::Backup, "Warning: 记录恢复历史版本失败: {e}");
+            }
+        }
+        fs::write(&target, content).await?;
+    }
+
+    if options.profiles {
+        let profiles = archive.profiles()?;
+        let restored = selected_profiles(&profiles, &options.profile_uids)
+            .unwrap_or_else(|| profiles.items.clone().unwrap_or_default());
+        let current = profiles.current;
+        Config::profiles()
+            .await
+            .with_data_modify(|mut profiles| async move {
+                profiles.merge_items(restored, current);
+                profiles.save_file().await?;
+                Ok((profiles, ()))
+            })
+            .await?;
+    }
+
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PrfOption;
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn test_selected_profiles() {
+        let item = |uid: &str, option: Option<PrfOption>| PrfItem {
+            uid: Some(uid.into()),
+            file: Some(format!("{uid}.yaml").into()),
+            option,
+            ..Default::default()
+        };
+        let profiles = IProfiles {
+            current: Some("r1".into()),
+            items: Some(vec![
+                item(
+                    "r1",
+                    Some(PrfOption {
+                        merge: Some("m1".into()),
+                        ..Default::default()
+                    }),
+                ),
+                item("r2", None),
+                item("m1", None),
+            ]),
+        };
+
+        let uids = |items: Vec<PrfItem>| -> Vec<std::string::String> {
+            items
+                .into_iter()
+                .filter_map(|item| item.uid.map(|uid| uid.to_string()))
+                .collect()
+        };
+
+        assert!(selected_profiles(&profiles, &[]).is_none());
+        let selected = selected_profiles(&profiles, &["r1".into()]).unwrap();
+        assert_eq!(uids(selected), vec!["r1", "m1"]);
+
+        let mut current = IProfiles {
+            current: None,
+            items: Some(vec![item("r2", None), item("m1", None)]),
+        };
+        current.merge_items(profiles.items.unwrap(), profiles.current);
+        assert_eq!(current.current.as_deref(), Some("r1"));
+        assert_eq!(uids(current.items.unwrap()), vec!["r2", "m1", "r1"]);
+    }
+}
@@ -27,6 +27,8 @@ use tauri_plugin_autostart::MacosLauncher;
 use tauri_plugin_deep_link::DeepLinkExt as _;
 use utils::logging::Type;
 
+pub use crate::enhance::{SCRIPT_WORKER_ARG, run_script_worker};
+
 i18n!("locales", fallback = "zh");
 
 pub static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
@@ -159,6 +161,7 @@ mod app_init {
         tauri::generate_handler![
             cmd::get_sys_proxy,
             cmd::get_auto_proxy,
+            cmd::get_pac_preview,
             cmd::open_app_dir,
             cmd::open_logs_dir,
             cmd::open_web_url,
@@ -194,6 +197,7 @@ mod app_init {
             cmd::get_runtime_yaml,
             cmd::get_runtime_exists,
             cmd::get_runtime_logs,
+            cmd::enhance_dry_run,
             cmd::get_runtime_proxy_chain_config,
             cmd::update_proxy_chain_config_in_runtime,
             cmd::invoke_uwp_tool,
@@ -207,6 +211,7 @@ mod app_init {
             cmd::get_clash_logs,
             cmd::get_verge_config,
             cmd::patch_verge_config,
+            cmd::regenerate_rest_api_token,
             cmd::test_delay,
             cmd::get_app_dir,
             cmd::copy_icon_file,
@@ -229,22 +234,39 @@ mod app_init {
             cmd::read_profile_file,
             cmd::save_profile_file,
             cmd::get_next_update_time,
+            cmd::get_profile_diffs,
+            cmd::list_profile_revisions,
+            cmd::view_profile_revision,
+            cmd::rollback_profile_revision,
             cmd::script_validate_notice,
             cmd::validate_script_file,
             cmd::create_local_backup,
             cmd::list_local_backup,
             cmd::delete_local_backup,
+            cmd::preview_local_backup_restore,
             cmd::restore_local_backup,
             cmd::export_local_backup,
+            cmd::preview_backup_retention,
+            cmd::apply_backup_retention,
             cmd::create_webdav_backup,
             cmd::save_webdav_config,
             cmd::list_webdav_backup,
             cmd::delete_webdav_backup,
+            cmd::preview_webdav_backup_restore,
             cmd::restore_webdav_backup,
+            cmd::create_target_backup,
+            cmd::list_target_backup,
+            cmd::delete_target_backup,
+            cmd::preview_target_backup_restore,
+            cmd::restore_target_backup,
             cmd::export_diagnostic_info,
             cmd::get_system_info,
             cmd::get_unlock_items,
             cmd::check_media_unlock,
+            cmd::check_media_unlock_matrix,
+            cmd::get_unlock_history,
+            cmd::get_traffic_stats,
+            cmd::export_traffic_csv,
         ]
     }
 }
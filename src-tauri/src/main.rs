@@ -1,5 +1,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 fn main() {
+    // 扩展脚本在独立的子进程中执行，不初始化应用
+    if std::env::args().nth(1).as_deref() == Some(app_lib::SCRIPT_WORKER_ARG) {
+        std::process::exit(app_lib::run_script_worker());
+    }
+
     // Output startup info to stderr immediately, so it's visible from command line
     eprintln!("========================================");
     eprintln!("[RV Verge] Rust backend starting...");
@@ -1,6 +1,7 @@
 use crate::{
     config::{Config, IVerge},
-    feat::create_local_backup_with_namer,
+    core::{backup_retention::RetentionPolicy, backup_target},
+    feat::{apply_local_retention, create_local_backup_with_namer},
     logging,
     process::AsyncHandler,
     utils::{dirs::local_backup_dir, logging::Type},
@@ -220,7 +221,7 @@ impl AutoBackupManager {
         self.last_backup
             .store(Local::now().timestamp(), Ordering::Release);
 
-        if let Err(err) = cleanup_auto_backups().await {
+        if let Err(err) = cleanup_backups().await {
             logging!(
                 warn,
                 Type::Backup,
@@ -235,6 +236,8 @@ impl AutoBackupManager {
             trigger,
             file_name
         );
+
+        push_to_targets(&file_name).await;
         Ok(())
     }
 
@@ -260,6 +263,24 @@ fn append_auto_suffix(file_name: &str, slug: &str) -> String {
     }
 }
 
+/// Apply the configured retention policy, or keep the newest auto backups when there is none
+async fn cleanup_backups() -> Result<()> {
+    let policy = RetentionPolicy::from_verge(&Config::verge().await.latest_arc());
+    if policy.is_enabled() {
+        let removed = apply_local_retention().await?;
+        if !removed.is_empty() {
+            logging!(
+                info,
+                Type::Backup,
+                "Removed {} backups by retention policy",
+                removed.len()
+            );
+        }
+        return Ok(());
+    }
+    cleanup_auto_backups().await
+}
+
 async fn cleanup_auto_backups() -> Result<()> {
     if AUTO_BACKUP_KEEP == 0 {
         return Ok(());
@@ -330,3 +351,41 @@ async fn cleanup_auto_backups() -> Result<()> {
 
     Ok(())
 }
+
+/// Push an auto backup to every backup target with `auto_upload` enabled
+async fn push_to_targets(file_name: &str) {
+    let targets = Config::verge()
+        .await
+        .latest_arc()
+        .backup_targets
+        .clone()
+        .unwrap_or_default();
+    let targets = targets
+        .into_iter()
+        .filter(|target| target.auto_upload.unwrap_or(false));
+
+    let backup_dir = match local_backup_dir() {
+        Ok(dir) => dir,
+        Err(err) => {
+            logging!(
+                warn,
+                Type::Backup,
+                "Failed to get local backup dir: {err:#?}"
+            );
+            return;
+        }
+    };
+    let file_path = backup_dir.join(file_name);
+    for target in targets {
+        if let Err(err) =
+            backup_target::push_backup(&target, file_path.clone(), file_name.into()).await
+        {
+            logging!(
+                warn,
+                Type::Backup,
+                "Failed to push auto backup to {}: {err:#?}",
+                target.name.as_deref().unwrap_or_default()
+            );
+        }
+    }
+}
@@ -1,7 +1,13 @@
 use crate::{
     config::Config,
-    core::{handle, timer::Timer, tray::Tray},
+    core::{
+        handle,
+        timer::Timer,
+        tray::{Tray, speed_rate::SpeedRate},
+        update_schedule::UpdateSchedule,
+    },
     log_err, logging,
+    module::traffic_stats::TrafficStats,
     process::AsyncHandler,
     utils::logging::Type,
 };
@@ -82,6 +88,11 @@ async fn refresh_lightweight_tray_state() {
     if let Err(err) = Tray::global().update_menu().await {
         logging!(warn, Type::Lightweight, "更新托盘轻量模式状态失败: {err}");
     }
+    // 轻量模式下暂停托盘网速的流量订阅和流量统计的采样
+    SpeedRate::global().refresh().await;
+    if let Err(err) = TrafficStats::global().refresh_settings().await {
+        logging!(warn, Type::Lightweight, "更新流量统计状态失败: {err}");
+    }
 }
 
 pub async fn auto_lightweight_boot() -> Result<()> {
@@ -251,8 +262,8 @@ async fn setup_light_weight_timer() -> Result<()> {
         let mut timer_map = Timer::global().timer_map.write();
         let timer_task = crate::core::timer::TimerTask {
             task_id,
-            interval_minutes: once_by_minutes,
-            last_run: chrono::Local::now().timestamp(),
+            schedule: UpdateSchedule::interval(once_by_minutes),
+            next_run: chrono::Local::now().timestamp() + once_by_minutes as i64 * 60,
         };
         timer_map.insert(LIGHT_WEIGHT_TASK_UID.into(), timer_task);
     }
@@ -2,3 +2,4 @@ pub mod auto_backup;
 pub mod lightweight;
 pub mod signal;
 pub mod sysinfo;
+pub mod traffic_stats;
@@ -0,0 +1,253 @@
+use crate::{
+    config::Config,
+    core::{
+        handle,
+        traffic_store::{self, ConnectionSample, DATE_FORMAT, DayTraffic, Sampler, Usage},
+    },
+    logging,
+    module::lightweight::is_in_lightweight_mode,
+    process::AsyncHandler,
+    utils::{
+        logging::Type,
+        notification::{NotificationEvent, notify_event},
+    },
+};
+use anyhow::Result;
+use chrono::{Days, Local};
+use once_cell::sync::OnceCell;
+use smartstring::alias::String;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use tokio::sync::{Mutex, watch};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+/// samples are merged into the store once per flush
+const FLUSH_EVERY_SAMPLES: u32 = 12;
+const DEFAULT_KEEP_DAYS: u32 = 90;
+const DEFAULT_QUOTA_WARN_PERCENT: u8 = 90;
+
+/// Samples the traffic of the core and keeps per day totals
+pub struct TrafficStats {
+    enabled_tx: watch::Sender<bool>,
+    runner_started: AtomicBool,
+    /// traffic sampled since the last flush, with its date
+    pending: Mutex<Option<(String, DayTraffic)>>,
+}
+
+impl TrafficStats {
+    pub fn global() -> &'static Self {
+        static INSTANCE: OnceCell<TrafficStats> = OnceCell::new();
+        INSTANCE.get_or_init(|| {
+            let (tx, _rx) = watch::channel(false);
+            Self {
+                enabled_tx: tx,
+                runner_started: AtomicBool::new(false),
+                pending: Mutex::new(None),
+            }
+        })
+    }
+
+    pub async fn init(&self) -> Result<()> {
+        self.refresh_settings().await
+    }
+
+    /// Sample while `enable_traffic_stats` is on, pausing in the lightweight mode
+    pub async fn refresh_settings(&self) -> Result<()> {
+        let enabled = Config::verge()
+            .await
+            .latest_arc()
+            .enable_traffic_stats
+            .unwrap_or(false)
+            && !is_in_lightweight_mode();
+        let _ = self.enabled_tx.send(enabled);
+        if enabled && !self.runner_started.swap(true, Ordering::SeqCst) {
+            let mut rx = self.enabled_tx.subscribe();
+            AsyncHandler::spawn(move || async move {
+                Self::run_sampler(&mut rx).await;
+            });
+        }
+        Ok(())
+    }
+
+    async fn run_sampler(rx: &mut watch::Receiver<bool>) {
+        let mut sampler = Sampler::default();
+        let mut samples = 0;
+        loop {
+            if !*rx.borrow() {
+                // 关闭时写入已采样的流量，重新开启后重新建立基线
+                Self::global().flush().await;
+                sampler = Sampler::default();
+                if rx.changed().await.is_err() {
+                    break;
+                }
+                continue;
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(SAMPLE_INTERVAL) => {
+                    Self::global().sample(&mut sampler).await;
+                    samples += 1;
+                    if samples >= FLUSH_EVERY_SAMPLES {
+                        samples = 0;
+                        Self::global().flush().await;
+                    }
+                }
+                changed = rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn sample(&self, sampler: &mut Sampler) {
+        let profile = Config::profiles()
+            .await
+            .latest_arc()
+            .get_current()
+            .cloned()
+            .unwrap_or_default();
+
+        let delta = {
+            let mihomo = handle::Handle::mihomo().await;
+            let connections = match mihomo.get_connections().await {
+                Ok(connections) => connections,
+                Err(e) => {
+                    logging!(debug, Type::Core, "Failed to sample traffic: {e}");
+                    return;
+                }
+            };
+            drop(mihomo);
+            let totals = Usage {
+                up: connections.upload_total,
+                down: connections.download_total,
+            };
+            let samples =
+                connections
+                    .connections
+                    .iter()
+                    .flatten()
+                    .map(|connection| ConnectionSample {
+                        id: &connection.id,
+                        usage: Usage {
+                            up: connection.upload,
+                            down: connection.download,
+                        },
+                        chains: &connection.chains,
+                        rule: &connection.rule,
+                        rule_payload: &connection.rule_payload,
+                    });
+            sampler.sample(&profile, totals, samples)
+        };
+        if delta.is_empty() {
+            return;
+        }
+
+        let today: String = Local::now().format(DATE_FORMAT).to_string().into();
+        let finished = {
+            let mut pending = self.pending.lock().await;
+            match pending.as_mut() {
+                Some((date, traffic)) if *date == today => {
+                    traffic.merge(&delta);
+                    None
+                }
+                // 跨天时写入前一天的流量
+                _ => pending.replace((today, delta)),
+            }
+        };
+        if let Some((date, traffic)) = finished {
+            Self::store(&date, &traffic).await;
+        }
+    }
+
+    async fn flush(&self) {
+        let pending = self.pending.lock().await.take();
+        if let Some((date, traffic)) = pending {
+            Self::store(&date, &traffic).await;
+        }
+    }
+
+    async fn store(date: &str, traffic: &DayTraffic) {
+        if let Err(e) = traffic_store::merge_day(date, traffic).await {
+            logging!(warn, Type::Core, "Failed to store traffic of {date}: {e}");
+            return;
+        }
+
+        let keep_days = Config::verge()
+            .await
+            .latest_arc()
+            .traffic_stats_days
+            .unwrap_or(DEFAULT_KEEP_DAYS);
+        if let Some(cutoff) = Local::now()
+            .date_naive()
+            .checked_sub_days(Days::new(keep_days.into()))
+            && let Err(e) = traffic_store::prune(cutoff).await
+        {
+            logging!(warn, Type::Core, "Failed to prune traffic stats: {e}");
+        }
+
+        if let Err(e) = Self::check_quota(traffic).await {
+            logging!(warn, Type::Core, "Failed to check traffic quota: {e}");
+        }
+    }
+
+    /// Warn once per subscription update when the estimated usage of a profile reaches the threshold
+    async fn check_quota(traffic: &DayTraffic) -> Result<()> {
+        let threshold = Config::verge()
+            .await
+            .latest_arc()
+            .traffic_quota_warn_percent
+            .unwrap_or(DEFAULT_QUOTA_WARN_PERCENT);
+        let mut quota = traffic_store::load_quota().await?;
+        let mut warnings = Vec::new();
+        {
+            let profiles = Config::profiles().await.latest_arc();
+            for (uid, usage) in &traffic.profiles {
+                let Ok(item) = profiles.get_item(uid) else {
+                    continue;
+                };
+                let updated = item.updated.unwrap_or(0);
+                let state = quota.entry(uid.clone()).or_default();
+                if state.updated != updated {
+                    *state = traffic_store::QuotaState {
+                        updated,
+                        ..Default::default()
+                    };
+                }
+                state.usage.add(*usage);
+
+                let Some(percent) = item
+                    .extra
+                    .as_ref()
+                    .and_then(|extra| traffic_store::quota_percent(extra, state.usage))
+                else {
+                    continue;
+                };
+                if threshold > 0 && percent >= u64::from(threshold) && !state.warned {
+                    state.warned = true;
+                    let name = item.name.clone().unwrap_or_else(|| uid.clone());
+                    warnings.push((name, percent));
+                }
+            }
+            quota.retain(|uid, _| profiles.get_item(uid).is_ok());
+        }
+        traffic_store::save_quota(&quota).await?;
+
+        for (name, percent) in warnings {
+            logging!(
+                warn,
+                Type::Core,
+                "Profile {name} has used about {percent}% of its traffic quota"
+            );
+            notify_event(NotificationEvent::TrafficQuota {
+                profile: &name,
+                percent,
+            })
+            .await;
+        }
+        Ok(())
+    }
+}
@@ -26,6 +26,8 @@ pub static PORTABLE_FLAG: OnceCell<bool> = OnceCell::new();
 pub static CLASH_CONFIG: &str = "config.yaml";
 pub static VERGE_CONFIG: &str = "verge.yaml";
 pub static PROFILE_YAML: &str = "profiles.yaml";
+pub static UPDATE_SCHEDULE: &str = "update_schedule.yaml";
+pub static UNLOCK_HISTORY: &str = "unlock_history.json";
 
 /// init portable flag
 pub fn init_portable_flag() -> Result<()> {
@@ -153,6 +155,14 @@ pub fn profiles_path() -> Result<PathBuf> {
     Ok(app_home_dir()?.join(PROFILE_YAML))
 }
 
+pub fn update_schedule_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(UPDATE_SCHEDULE))
+}
+
+pub fn unlock_history_path() -> Result<PathBuf> {
+    Ok(app_home_dir()?.join(UNLOCK_HISTORY))
+}
+
 #[cfg(target_os = "macos")]
 pub fn service_path() -> Result<PathBuf> {
     let res_dir = app_resources_dir()?;
@@ -12,6 +12,7 @@ pub mod logging;
 pub mod network;
 pub mod notification;
 pub mod resolve;
+pub mod rest_api;
 pub mod server;
 pub mod singleton;
 pub mod tmpl;
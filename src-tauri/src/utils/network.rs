@@ -8,7 +8,7 @@ use isahc::{
     config::RedirectPolicy,
     http::{
         StatusCode, Uri,
-        header::{HeaderMap, HeaderValue, USER_AGENT},
+        header::{HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, USER_AGENT},
     },
 };
 use smartstring::alias::String;
@@ -47,11 +47,20 @@ impl HttpResponse {
     }
 }
 
+/// Validators of a cached response, sent as `If-None-Match` and `If-Modified-Since`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheValidators<'a> {
+    pub etag: Option<&'a str>,
+    pub last_modified: Option<&'a str>,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum ProxyType {
     None,
     Localhost,
     System,
+    /// a local HTTP proxy on this port
+    Port(u16),
 }
 
 pub struct NetworkManager {
@@ -168,6 +177,7 @@ impl NetworkManager {
                 let proxy_scheme = format!("http://127.0.0.1:{port}");
                 proxy_scheme.parse::<Uri>().ok()
             }
+            ProxyType::Port(port) => format!("http://127.0.0.1:{port}").parse::<Uri>().ok(),
             ProxyType::System => {
                 if let Ok(p @ Sysproxy { enable: true, .. }) = Sysproxy::get_system_proxy() {
                     let proxy_scheme = format!("http://{}:{}", p.host, p.port);
@@ -200,6 +210,27 @@ impl NetworkManager {
         timeout_secs: Option<u64>,
         user_agent: Option<String>,
         accept_invalid_certs: bool,
+    ) -> Result<HttpResponse> {
+        self.get_conditional(
+            url,
+            proxy_type,
+            timeout_secs,
+            user_agent,
+            accept_invalid_certs,
+            CacheValidators::default(),
+        )
+        .await
+    }
+
+    /// Like `get_with_interrupt`, answered with `304 Not Modified` when `validators` still match
+    pub async fn get_conditional(
+        &self,
+        url: &str,
+        proxy_type: ProxyType,
+        timeout_secs: Option<u64>,
+        user_agent: Option<String>,
+        accept_invalid_certs: bool,
+        validators: CacheValidators<'_>,
     ) -> Result<HttpResponse> {
         if self.should_reset_clients().await {
             self.reset_clients().await;
@@ -218,6 +249,12 @@ impl NetworkManager {
                 HeaderValue::from_str(&format!("Basic {}", encoded))?,
             );
         }
+        if let Some(etag) = validators.etag {
+            extra_headers.insert(IF_NONE_MATCH, HeaderValue::from_str(etag)?);
+        }
+        if let Some(last_modified) = validators.last_modified {
+            extra_headers.insert(IF_MODIFIED_SINCE, HeaderValue::from_str(last_modified)?);
+        }
 
         let clean_url = {
             let mut no_auth = parsed.clone();
@@ -9,6 +9,19 @@ pub enum NotificationEvent<'a> {
     SystemProxyToggled,
     TunModeToggled,
     LightweightModeEntered,
+    UnlockLost {
+        target: &'a str,
+        status: &'a str,
+    },
+    UnlockRegionChanged {
+        target: &'a str,
+        from: &'a str,
+        to: &'a str,
+    },
+    TrafficQuota {
+        profile: &'a str,
+        percent: u64,
+    },
     AppQuit,
     #[cfg(target_os = "macos")]
     AppHidden,
@@ -54,6 +67,28 @@ pub async fn notify_event<'a>(event: NotificationEvent<'a>) {
             let body = rust_i18n::t!("notifications.lightweightModeEntered.body").to_string();
             notify(&title, &body);
         }
+        NotificationEvent::UnlockLost { target, status } => {
+            let title = rust_i18n::t!("notifications.unlockLost.title").to_string();
+            let body = rust_i18n::t!("notifications.unlockLost.body")
+                .replace("{target}", target)
+                .replace("{status}", status);
+            notify(&title, &body);
+        }
+        NotificationEvent::UnlockRegionChanged { target, from, to } => {
+            let title = rust_i18n::t!("notifications.unlockRegionChanged.title").to_string();
+            let body = rust_i18n::t!("notifications.unlockRegionChanged.body")
+                .replace("{target}", target)
+                .replace("{from}", from)
+                .replace("{to}", to);
+            notify(&title, &body);
+        }
+        NotificationEvent::TrafficQuota { profile, percent } => {
+            let title = rust_i18n::t!("notifications.trafficQuota.title").to_string();
+            let body = rust_i18n::t!("notifications.trafficQuota.body")
+                .replace("{profile}", profile)
+                .replace("{percent}", &percent.to_string());
+            notify(&title, &body);
+        }
         NotificationEvent::AppQuit => {
             let title = rust_i18n::t!("notifications.appQuit.title").to_string();
             let body = rust_i18n::t!("notifications.appQuit.body").to_string();
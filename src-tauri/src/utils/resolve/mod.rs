@@ -10,7 +10,10 @@ use crate::{
         tray::Tray,
     },
     logging, logging_error,
-    module::{auto_backup::AutoBackupManager, lightweight::auto_lightweight_boot, signal},
+    module::{
+        auto_backup::AutoBackupManager, lightweight::auto_lightweight_boot, signal,
+        traffic_stats::TrafficStats,
+    },
     process::AsyncHandler,
     utils::{init, logging::Type, server, window_manager::WindowManager, debug_startup::with_timeout},
 };
@@ -253,6 +256,8 @@ pub fn resolve_setup_async() {
             init_hotkey(),
             init_auto_lightweight_boot(),
             init_auto_backup(),
+            init_pac_lan_server(),
+            init_traffic_stats(),
         );
         
         let join_elapsed = join_start.elapsed();
@@ -340,6 +345,14 @@ pub(super) async fn init_auto_backup() {
     logging_error!(Type::Setup, AutoBackupManager::global().init().await);
 }
 
+pub(super) async fn init_pac_lan_server() {
+    logging_error!(Type::Setup, server::restart_pac_lan_server().await);
+}
+
+pub(super) async fn init_traffic_stats() {
+    logging_error!(Type::Setup, TrafficStats::global().init().await);
+}
+
 pub(super) fn init_signal() {
     logging!(info, Type::Setup, "Initializing signal handlers...");
     signal::register();
@@ -0,0 +1,404 @@
+use crate::{
+    cmd,
+    config::{Config, IVerge},
+    core::{Timer, handle},
+    feat, logging,
+    utils::logging::Type,
+};
+use anyhow::{Result, anyhow, bail};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use smartstring::alias::String;
+use warp::{
+    Filter,
+    http::{Response, StatusCode},
+};
+
+const CLASH_MODES: [&str; 3] = ["rule", "global", "direct"];
+
+/// A request to the local REST API, authenticated with `Authorization: Bearer <rest_api_token>`
+#[derive(Debug)]
+enum ApiRequest {
+    Status,
+    SwitchProfile(String),
+    UpdateProfile(String),
+    SystemProxy(bool),
+    Tun(bool),
+    Mode(String),
+    /// `local`, `webdav` or the uid of a backup target
+    Backup(String),
+}
+
+#[derive(Deserialize)]
+struct ProfileBody {
+    uid: String,
+}
+
+#[derive(Deserialize)]
+struct EnabledBody {
+    enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct ModeBody {
+    mode: String,
+}
+
+#[derive(Deserialize)]
+struct BackupQuery {
+    target: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Status {
+    version: &'static str,
+    mode: Option<String>,
+    system_proxy: bool,
+    tun_mode: bool,
+    current_profile: Option<String>,
+    profiles: Vec<ProfileStatus>,
+}
+
+#[derive(Serialize)]
+struct ProfileStatus {
+    uid: String,
+    name: Option<String>,
+    updated: Option<usize>,
+    next_update: Option<i64>,
+    stale: bool,
+}
+
+#[derive(Serialize)]
+struct ApiResponse<T: Serialize> {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// upper bound of a request body, the API only takes small JSON objects
+const MAX_BODY_SIZE: u64 = 4 * 1024;
+
+/// `/api/...` routes of the embedded server
+///
+/// - `GET /api/status`
+/// - `PUT /api/profiles/current` with `{"uid": "..."}`
+/// - `POST /api/profiles/<uid>/update`
+/// - `PUT /api/system-proxy` and `PUT /api/tun` with `{"enabled": true}`
+/// - `PUT /api/mode` with `{"mode": "rule"}`
+/// - `POST /api/backups?target=local`
+pub fn routes()
+-> impl Filter<Extract = (Response<std::string::String>,), Error = warp::Rejection> + Clone {
+    let status = warp::path!("status")
+        .and(warp::get())
+        .map(|| ApiRequest::Status);
+    let switch_profile = warp::path!("profiles" / "current")
+        .and(warp::put())
+        .and(json_body())
+        .map(|body: ProfileBody| ApiRequest::SwitchProfile(body.uid));
+    let update_profile = warp::path!("profiles" / std::string::String / "update")
+        .and(warp::post())
+        .map(|uid: std::string::String| ApiRequest::UpdateProfile(uid.into()));
+    let system_proxy = warp::path!("system-proxy")
+        .and(warp::put())
+        .and(json_body())
+        .map(|body: EnabledBody| ApiRequest::SystemProxy(body.enabled));
+    let tun = warp::path!("tun")
+        .and(warp::put())
+        .and(json_body())
+        .map(|body: EnabledBody| ApiRequest::Tun(body.enabled));
+    let mode = warp::path!("mode")
+        .and(warp::put())
+        .and(json_body())
+        .map(|body: ModeBody| ApiRequest::Mode(body.mode));
+    let backup = warp::path!("backups")
+        .and(warp::post())
+        .and(warp::query::<BackupQuery>())
+        .map(|query: BackupQuery| {
+            ApiRequest::Backup(query.target.unwrap_or_else(|| "local".into()))
+        });
+
+    // 先校验令牌，未授权的请求不会读取请求体
+    warp::path("api")
+        .and(authorized())
+        .and(
+            status
+                .or(switch_profile)
+                .unify()
+                .or(update_profile)
+                .unify()
+                .or(system_proxy)
+                .unify()
+                .or(tun)
+                .unify()
+                .or(mode)
+                .unify()
+                .or(backup)
+                .unify(),
+        )
+        .and_then(handle_request)
+        .recover(recover_rejection)
+        .unify()
+}
+
+/// A request turned away before its route runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ApiRejection {
+    status: StatusCode,
+    error: &'static str,
+}
+
+impl warp::reject::Reject for ApiRejection {}
+
+impl ApiRejection {
+    fn reply(self) -> Response<std::string::String> {
+        error_reply(self.status, self.error)
+    }
+}
+
+/// Rejects the request unless the API is enabled and the bearer token matches
+fn authorized() -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::header::optional::<std::string::String>("authorization")
+        .and_then(|authorization: Option<std::string::String>| async move {
+            let (enabled, token) = {
+                let verge = Config::verge().await.latest_arc();
+                (
+                    verge.rest_api_enabled.unwrap_or(false),
+                    verge.rest_api_token.clone(),
+                )
+            };
+            match reject(enabled, token.as_deref(), authorization.as_deref()) {
+                Some(rejection) => Err(warp::reject::custom(rejection)),
+                None => Ok(()),
+            }
+        })
+        .untuple_one()
+}
+
+/// A JSON body of at most [`MAX_BODY_SIZE`] bytes
+fn json_body<T: DeserializeOwned + Send>()
+-> impl Filter<Extract = (T,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(MAX_BODY_SIZE).and(warp::body::json())
+}
+
+async fn recover_rejection(
+    rejection: warp::Rejection,
+) -> Result<Response<std::string::String>, warp::Rejection> {
+    match rejection.find::<ApiRejection>() {
+        Some(rejection) => Ok(rejection.reply()),
+        None => Err(rejection),
+    }
+}
+
+async fn handle_request(
+    request: ApiRequest,
+) -> Result<Response<std::string::String>, warp::Rejection> {
+    logging!(info, Type::Network, "[REST API] {:?}", request);
+    let result = match request {
+        ApiRequest::Status => to_json(&status().await),
+        ApiRequest::SwitchProfile(uid) => switch_profile(uid).await,
+        ApiRequest::UpdateProfile(uid) => feat::update_profile(&uid, None, true, true)
+            .await
+            .and_then(|()| to_json(&())),
+        ApiRequest::SystemProxy(enabled) => {
+            patch_verge(IVerge {
+                enable_system_proxy: Some(enabled),
+                ..IVerge::default()
+            })
+            .await
+        }
+        ApiRequest::Tun(enabled) => {
+            patch_verge(IVerge {
+                enable_tun_mode: Some(enabled),
+                ..IVerge::default()
+            })
+            .await
+        }
+        ApiRequest::Mode(mode) => change_mode(mode).await,
+        ApiRequest::Backup(target) => create_backup(&target).await,
+    };
+
+    Ok(match result {
+        Ok(data) => reply(data),
+        Err(err) => {
+            logging!(warn, Type::Network, "Warning: [REST API] 请求失败: {err}");
+            error_reply(StatusCode::BAD_REQUEST, &err.to_string())
+        }
+    })
+}
+
+/// Why a request may not reach the API, if it may not
+fn reject(enabled: bool, token: Option<&str>, authorization: Option<&str>) -> Option<ApiRejection> {
+    if !enabled {
+        return Some(ApiRejection {
+            status: StatusCode::FORBIDDEN,
+            error: "REST API is disabled",
+        });
+    }
+    if !token.is_some_and(|token| is_authorized(authorization, token)) {
+        return Some(ApiRejection {
+            status: StatusCode::UNAUTHORIZED,
+            error: "invalid token",
+        });
+    }
+    None
+}
+
+/// Compares the bearer token in constant time
+fn is_authorized(authorization: Option<&str>, token: &str) -> bool {
+    let Some(given) = authorization.and_then(|value| value.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    !token.is_empty()
+        && given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn status() -> Status {
+    let verge = Config::verge().await.latest_arc();
+    let mode = Config::clash()
+        .await
+        .latest_arc()
+        .0
+        .get("mode")
+        .and_then(|mode| mode.as_str())
+        .map(Into::into);
+    let profiles = Config::profiles().await.latest_arc();
+
+    let mut items = vec![];
+    for item in profiles.items.iter().flatten() {
+        let (Some(uid), Some("remote" | "local")) = (item.uid.as_ref(), item.itype.as_deref())
+        else {
+            continue;
+        };
+        items.push(ProfileStatus {
+            uid: uid.clone(),
+            name: item.name.clone(),
+            updated: item.updated,
+            next_update: Timer::global().get_next_update_time(uid).await,
+            stale: item.stale.unwrap_or(false),
+        });
+    }
+
+    Status {
+        version: env!("CARGO_PKG_VERSION"),
+        mode,
+        system_proxy: verge.enable_system_proxy.unwrap_or(false),
+        tun_mode: verge.enable_tun_mode.unwrap_or(false),
+        current_profile: profiles.current.clone(),
+        profiles: items,
+    }
+}
+
+async fn switch_profile(uid: String) -> Result<serde_json::Value> {
+    if !cmd::patch_profiles_config_by_profile_index(uid)
+        .await
+        .map_err(|err| anyhow!(err))?
+    {
+        bail!("another profile switch is in progress");
+    }
+    to_json(&())
+}
+
+async fn patch_verge(patch: IVerge) -> Result<serde_json::Value> {
+    feat::patch_verge(&patch, false).await?;
+    handle::Handle::refresh_verge();
+    to_json(&())
+}
+
+async fn change_mode(mode: String) -> Result<serde_json::Value> {
+    if !CLASH_MODES.contains(&mode.as_str()) {
+        bail!("unknown clash mode \"{mode}\"");
+    }
+    feat::change_clash_mode(mode).await;
+    to_json(&())
+}
+
+async fn create_backup(target: &str) -> Result<serde_json::Value> {
+    match target {
+        "local" => feat::create_local_backup().await?,
+        "webdav" => feat::create_backup_and_upload_webdav().await?,
+        uid => feat::create_target_backup(uid.into()).await?,
+    }
+    to_json(&())
+}
+
+fn to_json<T: Serialize>(data: &T) -> Result<serde_json::Value> {
+    Ok(serde_json::to_value(data)?)
+}
+
+fn reply(data: serde_json::Value) -> Response<std::string::String> {
+    let body = ApiResponse {
+        ok: true,
+        data: Some(data).filter(|data| !data.is_null()),
+        error: None,
+    };
+    json_response(StatusCode::OK, &body)
+}
+
+fn error_reply(status: StatusCode, error: &str) -> Response<std::string::String> {
+    let body = ApiResponse::<()> {
+        ok: false,
+        data: None,
+        error: Some(error.into()),
+    };
+    json_response(status, &body)
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<std::string::String> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(body).unwrap_or_default())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized(Some("Bearer secret"), "secret"));
+        assert!(!is_authorized(None, "secret"));
+        assert!(!is_authorized(Some("Basic secret"), "secret"));
+        assert!(!is_authorized(Some("bearer secret"), "secret"));
+        assert!(!is_authorized(Some("Bearer secre"), "secret"));
+        assert!(!is_authorized(Some("Bearer secrets"), "secret"));
+        assert!(!is_authorized(Some("Bearer secreT"), "secret"));
+        assert!(!is_authorized(Some("Bearer "), ""));
+    }
+
+    #[test]
+    fn test_reject() {
+        let status = |enabled, token, authorization| {
+            reject(enabled, token, authorization).map(|rejection| rejection.status)
+        };
+        assert_eq!(
+            status(false, Some("secret"), Some("Bearer secret")),
+            Some(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            status(true, None, Some("Bearer ")),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            status(true, Some("secret"), None),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(
+            status(true, Some("secret"), Some("Bearer wrong")),
+            Some(StatusCode::UNAUTHORIZED)
+        );
+        assert_eq!(status(true, Some("secret"), Some("Bearer secret")), None);
+
+        let response = reject(true, Some("secret"), None)
+            .map(ApiRejection::reply)
+            .unwrap_or_default();
+        assert_eq!(response.body(), r#"{"ok":false,"error":"invalid token"}"#);
+    }
+}
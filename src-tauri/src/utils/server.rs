@@ -1,20 +1,26 @@
 use super::resolve;
 use crate::{
-    config::{Config, DEFAULT_PAC, IVerge},
+    config::{Config, IVerge},
+    constants::network::ports,
+    core::pac,
     logging, logging_error,
     module::lightweight,
     process::AsyncHandler,
-    utils::{logging::Type, window_manager::WindowManager},
+    utils::{help, logging::Type, rest_api, window_manager::WindowManager},
 };
 use anyhow::{Result, bail};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use port_scanner::local_port_available;
 use reqwest::ClientBuilder;
+use serde_yaml_ng::Value;
 use smartstring::alias::String;
 use std::time::Duration;
 use tokio::sync::oneshot;
-use warp::Filter as _;
+use warp::{
+    Filter,
+    http::{Response, StatusCode, uri::Authority},
+};
 
 #[derive(serde::Deserialize, Debug)]
 struct QueryParam {
@@ -23,6 +29,8 @@ struct QueryParam {
 
 // 关闭 embedded server 的信号发送端
 static SHUTDOWN_SENDER: OnceCell<Mutex<Option<oneshot::Sender<()>>>> = OnceCell::new();
+// 关闭局域网 PAC 服务的信号发送端
+static PAC_LAN_SENDER: OnceCell<Mutex<Option<oneshot::Sender<()>>>> = OnceCell::new();
 
 /// check whether there is already exists
 pub async fn check_singleton() -> Result<()> {
@@ -82,8 +90,7 @@ pub async fn check_singleton() -> Result<()> {
     Ok(())
 }
 
-/// The embed server only be used to implement singleton process
-/// maybe it can be used as pac server later
+/// The embed server implements the singleton process, serves the pac script and the local REST API
 pub fn embed_server() {
     let (shutdown_tx, shutdown_rx) = oneshot::channel();
     #[allow(clippy::expect_used)]
@@ -106,28 +113,6 @@ pub fn embed_server() {
             ))
         });
 
-        let verge_config = Config::verge().await;
-        let clash_config = Config::clash().await;
-
-        let pac_content = verge_config
-            .data_arc()
-            .pac_file_content
-            .clone()
-            .unwrap_or_else(|| DEFAULT_PAC.into());
-
-        let pac_port = verge_config
-            .data_arc()
-            .verge_mixed_port
-            .unwrap_or_else(|| clash_config.data_arc().get_mixed_port());
-
-        let pac = warp::path!("commands" / "pac").map(move || {
-            let processed_content = pac_content.replace("%mixed-port%", &format!("{pac_port}"));
-            warp::http::Response::builder()
-                .header("Content-Type", "application/x-ns-proxy-autoconfig")
-                .body(processed_content)
-                .unwrap_or_default()
-        });
-
         // Use map instead of and_then to avoid Send issues
         let scheme = warp::path!("commands" / "scheme")
             .and(warp::query::<QueryParam>())
@@ -141,7 +126,7 @@ pub fn embed_server() {
                 ))
             });
 
-        let commands = visible.or(scheme).or(pac);
+        let commands = visible.or(scheme).or(pac_route()).or(rest_api::routes());
         warp::serve(commands)
             .bind(([127, 0, 0, 1], port))
             .await
@@ -160,4 +145,122 @@ pub fn shutdown_embedded_server() {
     {
         sender.send(()).ok();
     }
+    stop_pac_lan_server();
+}
+
+/// `/commands/pac`, answered with `304 Not Modified` while the client's copy is current
+fn pac_route()
+-> impl Filter<Extract = (Response<std::string::String>,), Error = warp::Rejection> + Clone {
+    warp::path!("commands" / "pac")
+        .and(warp::header::optional::<std::string::String>("host"))
+        .and(warp::header::optional::<std::string::String>(
+            "if-none-match",
+        ))
+        .and_then(pac_response)
+}
+
+async fn pac_response(
+    host: Option<std::string::String>,
+    if_none_match: Option<std::string::String>,
+) -> Result<Response<std::string::String>, warp::Rejection> {
+    // LAN 设备通过哪个地址访问就让它连接哪个地址的代理端口
+    let host: String = host
+        .and_then(|host| host.parse::<Authority>().ok())
+        .map_or_else(|| "127.0.0.1".into(), |authority| authority.host().into());
+    let content = pac::render(&host).await;
+    let max_age = Config::verge()
+        .await
+        .latest_arc()
+        .pac_cache_max_age
+        .unwrap_or(pac::DEFAULT_CACHE_MAX_AGE);
+    let etag = format!("\"{}\"", help::sha256_hex(content.as_bytes()));
+
+    let builder = Response::builder()
+        .header("Cache-Control", format!("max-age={max_age}"))
+        .header("ETag", &etag);
+    if if_none_match.is_some_and(|value| value == etag) {
+        return Ok(builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(std::string::String::new())
+            .unwrap_or_default());
+    }
+    Ok(builder
+        .header("Content-Type", "application/x-ns-proxy-autoconfig")
+        .body(content.into())
+        .unwrap_or_default())
+}
+
+/// Serve the pac script on all interfaces while `pac_lan_enabled` is on,
+/// so devices on the LAN can use this machine as their proxy
+pub async fn restart_pac_lan_server() -> Result<()> {
+    let was_running = stop_pac_lan_server();
+    let (enabled, port) = {
+        let verge = Config::verge().await.latest_arc();
+        (
+            verge.pac_lan_enabled.unwrap_or(false),
+            verge.pac_lan_port.unwrap_or(ports::DEFAULT_PAC_LAN),
+        )
+    };
+    if !enabled {
+        return Ok(());
+    }
+
+    // 等待旧的服务释放端口
+    let mut available = local_port_available(port);
+    for _ in 0..10 {
+        if available || !was_running {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        available = local_port_available(port);
+    }
+    if !available {
+        bail!("pac LAN port {port} is already in use");
+    }
+
+    let allow_lan = Config::clash()
+        .await
+        .latest_arc()
+        .0
+        .get("allow-lan")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    if !allow_lan {
+        logging!(
+            warn,
+            Type::Network,
+            "Warning: 内核未开启 allow-lan，局域网设备无法连接代理端口"
+        );
+    }
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    *PAC_LAN_SENDER.get_or_init(|| Mutex::new(None)).lock() = Some(shutdown_tx);
+    AsyncHandler::spawn(move || async move {
+        warp::serve(pac_route())
+            .bind(([0, 0, 0, 0], port))
+            .await
+            .graceful(async {
+                shutdown_rx.await.ok();
+            })
+            .run()
+            .await;
+    });
+    logging!(
+        info,
+        Type::Network,
+        "局域网 PAC 服务已启动: 0.0.0.0:{}",
+        port
+    );
+    Ok(())
+}
+
+/// Returns whether the server was running
+fn stop_pac_lan_server() -> bool {
+    match PAC_LAN_SENDER.get().and_then(|sender| sender.lock().take()) {
+        Some(sender) => {
+            sender.send(()).ok();
+            true
+        }
+        None => false,
+    }
 }
@@ -58,3 +58,23 @@ append: []
 
 delete: []
 ";
+
+/// enhanced profile
+pub const ITEM_RULE_PROVIDERS: &str = "# Profile Enhancement Rule Providers Template for RV Verge
+
+prepend: {}
+
+append: {}
+
+delete: []
+";
+
+/// enhanced profile
+pub const ITEM_PROXY_PROVIDERS: &str = "# Profile Enhancement Proxy Providers Template for RV Verge
+
+prepend: {}
+
+append: {}
+
+delete: []
+";